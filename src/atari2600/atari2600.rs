@@ -1,43 +1,131 @@
 //! Main container for the Atari2600
 //!
-//! Holds all of the components/sub-systems and settings for the Atari. Although it's also coupled to the output (ie display and audio out).
+//! Holds the machine (see 'machine') and settings for the Atari, along with the SDL output (ie display and audio out)
+//! and the optional tools (debugger, profiler, rewind, movies).
 //!
-//! Initially structures so that it would 'work', there's definitely some more
-//! improvements to make interaction a bit more 'natural'.  Currently only
-//! designed to support a single instance of an atari.
+//! Currently only designed to support a single instance of an atari.
 //!
-//! TODO: Improve/change flow to make it a bit more 'natural'.  
-//! Maybe something like:
-//!  - Default Construction
-//!  - Set Configuration/Setting/Switches (ie all of the flags screen/realtime)
-//!  - Insert cartridge (currently/initially this was too coupled with construction).
-//!  - Power on (possibly pair with reset, possibly have an 'on/off' flag).
-//!  - Run (re-entry)
-//!  - Power off.
-//!
-//!  -   Consider allowing 'set confugration' at differeint/any time (ie real-time toggle).
+//! TODO: Consider allowing 'set confugration' at differeint/any time (ie real-time toggle).
 
 use super::audio::sound;
+use super::capture;
 use super::clocks;
 use super::cpu;
+use super::debugger;
+use super::gdb;
 use super::graphics;
+use super::headless;
 use super::inputs;
+use super::machine;
 use super::memory;
-use super::ports;
+use super::movie;
+use super::profiler;
+use super::rewind;
+use super::screenshot;
+use super::state;
+use super::symbols;
 
 use sdl2::pixels;
 use sdl2::rect;
 use sdl2::render;
 use sdl2::video;
+use std::io::{self, Write};
+use std::path;
+use std::thread;
+use std::time;
+
+/// Optional debugging/analysis tools, hooked around each CPU step.
+#[derive(Default)]
+struct Tools {
+    debugger: Option<debugger::Debugger>,
+    gdb_stub: Option<gdb::GdbStub>,
+    profiler: Option<profiler::Profiler>,
+}
+
+impl Tools {
+    /// Step the machine, via the tools (if attached). Returns None if the debugger/GDB stub has quit, otherwise if
+    /// it's the end of a frame.
+    fn step(&mut self, machine: &mut machine::Machine) -> Option<bool> {
+        if let Some(debugger) = self.debugger.as_mut() {
+            if !debugger.before_step(machine.core_mut(), &mut io::stdin().lock(), &mut io::stdout()) {
+                return None;
+            }
+        }
+        if let Some(gdb_stub) = self.gdb_stub.as_mut() {
+            if !gdb_stub.before_step(machine.core_mut()) {
+                return None;
+            }
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.before_step(machine.core_mut());
+        }
+
+        let end_of_frame = machine.step();
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.after_step(machine.core_mut());
+        }
+        Some(end_of_frame)
+    }
+}
+
+/// Per frame snapshots (for rewind), movie inputs, screenshots and audio/video capture.
+struct Frames {
+    // Frames run since power-on.
+    count: u64,
+    rewind: Option<rewind::Rewind>,
+    rewinding: bool,
+    movie: Option<movie::Movie>,
+    screenshots: screenshot::Screenshots,
+    capture: Option<capture::Capture>,
+}
+
+impl Frames {
+    /// Screenshot, snapshot for rewind and record/replay the movie inputs (the live inputs are recorded), once a frame.
+    fn end_of_frame(&mut self, machine: &mut machine::Machine, live_input: inputs::Input) {
+        self.count += 1;
+        self.screenshots.end_of_frame(machine, self.count);
+        if let Some(capture) = self.capture.as_mut() {
+            if let Err(e) = capture.frame(machine) {
+                println!("Unable to write the capture to {}, stopping: {}", capture.name(), e);
+                self.capture = None;
+                machine.set_audio_capture(false);
+            }
+        }
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.push(state::save(machine.core()));
+        }
+        if let Some(movie) = self.movie.as_mut() {
+            let mut input = live_input;
+            movie.frame(&mut input);
+            machine.set_input(input);
+        }
+    }
+
+    /// Step back to the previous frame (stays at the oldest frame once the rewind buffer is empty).
+    fn rewind_frame(&mut self, machine: &mut machine::Machine) {
+        if let Some(snapshot) = self.rewind.as_mut().and_then(|rewind| rewind.pop()) {
+            if let Err(e) = state::restore(machine.core_mut(), &snapshot) {
+                println!("Unable to rewind: {}", e);
+            }
+        }
+    }
+}
 
 /// Main container and control point for the Atari Emulator.
 pub struct Atari2600 {
-    core: cpu::core::Core,
-    debug: bool,
-    realtime: bool,
+    machine: machine::Machine,
     stop_clock: clocks::ClockType,
     fullscreen: bool,
     counter: u32,
+    tools: Tools,
+    // Save/load state hotkey file.
+    state_file: String,
+    // Capture hotkey files are named '<cartridge>.<frame>.y4m/.wav'.
+    cartridge_name: String,
+    frames: Frames,
+    // The keyboard state, the inputs used by the machine unless a movie is replaying.
+    joystick: inputs::Joystick,
     pub powered: bool,
 
     // These appear as 'Options' to simplify delayed initialisation.
@@ -49,19 +137,66 @@ pub struct Atari2600 {
 impl Atari2600 {
     const DISPLAY_UPDATES_PER_KEY_EVENT: u32 = 10000; // Number of display updates per key press event. (reduces texture creation overhead).
     const CPU_STEPS_PER_AUDIO_UPDATE: u32 = 50; // Number of times to step the CPU before updating the audio.
+    const REWIND_FRAME_MS: u64 = 1000 / 60; // Time each frame is shown for when rewinding.
 
-    pub fn build_atari2600(cartridge_name: &String, cartridge_type: &memory::cartridge::CartridgeType, debug: bool, realtime: bool, pal_palette: bool) -> cpu::core::Core {
-        let clock = clocks::Clock::new();
-        let pc_state = cpu::pc_state::PcState::new();
-        let stella = graphics::stella::Stella::new(debug, realtime, pal_palette);
-        let riot = memory::riot::Riot::new();
-        let memory = memory::memory::Memory::new(cartridge_name, cartridge_type, Box::new(stella), Box::new(riot));
-        let ports = ports::Ports::new();
+    pub fn build_atari2600(cartridge_name: &str, cartridge_type: &memory::cartridge::CartridgeType, debug: bool, realtime: bool, pal_palette: bool) -> cpu::core::Core {
+        let mut machine = machine::Machine::new(machine::MachineConfig { debug, realtime, pal_palette });
+        machine.insert_cartridge(&memory::cartridge::read_cartridge(cartridge_name), cartridge_type);
+        machine.into_core()
+    }
+
+    /// Run the emulator (without display/audio output) until 'stop_clock', with and without the
+    /// instruction dispatch table, reporting the time taken for each.
+    pub fn benchmark(cartridge_name: &str, cartridge_type: &memory::cartridge::CartridgeType, stop_clock: clocks::ClockType, pal_palette: bool) {
+        let mut timings = Vec::new();
+        for (description, table_dispatch) in [("decode each op-code", false), ("dispatch table", true)] {
+            let mut core = Self::build_atari2600(cartridge_name, cartridge_type, false, false, pal_palette);
+            core.set_table_dispatch(table_dispatch);
+
+            let start = time::Instant::now();
+            while core.clock.ticks <= stop_clock {
+                core.step(false, false);
+                core.memory.stella.step_tia_sound(&core.clock);
+            }
+            let elapsed = start.elapsed();
 
-        let mut core = cpu::core::Core::new(clock, memory, pc_state, ports);
-        core.reset();
+            let cpu_clock_hz = stop_clock as f64 / cpu::pc_state::PcState::CYCLES_TO_CLOCK as f64 / elapsed.as_secs_f64();
+            println!("{:>20}: {:>8.3}s ({:.2} MHz)", description, elapsed.as_secs_f64(), cpu_clock_hz / 1_000_000.0);
+            timings.push(elapsed.as_secs_f64());
+        }
+        println!("Speed up: {:.2}x", timings[0] / timings[1]);
+    }
+
+    /// Disassemble a bank of the cartridge (to stdout), 'origin' defaults to the bank ending at 0xFFFF.
+    pub fn disassemble_bank(cartridge_name: &str, cartridge_type: &memory::cartridge::CartridgeType, bank: usize, origin: Option<u16>) -> Result<(), String> {
+        let cartridge = memory::cartridge::get_new_cartridge(cartridge_name, cartridge_type);
+        let data = cartridge.bank_data(bank).ok_or(format!("Bank {} not found, the cartridge has {} bank(s)", bank, cartridge.bank_count()))?;
+        let origin = origin.unwrap_or((0x10000 - data.len()) as u16);
+
+        let symbols = symbols::Symbols::for_cartridge(cartridge_name);
+        let read = |address: u16| data.get(address.wrapping_sub(origin) as usize).copied().unwrap_or(0);
+        let mut out = io::stdout().lock();
+        for instruction in cpu::disasm::disassemble_range(origin, origin as u32 + data.len() as u32, &read) {
+            // Stop quietly if the output is closed (ie piped to 'head').
+            if writeln!(out, "{}", instruction.line_with_symbols(&symbols)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
 
-        core
+    /// Run without SDL (display/audio), for the frames and/or until the stop clock, then write the outputs.
+    pub fn run_headless(&mut self, headless: &headless::Headless) -> io::Result<()> {
+        // Nothing to rewind to without the window (so no per frame snapshots).
+        self.frames.rewind = None;
+        let (tools, frames, input) = (&mut self.tools, &mut self.frames, self.joystick.input);
+        headless.run_with(&mut self.machine, self.stop_clock, |machine| {
+            let end_of_frame = tools.step(machine)?;
+            if end_of_frame {
+                frames.end_of_frame(machine, input);
+            }
+            Some(end_of_frame)
+        })
     }
 
     pub fn get_console_size() -> graphics::display::ConsoleSize {
@@ -91,11 +226,17 @@ impl Atari2600 {
         for event in event_pump.poll_iter() {
             graphics::display::SDLUtility::handle_events(&event);
 
-            if !inputs::UserInput::handle_events(event, &mut me.core.ports.joysticks) {
+            if let Some(hotkey) = inputs::UserInput::hotkey(&event) {
+                me.handle_hotkey(hotkey);
+            }
+
+            if !inputs::UserInput::handle_events(event, &mut me.joystick) {
                 return false;
             };
-            me.core.memory.riot.set_inputs(me.core.ports.joysticks.input);
-            me.core.memory.stella.set_inputs(me.core.ports.joysticks.input);
+            // Movies only change the inputs at the end of a frame.
+            if me.frames.movie.is_none() {
+                me.machine.set_input(me.joystick.input);
+            }
         }
 
         // Need to temporarily move the mutable fields out of 'self' to avoid multiple borrows of mutable self.
@@ -114,15 +255,30 @@ impl Atari2600 {
         self.powered = true;
     }
 
-    pub fn new(debug: bool, realtime: bool, stop_clock: clocks::ClockType, cartridge_name: &String, cartridge_type: &memory::cartridge::CartridgeType, fullscreen: bool, pal_palette: bool) -> Self {
-        let core = Self::build_atari2600(cartridge_name, cartridge_type, debug, realtime, pal_palette);
+    pub fn new(debug: bool, realtime: bool, stop_clock: clocks::ClockType, cartridge_name: &str, cartridge_type: &memory::cartridge::CartridgeType, fullscreen: bool, pal_palette: bool) -> Self {
+        #[allow(unused_mut)]
+        let mut machine = machine::Machine::new(machine::MachineConfig { debug, realtime, pal_palette });
+        // The emscripten build is given the cartridge later (by javascript), see 'insert_cartridge'.
+        #[cfg(not(target_os = "emscripten"))]
+        machine.insert_cartridge(&memory::cartridge::read_cartridge(cartridge_name), cartridge_type);
+
         Self {
-            core,
-            debug,
-            realtime,
+            machine,
             stop_clock,
             fullscreen,
             counter: 0,
+            tools: Tools::default(),
+            state_file: path::Path::new(cartridge_name).with_extension("state").to_string_lossy().to_string(),
+            cartridge_name: cartridge_name.to_string(),
+            frames: Frames {
+                count: 0,
+                rewind: Some(rewind::Rewind::new(rewind::Rewind::DEFAULT_BUDGET_MB * 1024 * 1024)),
+                rewinding: false,
+                movie: None,
+                screenshots: screenshot::Screenshots::new(cartridge_name),
+                capture: None,
+            },
+            joystick: inputs::Joystick::new(),
             powered: false,
             sdl_context: None,
             canvas: None,
@@ -130,9 +286,116 @@ impl Atari2600 {
         }
     }
 
-    pub fn reset(&mut self, cartridge_name: &String, cartridge_type: &memory::cartridge::CartridgeType) {
-        self.core.memory.reset(cartridge_name, cartridge_type);
-        self.core.reset();
+    pub fn set_debugger(&mut self, debugger: debugger::Debugger) {
+        self.tools.debugger = Some(debugger);
+    }
+
+    pub fn set_symbols(&mut self, symbols: symbols::Symbols) {
+        self.machine.core_mut().set_symbols(symbols);
+    }
+
+    pub fn set_gdb_stub(&mut self, gdb_stub: gdb::GdbStub) {
+        self.tools.gdb_stub = Some(gdb_stub);
+    }
+
+    pub fn set_profiler(&mut self, profiler: profiler::Profiler) {
+        self.machine.core_mut().memory.record_cartridge_reads(true);
+        self.tools.profiler = Some(profiler);
+    }
+
+    pub fn set_trace(&mut self, trace: cpu::trace::Trace) {
+        self.machine.core_mut().set_trace(trace);
+    }
+
+    pub fn set_state_file(&mut self, state_file: &str) {
+        self.state_file = state_file.to_string();
+    }
+
+    pub fn load_state(&mut self, file_name: &str) -> io::Result<()> {
+        state::load_file(self.machine.core_mut(), file_name)
+    }
+
+    pub fn save_state(&self, file_name: &str) -> io::Result<()> {
+        state::save_file(self.machine.core(), file_name)
+    }
+
+    fn handle_hotkey(&mut self, hotkey: inputs::Hotkey) {
+        match hotkey {
+            inputs::Hotkey::SaveState => match self.save_state(&self.state_file) {
+                Ok(()) => println!("State saved to: {}", self.state_file),
+                Err(e) => println!("Unable to save state to {}: {}", self.state_file, e),
+            },
+            inputs::Hotkey::LoadState | inputs::Hotkey::Rewind(true) if self.frames.movie.is_some() => println!("Loading states and rewind aren't available with a movie"),
+            inputs::Hotkey::LoadState => match self.load_state(&self.state_file.clone()) {
+                Ok(()) => println!("State loaded from: {}", self.state_file),
+                Err(e) => println!("Unable to load state from {}: {}", self.state_file, e),
+            },
+            inputs::Hotkey::Rewind(rewinding) => self.frames.rewinding = rewinding && self.frames.rewind.is_some(),
+            inputs::Hotkey::Screenshot => self.frames.screenshots.request(),
+            inputs::Hotkey::Capture if self.frames.capture.is_some() => self.stop_capture(),
+            inputs::Hotkey::Capture => {
+                let name = path::Path::new(&self.cartridge_name).with_extension(self.frames.count.to_string()).to_string_lossy().to_string();
+                match self.start_capture(&name) {
+                    Ok(()) => println!("Capturing to: {}.y4m/.wav", name),
+                    Err(e) => println!("Unable to capture to {}: {}", name, e),
+                }
+            }
+        }
+    }
+
+    /// Capture the video ('<name>.y4m') and audio ('<name>.wav') from this point, until 'stop_capture' (or exit).
+    pub fn start_capture(&mut self, name: &str) -> io::Result<()> {
+        self.stop_capture();
+        self.frames.capture = Some(capture::Capture::start(name, &mut self.machine)?);
+        Ok(())
+    }
+
+    pub fn stop_capture(&mut self) {
+        if let Some(capture) = self.frames.capture.take() {
+            let name = capture.name().to_string();
+            if let Err(e) = capture.stop(&mut self.machine) {
+                println!("Unable to write the capture to {}: {}", name, e);
+            }
+        }
+    }
+
+    /// Save these frames (counted from power-on) as PNG screenshots, optionally stretched to the displayed width.
+    pub fn set_screenshots(&mut self, frames: &[u64], stretch: bool) {
+        self.frames.screenshots.set_frames(frames);
+        self.frames.screenshots.set_stretch(stretch);
+    }
+
+    /// Memory (bytes) used for the rewind snapshots, disabled if 0.
+    pub fn set_rewind_budget(&mut self, budget: usize) {
+        self.frames.rewind = (budget > 0).then(|| rewind::Rewind::new(budget));
+    }
+
+    /// Record or replay the inputs (from power-on).
+    pub fn set_movie(&mut self, movie: movie::Movie) {
+        self.frames.movie = Some(movie);
+    }
+
+    pub fn cartridge_checksum(&self) -> u32 {
+        state::cartridge_checksum(self.machine.core())
+    }
+
+    fn present_frame(canvas: &mut render::Canvas<video::Window>, texture: &mut render::Texture, machine: &machine::Machine, console_size: &graphics::display::ConsoleSize) {
+        let rgb = machine.frame_rgb24();
+        texture.with_lock(None, |buffer: &mut [u8], _pitch: usize| graphics::display::SDLUtility::copy_rgb24(&rgb, buffer)).unwrap();
+
+        canvas.clear();
+        canvas
+            .copy(
+                texture,
+                None,
+                Some(rect::Rect::new(0, 0, graphics::stella::Constants::PIXEL_WIDTH_STRETCH as u32 * console_size.console_width as u32, console_size.console_height as u32)),
+            )
+            .unwrap();
+        canvas.present();
+    }
+
+    pub fn insert_cartridge(&mut self, data: &[u8], cartridge_type: &memory::cartridge::CartridgeType) {
+        self.machine.insert_cartridge(data, cartridge_type);
     }
 
     pub fn draw_loop(&mut self, pixel_format: pixels::PixelFormatEnum, console_size: &graphics::display::ConsoleSize, iterations: u32) -> bool {
@@ -149,57 +412,61 @@ impl Atari2600 {
             let texture_creator = graphics::display::SDLUtility::texture_creator(canvas);
             let mut texture = graphics::display::SDLUtility::create_texture(&texture_creator, pixel_format, console_size.console_width, console_size.console_height);
 
+            if self.frames.rewinding {
+                // A frame at a time, so events are checked (ie the rewind key released) between frames.
+                self.frames.rewind_frame(&mut self.machine);
+                Self::present_frame(canvas, &mut texture, &self.machine, console_size);
+                thread::sleep(time::Duration::from_millis(Atari2600::REWIND_FRAME_MS));
+                return true;
+            }
+
             while display_refreshes < iterations {
-                if self.stop_clock > 0 && self.core.clock.ticks > self.stop_clock {
+                if self.stop_clock > 0 && self.machine.core().clock.ticks > self.stop_clock {
                     return false;
                 }
-                self.core.step(self.debug, self.realtime);
-                self.core.memory.stella.step_tia_sound(&self.core.clock);
+                let Some(end_of_frame) = self.tools.step(&mut self.machine) else {
+                    return false;
+                };
 
                 if 0 == audio_steps % Atari2600::CPU_STEPS_PER_AUDIO_UPDATE {
                     // Top-up the audio queue
                     // TODO: Change this thing of beauty to something even better.
                     if self.audio_queue.is_some() {
                         let audio_queue = self.audio_queue.as_mut().expect("Optional audio not set");
-                        sound::SDLUtility::top_up_audio_queue(&mut **audio_queue, |fill_size| self.core.memory.stella.get_next_audio_chunk(fill_size));
+                        sound::SDLUtility::top_up_audio_queue(&mut **audio_queue, |fill_size| self.machine.next_audio_chunk(fill_size));
                     }
                 }
                 audio_steps += 1;
 
-                if self.core.memory.stella.export() {
-                    texture.with_lock(None, |buffer: &mut [u8], _pitch: usize| self.core.memory.stella.generate_display(buffer)).unwrap();
-
-                    canvas.clear();
-                    canvas
-                        .copy(
-                            &texture,
-                            None,
-                            Some(rect::Rect::new(0, 0, graphics::stella::Constants::PIXEL_WIDTH_STRETCH as u32 * console_size.console_width as u32, console_size.console_height as u32)),
-                        )
-                        .unwrap();
-                    canvas.present();
+                if end_of_frame {
+                    self.frames.end_of_frame(&mut self.machine, self.joystick.input);
+                    Self::present_frame(canvas, &mut texture, &self.machine, console_size);
                 }
                 display_refreshes += 1;
             }
             true
         } else {
             while display_refreshes < iterations {
-                if self.stop_clock > 0 && self.core.clock.ticks > self.stop_clock {
+                if self.stop_clock > 0 && self.machine.core().clock.ticks > self.stop_clock {
                     return false;
                 }
-                self.core.step(self.debug, self.realtime);
-                self.core.memory.stella.step_tia_sound(&self.core.clock);
+                let Some(end_of_frame) = self.tools.step(&mut self.machine) else {
+                    return false;
+                };
 
                 if 0 == audio_steps % Atari2600::CPU_STEPS_PER_AUDIO_UPDATE {
                     // Top-up the audio queue
                     // TODO: Change this thing of beauty to something even better.
                     if self.audio_queue.is_some() {
                         let audio_queue = self.audio_queue.as_mut().expect("Optional audio not set");
-                        sound::SDLUtility::top_up_audio_queue(&mut **audio_queue, |fill_size| self.core.memory.stella.get_next_audio_chunk(fill_size));
+                        sound::SDLUtility::top_up_audio_queue(&mut **audio_queue, |fill_size| self.machine.next_audio_chunk(fill_size));
                     }
                 }
                 audio_steps += 1;
 
+                if end_of_frame {
+                    self.frames.end_of_frame(&mut self.machine, self.joystick.input);
+                }
                 display_refreshes += 1;
             }
             true
@@ -227,6 +494,17 @@ impl Atari2600 {
 
 impl Drop for Atari2600 {
     fn drop(&mut self) {
+        self.stop_capture();
+        if let Some(movie) = &self.frames.movie {
+            if let Err(e) = movie.finish() {
+                println!("Unable to write the movie: {}", e);
+            }
+        }
+        if let Some(profiler) = &self.tools.profiler {
+            if let Err(e) = profiler.write_reports(self.machine.core()) {
+                println!("Unable to write the profile: {}", e);
+            }
+        }
         println!("Done.");
     }
 }
@@ -1,5 +1,6 @@
 use super::super::audio::tiasound;
 use super::soundchannel;
+#[cfg(feature = "sdl")]
 use sdl2::audio;
 
 pub trait SoundQueue {
@@ -7,6 +8,7 @@ pub trait SoundQueue {
     fn size(&self) -> usize;
 }
 
+#[cfg(feature = "sdl")]
 impl SoundQueue for audio::AudioQueue<soundchannel::PlaybackType> {
     fn add_audio(&mut self, new_audio_data: &[soundchannel::PlaybackType]) {
         self.queue_audio(new_audio_data).unwrap();
@@ -17,6 +19,7 @@ impl SoundQueue for audio::AudioQueue<soundchannel::PlaybackType> {
     }
 }
 
+#[cfg(feature = "sdl")]
 pub type SoundQueueType = audio::AudioQueue<soundchannel::PlaybackType>;
 
 pub struct HoundOutput {
@@ -25,29 +28,35 @@ pub struct HoundOutput {
 }
 
 impl HoundOutput {
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str) -> hound::Result<Self> {
         let wav_spec = hound::WavSpec {
             channels: SDLUtility::MONO_STERO_FLAG as u16,
             sample_rate: tiasound::TiaSound::SAMPLERATE as u32, // Setting to 'chip' frequency, to avoid conversion.
             bits_per_sample: std::mem::size_of::<soundchannel::PlaybackType>() as u16 * 8,
             sample_format: hound::SampleFormat::Int,
         };
-        Self {
+        Ok(Self {
             spec: wav_spec,
-            out_file: hound::WavWriter::create(std::path::Path::new(&filename), wav_spec).unwrap(),
-        }
+            out_file: hound::WavWriter::create(std::path::Path::new(&filename), wav_spec)?,
+        })
     }
 
-    pub fn write(&mut self, data: &[soundchannel::PlaybackType]) {
+    pub fn write(&mut self, data: &[soundchannel::PlaybackType]) -> hound::Result<()> {
         for d in data {
-            self.out_file.write_sample(*d as i8).unwrap();
+            self.out_file.write_sample(*d as i8)?;
         }
+        Ok(())
+    }
+
+    /// Complete the WAV header (otherwise done when dropped, ignoring any errors).
+    pub fn finalize(self) -> hound::Result<()> {
+        self.out_file.finalize()
     }
 }
 
 impl SoundQueue for HoundOutput {
     fn add_audio(&mut self, new_audio_data: &[soundchannel::PlaybackType]) {
-        self.write(new_audio_data);
+        self.write(new_audio_data).unwrap();
     }
 
     fn size(&self) -> usize {
@@ -68,6 +77,7 @@ impl SDLUtility {
 
     pub const MONO_STERO_FLAG: u8 = 2; // TODO: Make this configurable 1 - mono, 2 - stereo
 
+    #[cfg(feature = "sdl")]
     pub fn get_audio_queue(sdl_context: &mut sdl2::Sdl) -> Option<Box<dyn SoundQueue>> {
         let audio_subsystem = sdl_context.audio().unwrap();
 
@@ -1,5 +1,6 @@
 use super::super::clocks;
 use super::super::cpu::core;
+use super::super::state;
 use super::sound;
 use super::soundchannel;
 
@@ -16,6 +17,8 @@ pub struct TiaSound {
     last_update_time: clocks::ClockType,
 
     working_stream: Vec<soundchannel::PlaybackType>,
+    // A copy of all of the audio generated, while capturing (the working stream is trimmed for playback).
+    captured_stream: Option<Vec<soundchannel::PlaybackType>>,
 }
 
 impl TiaSound {
@@ -41,6 +44,7 @@ impl TiaSound {
             last_update_time: 0,
 
             working_stream: Vec::new(),
+            captured_stream: None,
         }
     }
 
@@ -75,6 +79,18 @@ impl TiaSound {
         stream
     }
 
+    pub fn take_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        std::mem::take(&mut self.working_stream)
+    }
+
+    pub fn set_audio_capture(&mut self, capture: bool) {
+        self.captured_stream = capture.then(Vec::new);
+    }
+
+    pub fn take_captured_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        self.captured_stream.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
     // Clock poly 4, return new poly4 state
     // @staticmethod
     pub fn poly4(audio_ctrl: u8, poly5_state: u8, poly4_state: u8) -> u8 {
@@ -117,7 +133,9 @@ impl TiaSound {
                 self.poly5state[channel as usize] = next_poly5;
             }
 
-            if 0 != self.poly4state[channel as usize] & 1 {
+            // AUDC 0 and 0xB hold the output high ("set to 1"), so the volume is output directly (ie sample playback via
+            // AUDV).
+            if matches!(self.wave_form[channel as usize] & 0xF, 0x0 | 0xB) || 0 != self.poly4state[channel as usize] & 1 {
                 stream[i as usize] = (self.volume[channel as usize] & 0xF) * 0x7;
             }
 
@@ -168,17 +186,17 @@ impl TiaSound {
     }
 
     fn pre_write_generate_sound(&mut self, clock: &clocks::Clock) {
-        let audio_ticks: u32 = (clock.ticks - self.last_update_time) as u32;
-
         let mut raw_audio: (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
 
-        let num_samples = ((TiaSound::SAMPLERATE as u64 * audio_ticks as u64) / TiaSound::CPU_CLOCK_RATE as u64) as u16;
+        // Samples due since the last update, from the (absolute) sample times, so the rounding doesn't accumulate.
+        let sample_time = |ticks: clocks::ClockType| (TiaSound::SAMPLERATE as u64 * ticks) / TiaSound::CPU_CLOCK_RATE as u64;
+        let num_samples = sample_time(clock.ticks).saturating_sub(sample_time(self.last_update_time)) as u16;
         raw_audio.0.append(&mut self.get_channel_data(0, num_samples));
         raw_audio.1.append(&mut self.get_channel_data(1, num_samples));
 
-        // Update the time based on the number of samples.
-        self.last_update_time += ((num_samples as u64 * TiaSound::CPU_CLOCK_RATE as u64) / TiaSound::SAMPLERATE as u64) as clocks::ClockType;
+        self.last_update_time = clock.ticks;
 
+        let start = self.working_stream.len();
         while !raw_audio.0.is_empty() && !raw_audio.1.is_empty() {
             if 2 == sound::SDLUtility::MONO_STERO_FLAG {
                 self.working_stream.push(raw_audio.0.remove(0));
@@ -187,7 +205,53 @@ impl TiaSound {
                 self.working_stream.push(((raw_audio.0.remove(0) as u16 + raw_audio.1.remove(0) as u16) / 2) as u8);
             }
         }
+        if let Some(captured_stream) = self.captured_stream.as_mut() {
+            captured_stream.extend_from_slice(&self.working_stream[start..]);
+        }
     }
 
     fn post_write_generate_sound(&mut self) {}
+
+    /// Save the channel registers and poly counters (not the queued output).
+    pub fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&self.volume);
+        state.write_bytes(&self.freq);
+        state.write_bytes(&self.poly4state);
+        state.write_bytes(&self.poly5state);
+        state.write_bytes(&self.wave_form);
+        for freq_pos in &self.freq_pos {
+            state.write_u32(*freq_pos);
+        }
+        state.write_u64(self.last_update_time);
+    }
+
+    pub fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        state.read_bytes_into(&mut self.volume)?;
+        state.read_bytes_into(&mut self.freq)?;
+        state.read_bytes_into(&mut self.poly4state)?;
+        state.read_bytes_into(&mut self.poly5state)?;
+        state.read_bytes_into(&mut self.wave_form)?;
+        for freq_pos in self.freq_pos.iter_mut() {
+            *freq_pos = state.read_u32()?;
+        }
+        self.last_update_time = state.read_u64()?;
+        // Drop any sound generated from the previous state.
+        self.working_stream.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::audio::tiasound;
+
+    #[test]
+    fn test_set_to_1_outputs_volume() {
+        for audio_ctrl in [0x0, 0xB, 0x10, 0x1B] {
+            let mut tiasound = tiasound::TiaSound::new(false);
+            tiasound.wave_form[0] = audio_ctrl;
+            tiasound.volume[0] = 0xF;
+            assert_eq!(tiasound.get_channel_data(0, 16), vec![0xF * 0x7; 16], "AUDC {:X}", audio_ctrl);
+        }
+    }
 }
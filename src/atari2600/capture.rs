@@ -0,0 +1,175 @@
+//! Audio/video capture, the video frames (YUV4MPEG2, '<name>.y4m') and TIA audio (WAV, '<name>.wav') of the same run.
+//!
+//! The video has a fixed frame rate (NTSC 60000/1001, PAL 50), frames are repeated/skipped to keep in step with the
+//! emulated time (cartridges don't always generate the standard number of lines), so it stays in sync with the audio,
+//! which is generated in emulated time.  Frames are at the native 160 pixel width (with a 2:1 pixel aspect ratio), as
+//! full range BT.601 with no chroma subsampling (4:4:4).
+
+use super::audio::sound;
+use super::clocks;
+use super::cpu::core;
+use super::machine;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+pub struct Capture {
+    name: String,
+    video: io::BufWriter<fs::File>,
+    audio: sound::HoundOutput,
+    // Frames per second, as a fraction (numerator, denominator).
+    frame_rate: (u64, u64),
+    start_ticks: clocks::ClockType,
+    video_frames: u64,
+    audio_samples: usize,
+}
+
+impl Capture {
+    const NTSC_FRAME_RATE: (u64, u64) = (60000, 1001);
+    const PAL_FRAME_RATE: (u64, u64) = (50, 1);
+
+    /// Start capturing (from the current point), creating the video and audio files.
+    pub fn start(name: &str, machine: &mut machine::Machine) -> io::Result<Self> {
+        let frame_rate = if machine.config().pal_palette { Capture::PAL_FRAME_RATE } else { Capture::NTSC_FRAME_RATE };
+        let mut video = io::BufWriter::new(fs::File::create(format!("{}.y4m", name))?);
+        Capture::write_y4m_header(&mut video, machine::Machine::FRAME_WIDTH, machine::Machine::FRAME_HEIGHT, frame_rate)?;
+        let audio = sound::HoundOutput::new(&format!("{}.wav", name)).map_err(io::Error::other)?;
+
+        machine.set_audio_capture(true);
+        Ok(Self {
+            name: name.to_string(),
+            video,
+            audio,
+            frame_rate,
+            start_ticks: machine.core().clock.ticks,
+            video_frames: 0,
+            audio_samples: 0,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn write_y4m_header(output: &mut dyn Write, width: u16, height: u16, frame_rate: (u64, u64)) -> io::Result<()> {
+        writeln!(output, "YUV4MPEG2 W{} H{} F{}:{} Ip A2:1 C444 XCOLORRANGE=FULL", width, height, frame_rate.0, frame_rate.1)
+    }
+
+    /// Write a frame, 'rgb' is 3 bytes per pixel, as the Y, Cb and Cr planes.
+    pub fn write_y4m_frame(output: &mut dyn Write, rgb: &[u8]) -> io::Result<()> {
+        let pixels: Vec<(u8, u8, u8)> = rgb.chunks(3).map(|pixel| Capture::rgb_to_ycbcr(pixel[0], pixel[1], pixel[2])).collect();
+        output.write_all(b"FRAME\n")?;
+        output.write_all(&pixels.iter().map(|pixel| pixel.0).collect::<Vec<u8>>())?;
+        output.write_all(&pixels.iter().map(|pixel| pixel.1).collect::<Vec<u8>>())?;
+        output.write_all(&pixels.iter().map(|pixel| pixel.2).collect::<Vec<u8>>())
+    }
+
+    /// Full range BT.601 (as JPEG), in 16.16 fixed point.
+    pub fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        let scale = |value: i32| ((value + 0x8000) >> 16).clamp(0, 255) as u8;
+        (scale(19595 * r + 38470 * g + 7471 * b), scale((128 << 16) - 11059 * r - 21709 * g + 32768 * b), scale((128 << 16) + 32768 * r - 27439 * g - 5329 * b))
+    }
+
+    /// At the end of each frame, write the audio generated and the frame (as many times as needed to catch up with the
+    /// emulated time, possibly none).
+    pub fn frame(&mut self, machine: &mut machine::Machine) -> io::Result<()> {
+        self.write_audio(machine)?;
+
+        let elapsed = machine.core().clock.ticks - self.start_ticks;
+        let (numerator, denominator) = self.frame_rate;
+        if self.video_frames * denominator * (core::Constants::CLOCK_HZ as u64) < elapsed * numerator {
+            let rgb = machine.frame_rgb24();
+            while self.video_frames * denominator * (core::Constants::CLOCK_HZ as u64) < elapsed * numerator {
+                Capture::write_y4m_frame(&mut self.video, &rgb)?;
+                self.video_frames += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_audio(&mut self, machine: &mut machine::Machine) -> io::Result<()> {
+        let audio = machine.take_captured_audio();
+        self.audio.write(&audio).map_err(io::Error::other)?;
+        self.audio_samples += audio.len();
+        Ok(())
+    }
+
+    /// Write the remaining audio and close the files.
+    pub fn stop(mut self, machine: &mut machine::Machine) -> io::Result<()> {
+        let result = self.write_audio(machine);
+        machine.set_audio_capture(false);
+        result?;
+        self.video.flush()?;
+        self.audio.finalize().map_err(io::Error::other)?;
+        println!("Captured {} video frames and {} audio samples to: {}.y4m/.wav", self.video_frames, self.audio_samples, self.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::audio::sound;
+    use crate::atari2600::capture;
+    use crate::atari2600::cpu::core;
+    use crate::atari2600::testing;
+    use std::fs;
+
+    #[test]
+    fn test_capture() {
+        assert_eq!(capture::Capture::rgb_to_ycbcr(0, 0, 0), (0, 128, 128));
+        assert_eq!(capture::Capture::rgb_to_ycbcr(255, 255, 255), (255, 128, 128));
+        assert_eq!(capture::Capture::rgb_to_ycbcr(255, 0, 0), (76, 85, 255));
+
+        // Frame loop: VSYNC for 3 lines, VBLANK off, LDA #$1E, STA COLUBK, LDA #$0F, STA AUDV0, STA AUDF0, LDA #$04,
+        // STA AUDC0, 200 lines of WSYNC, JMP $F000
+        let mut machine = testing::machine(&testing::rom(
+            &[
+                0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xA9, 0x1E, 0x85, 0x09, 0xA9, 0x0F, 0x85, 0x19, 0x85, 0x17, 0xA9, 0x04, 0x85, 0x15, 0xA2, 0xC8, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00,
+                0xF0,
+            ],
+            0x00,
+        ));
+        machine.run_frame();
+
+        let name = testing::temp_path("capture_test").to_string_lossy().to_string();
+        let mut capture = capture::Capture::start(&name, &mut machine).unwrap();
+        let start_ticks = machine.core().clock.ticks;
+        for _ in 0..30 {
+            machine.run_frame();
+            // Playback doesn't affect the capture.
+            machine.take_audio();
+            capture.frame(&mut machine).unwrap();
+        }
+        let elapsed = machine.core().clock.ticks - start_ticks;
+        let rgb = machine.frame_rgb24();
+        capture.stop(&mut machine).unwrap();
+        assert!(machine.take_captured_audio().is_empty());
+
+        // The frames cover the emulated time (at 60000/1001), so at most a frame ahead.
+        let video = fs::read(format!("{}.y4m", name)).unwrap();
+        let header = b"YUV4MPEG2 W160 H220 F60000:1001 Ip A2:1 C444 XCOLORRANGE=FULL\n";
+        assert!(video.starts_with(header));
+        let frame_size = b"FRAME\n".len() + 3 * 160 * 220;
+        assert_eq!((video.len() - header.len()) % frame_size, 0);
+        let frames = ((video.len() - header.len()) / frame_size) as u64;
+        let emulated_frames = elapsed * 60000 / (1001 * core::Constants::CLOCK_HZ as u64);
+        assert!(frames == emulated_frames || frames == emulated_frames + 1);
+        let mut last_frame = Vec::new();
+        capture::Capture::write_y4m_frame(&mut last_frame, &rgb).unwrap();
+        assert!(video.ends_with(&last_frame));
+
+        // All of the audio generated (stereo, at the TIA sample rate) over the same time.
+        let mut audio = hound::WavReader::open(format!("{}.wav", name)).unwrap();
+        assert_eq!(audio.spec().sample_rate, 32050);
+        let samples = audio.len() as u64 / sound::SDLUtility::MONO_STERO_FLAG as u64;
+        let expected_samples = elapsed * 32050 / core::Constants::CLOCK_HZ as u64;
+        assert!(samples.abs_diff(expected_samples) <= 1);
+        let audio: Vec<i8> = audio.samples::<i8>().map(|sample| sample.unwrap()).collect();
+        assert!(audio.iter().any(|sample| *sample != audio[0]));
+
+        fs::remove_file(format!("{}.y4m", name)).unwrap();
+        fs::remove_file(format!("{}.wav", name)).unwrap();
+    }
+}
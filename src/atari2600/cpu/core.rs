@@ -1,9 +1,14 @@
 use super::super::clocks;
-use super::super::graphics;
+use super::super::memory::addressing;
+use super::super::memory::bus::Bus;
 use super::super::memory::memory;
 use super::super::ports;
+use super::super::state;
+use super::super::symbols;
+use super::disasm;
 use super::instructions;
 use super::pc_state;
+use super::trace;
 use std::thread;
 use std::time;
 
@@ -18,8 +23,11 @@ pub struct Core {
     pub memory: memory::Memory,
     pc_state: pc_state::PcState,
     pub ports: ports::Ports,
-    raw_display: Vec<u8>,
     start_time: time::SystemTime,
+    // Use the precomputed table of decoded instructions (rather than decoding each op-code as it's executed).
+    table_dispatch: bool,
+    trace: Option<trace::Trace>,
+    symbols: symbols::Symbols,
 }
 
 impl Core {
@@ -31,14 +39,64 @@ impl Core {
             memory,
             pc_state,
             ports,
-            raw_display: vec![0; (graphics::stella::Constants::ATARI2600_WIDTH as usize) * (graphics::stella::Constants::ATARI2600_HEIGHT as usize) * (graphics::display::SDLUtility::bytes_per_pixel() as usize)],
             start_time: time::SystemTime::now(),
+            table_dispatch: true,
+            trace: None,
+            symbols: symbols::Symbols::new(),
         }
     }
 
+    pub fn set_table_dispatch(&mut self, table_dispatch: bool) {
+        self.table_dispatch = table_dispatch;
+    }
+
+    pub fn pc_state(&self) -> &pc_state::PcState {
+        &self.pc_state
+    }
+
+    pub fn pc_state_mut(&mut self) -> &mut pc_state::PcState {
+        &mut self.pc_state
+    }
+
+    /// Restart the real-time reference from the current clock (ie after being paused in the debugger).
+    pub fn resync_realtime(&mut self) {
+        let emulated = time::Duration::from_millis(1000 * self.clock.ticks / Constants::CLOCK_HZ as u64);
+        self.start_time = time::SystemTime::now().checked_sub(emulated).unwrap_or(time::SystemTime::now());
+    }
+
+    pub fn set_symbols(&mut self, symbols: symbols::Symbols) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &symbols::Symbols {
+        &self.symbols
+    }
+
+    pub fn set_trace(&mut self, trace: trace::Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// Save the CPU, clock, inputs and memory mapped devices (see 'state' for the file header).
+    pub fn save_state(&self, state: &mut state::StateWriter) {
+        self.pc_state.save_state(state);
+        state.write_u64(self.clock.ticks);
+        self.ports.joysticks.input.save_state(state);
+        self.memory.save_state(state);
+    }
+
+    pub fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        self.pc_state.restore_state(state)?;
+        self.clock.ticks = state.read_u64()?;
+        self.ports.joysticks.input.restore_state(state)?;
+        self.memory.restore_state(state)?;
+        self.resync_realtime();
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
         // Initialise the PC state with the program entry point.
         self.pc_state.set_pc(self.memory.read16(&self.clock, Core::PROGRAM_ENTRY_ADDR));
+        self.pc_state.set_halted(false);
         self.start_time = time::SystemTime::now();
     }
 
@@ -51,30 +109,38 @@ impl Core {
             }
         }
 
-        let op_code = self.memory.read(&self.clock, self.pc_state.get_pc());
+        if self.pc_state.get_halted() {
+            // A 'JAM' op-code has locked the CPU, keep the clock running so the rest of the system continues.
+            self.clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+            return;
+        }
+
+        // The trace/debug output is of the state at the start of the instruction (before the op-code fetch).
+        if let Some(trace) = self.trace.as_mut() {
+            trace.instruction(&self.clock, &self.pc_state, &self.memory, &self.symbols);
+        }
 
         if debug {
             print!(
-                "cycles:{} 0x{:X} {:X} (0x{:X}) ",
+                "cycles:{} 0x{:X} {} (0x{:X}) ",
                 (self.clock.ticks.wrapping_sub(self.memory.stella.debug_clock())) / pc_state::PcState::CYCLES_TO_CLOCK as u64,
-                op_code,
-                self.pc_state.get_pc(),
-                self.memory.read(&self.clock, self.pc_state.get_pc().wrapping_add(1))
+                self.memory.peek(&self.clock, self.pc_state.get_pc()),
+                self.symbols.name_or_hex(self.pc_state.get_pc()),
+                self.memory.peek(&self.clock, self.pc_state.get_pc().wrapping_add(1))
             );
+            if !self.symbols.is_empty() {
+                let disassembly = disasm::disassemble(self.pc_state.get_pc(), &|address| self.memory.peek(&self.clock, address));
+                print!("[{}] ", disassembly.text_with_symbols(&self.symbols));
+            }
             println!("{}", self.pc_state);
         }
 
-        instructions::Instruction::execute(op_code, &mut self.clock, &mut self.memory, &mut self.pc_state, &mut self.ports);
-    }
-
-    pub fn export(&mut self) -> bool {
-        // TODO
-        // Add trigger for re-drawing stella graphics
-        false
-    }
-
-    pub fn generate_display(&mut self, buffer: &mut [u8]) {
-        // Function to populate the display buffer drawn to the 2D texture/canvas/window.
-        buffer.clone_from_slice(self.raw_display.as_slice());
+        // T1 - Op-code, on its own cycle (as for every other access), the instructions continue from T2.
+        let op_code = addressing::cycle_read(&mut self.clock, &mut self.memory, self.pc_state.get_pc());
+        if self.table_dispatch {
+            instructions::Instruction::execute(op_code, &mut self.clock, &mut self.memory, &mut self.pc_state, &mut self.ports);
+        } else {
+            instructions::Instruction::execute_without_table(op_code, &mut self.clock, &mut self.memory, &mut self.pc_state, &mut self.ports);
+        }
     }
 }
@@ -0,0 +1,363 @@
+//! 6507 disassembler.
+//!
+//! Decodes op-codes via the same table as 'Instruction::execute' (so they can't drift apart), including the undocumented op-codes.
+
+use super::super::memory::addressing::Addressing;
+use super::super::symbols;
+use super::instructions::{AddressMode, Instruction, OpName, RegisterName};
+
+use std::fmt;
+
+/// Addressing mode, as written in assembly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl Mode {
+    /// Length of the instruction (op-code and operand) in bytes.
+    pub fn length(&self) -> u16 {
+        match self {
+            Mode::Implied | Mode::Accumulator => 1,
+            Mode::Immediate | Mode::ZeroPage | Mode::ZeroPageX | Mode::ZeroPageY | Mode::IndirectX | Mode::IndirectY | Mode::Relative => 2,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+    /// Operand value (the target address for branches).
+    pub operand: u16,
+    pub undocumented: bool,
+}
+
+impl Disassembly {
+    pub fn length(&self) -> u16 {
+        self.mode.length()
+    }
+
+    /// Instruction bytes as hex (ie 'A9 00').
+    pub fn bytes_text(&self) -> String {
+        self.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ")
+    }
+
+    pub fn operand_text(&self) -> String {
+        self.format_operand(None)
+    }
+
+    // Operand with the address shown as 'label' (if given).
+    fn format_operand(&self, label: Option<&str>) -> String {
+        let operand = self.operand;
+        let address = |digits: usize| label.map_or(format!("${:0digits$X}", operand, digits = digits), |label| label.to_string());
+        match self.mode {
+            Mode::Implied => String::new(),
+            Mode::Accumulator => "A".to_string(),
+            Mode::Immediate => format!("#${:02X}", operand),
+            Mode::ZeroPage => address(2),
+            Mode::ZeroPageX => format!("{},X", address(2)),
+            Mode::ZeroPageY => format!("{},Y", address(2)),
+            Mode::Absolute | Mode::Relative => address(4),
+            Mode::AbsoluteX => format!("{},X", address(4)),
+            Mode::AbsoluteY => format!("{},Y", address(4)),
+            Mode::Indirect => format!("({})", address(4)),
+            Mode::IndirectX => format!("({},X)", address(2)),
+            Mode::IndirectY => format!("({}),Y", address(2)),
+        }
+    }
+
+    fn format_text(&self, operand: String) -> String {
+        match self.mode {
+            Mode::Implied => self.mnemonic.to_string(),
+            _ => format!("{} {}", self.mnemonic, operand),
+        }
+    }
+
+    /// Instruction text (ie 'LDA #$00').
+    pub fn text(&self) -> String {
+        self.format_text(self.operand_text())
+    }
+
+    /// Instruction text with the operand address replaced by its symbol (if any), ie 'STA PlayerX'.
+    pub fn text_with_symbols(&self, symbols: &symbols::Symbols) -> String {
+        let label = match self.mode {
+            Mode::Implied | Mode::Accumulator | Mode::Immediate => None,
+            _ => symbols.name(self.operand),
+        };
+        self.format_text(self.format_operand(label))
+    }
+
+    fn format_line(&self, text: String) -> String {
+        format!("{:04X}  {:<8}  {}{}", self.address, self.bytes_text(), text, if self.undocumented { "  ; undocumented" } else { "" })
+    }
+
+    /// As displayed, but with symbols (and a line with the label before an instruction that has one).
+    pub fn line_with_symbols(&self, symbols: &symbols::Symbols) -> String {
+        let line = self.format_line(self.text_with_symbols(symbols));
+        match symbols.name(self.address) {
+            Some(label) => format!("{}:\n{}", label, line),
+            None => line,
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        write!(dest, "{}", self.format_line(self.text()))
+    }
+}
+
+fn mode(op_name: OpName, address_mode: AddressMode) -> Mode {
+    match Instruction::addressing(address_mode) {
+        Some(Addressing::Imm) => Mode::Immediate,
+        Some(Addressing::Zp) => Mode::ZeroPage,
+        Some(Addressing::Zpx) => Mode::ZeroPageX,
+        Some(Addressing::Zpy) => Mode::ZeroPageY,
+        Some(Addressing::Abs) => Mode::Absolute,
+        Some(Addressing::Abx | Addressing::AbxPageDelay) => Mode::AbsoluteX,
+        Some(Addressing::Aby | Addressing::AbyPageDelay) => Mode::AbsoluteY,
+        Some(Addressing::Indirect) => Mode::Indirect,
+        Some(Addressing::Izx) => Mode::IndirectX,
+        Some(Addressing::Izy | Addressing::IZYPageDelay) => Mode::IndirectY,
+        Some(Addressing::Accumulator) => Mode::Accumulator,
+        None => match op_name {
+            OpName::Jsr | OpName::JmpAbs => Mode::Absolute,
+            OpName::JmpInd => Mode::Indirect,
+            OpName::Br(..) => Mode::Relative,
+            OpName::Asl | OpName::Lsr | OpName::Rol | OpName::Ror if address_mode == AddressMode::Imp(RegisterName::A, RegisterName::A) => Mode::Accumulator,
+            _ => Mode::Implied,
+        },
+    }
+}
+
+fn mnemonic(op_name: OpName, address_mode: AddressMode) -> &'static str {
+    use RegisterName::*;
+
+    match (op_name, address_mode) {
+        (OpName::Inc, AddressMode::Imp(X, _)) => "INX",
+        (OpName::Inc, AddressMode::Imp(Y, _)) => "INY",
+        (OpName::Dec, AddressMode::Imp(X, _)) => "DEX",
+        (OpName::Dec, AddressMode::Imp(Y, _)) => "DEY",
+        (OpName::TStat | OpName::TNoStat, AddressMode::Imp(r, w)) => match (r, w) {
+            (A, X) => "TAX",
+            (A, Y) => "TAY",
+            (X, A) => "TXA",
+            (Y, A) => "TYA",
+            (S, X) => "TSX",
+            (X, S) => "TXS",
+            _ => "???",
+        },
+        (OpName::Br(bit, set), _) => match (bit, set) {
+            (7, false) => "BPL",
+            (7, true) => "BMI",
+            (6, false) => "BVC",
+            (6, true) => "BVS",
+            (0, false) => "BCC",
+            (0, true) => "BCS",
+            (1, false) => "BNE",
+            (1, true) => "BEQ",
+            _ => "???",
+        },
+        (OpName::Adc, _) => "ADC",
+        (OpName::And, _) => "AND",
+        (OpName::Asl, _) => "ASL",
+        (OpName::Bit, _) => "BIT",
+        (OpName::Clc, _) => "CLC",
+        (OpName::Cld, _) => "CLD",
+        (OpName::Cli, _) => "CLI",
+        (OpName::Clv, _) => "CLV",
+        (OpName::Cmp, _) => "CMP",
+        (OpName::Cpx, _) => "CPX",
+        (OpName::Cpy, _) => "CPY",
+        (OpName::Dcp, _) => "DCP",
+        (OpName::Dec, _) => "DEC",
+        (OpName::Eor, _) => "EOR",
+        (OpName::Inc, _) => "INC",
+        (OpName::Lda, _) => "LDA",
+        (OpName::Ldx, _) => "LDX",
+        (OpName::Ldy, _) => "LDY",
+        (OpName::Lsr, _) => "LSR",
+        (OpName::Nop, _) => "NOP",
+        (OpName::Or, _) => "ORA",
+        (OpName::Rol, _) => "ROL",
+        (OpName::Ror, _) => "ROR",
+        (OpName::Sax, _) => "SAX",
+        (OpName::Sbc, _) => "SBC",
+        (OpName::Sec, _) => "SEC",
+        (OpName::Sed, _) => "SED",
+        (OpName::Sei, _) => "SEI",
+        (OpName::Sta, _) => "STA",
+        (OpName::Stx, _) => "STX",
+        (OpName::Sty, _) => "STY",
+        (OpName::TNoStat | OpName::TStat, _) => "???",
+        (OpName::Jsr, _) => "JSR",
+        (OpName::Brk, _) => "BRK",
+        (OpName::Rti, _) => "RTI",
+        (OpName::Rts, _) => "RTS",
+        (OpName::JmpAbs | OpName::JmpInd, _) => "JMP",
+        (OpName::Php, _) => "PHP",
+        (OpName::Plp, _) => "PLP",
+        (OpName::Pha, _) => "PHA",
+        (OpName::Pla, _) => "PLA",
+        (OpName::Isc, _) => "ISC",
+        (OpName::Slo, _) => "SLO",
+        (OpName::Lax, _) => "LAX",
+        (OpName::Rla, _) => "RLA",
+        (OpName::Sre, _) => "SRE",
+        (OpName::Rra, _) => "RRA",
+        (OpName::Anc, _) => "ANC",
+        (OpName::Alr, _) => "ALR",
+        (OpName::Arr, _) => "ARR",
+        (OpName::Ane, _) => "ANE",
+        (OpName::Lxa, _) => "LXA",
+        (OpName::Sbx, _) => "SBX",
+        (OpName::Las, _) => "LAS",
+        (OpName::Sha, _) => "SHA",
+        (OpName::Shx, _) => "SHX",
+        (OpName::Shy, _) => "SHY",
+        (OpName::Tas, _) => "TAS",
+        (OpName::Jam, _) => "JAM",
+    }
+}
+
+fn is_undocumented(op_code: u8, op_name: OpName) -> bool {
+    const NOP: u8 = 0xEA;
+    const SBC_IMMEDIATE: u8 = 0xEB; // Duplicate of 0xE9.
+
+    match op_name {
+        OpName::Nop => op_code != NOP,
+        OpName::Sbc => op_code == SBC_IMMEDIATE,
+        OpName::Isc | OpName::Slo | OpName::Lax | OpName::Sax | OpName::Dcp | OpName::Rla | OpName::Sre | OpName::Rra => true,
+        OpName::Anc | OpName::Alr | OpName::Arr | OpName::Ane | OpName::Lxa | OpName::Sbx | OpName::Las => true,
+        OpName::Sha | OpName::Shx | OpName::Shy | OpName::Tas | OpName::Jam => true,
+        _ => false,
+    }
+}
+
+/// True if 'word' is a (documented or undocumented) mnemonic, ignoring case.
+pub fn is_mnemonic(word: &str) -> bool {
+    (0..=0xFF_u8).any(|op_code| {
+        let (op_name, address_mode) = Instruction::decode(op_code);
+        mnemonic(op_name, address_mode).eq_ignore_ascii_case(word)
+    })
+}
+
+/// Disassemble the instruction at 'address', 'read' provides the memory contents (without side effects).
+pub fn disassemble(address: u16, read: &dyn Fn(u16) -> u8) -> Disassembly {
+    let op_code = read(address);
+    let (op_name, address_mode) = Instruction::decode(op_code);
+    let mode = mode(op_name, address_mode);
+
+    let bytes: Vec<u8> = (0..mode.length()).map(|i| read(address.wrapping_add(i))).collect();
+    let operand = match mode.length() {
+        2 if mode == Mode::Relative => address.wrapping_add(2).wrapping_add(bytes[1] as i8 as u16),
+        2 => bytes[1] as u16,
+        3 => bytes[1] as u16 | ((bytes[2] as u16) << 8),
+        _ => 0,
+    };
+
+    Disassembly {
+        address,
+        bytes,
+        mnemonic: mnemonic(op_name, address_mode),
+        mode,
+        operand,
+        undocumented: is_undocumented(op_code, op_name),
+    }
+}
+
+/// Disassemble (linearly) from 'start' up to (but not including) 'end'.
+pub fn disassemble_range(start: u16, end: u32, read: &dyn Fn(u16) -> u8) -> Vec<Disassembly> {
+    let mut disassembly = Vec::new();
+    let mut address = start as u32;
+    while address < end {
+        let instruction = disassemble(address as u16, read);
+        address += instruction.length() as u32;
+        disassembly.push(instruction);
+    }
+    disassembly
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::clocks;
+    use crate::atari2600::cpu::disasm;
+    use crate::atari2600::cpu::instructions;
+    use crate::atari2600::cpu::pc_state;
+    use crate::atari2600::memory::flat_memory;
+    use crate::atari2600::ports;
+    use crate::atari2600::symbols;
+
+    #[test]
+    fn test_disassemble() {
+        let code = [0xA9, 0x00, 0x85, 0x02, 0xBD, 0x34, 0x12, 0xD0, 0xFC, 0x6C, 0xFC, 0xFF, 0xB1, 0x80, 0x0A, 0xAA, 0xA7, 0x81, 0x02];
+        let read = |address: u16| code.get((address - 0xF000) as usize).copied().unwrap_or(0);
+        let lines: Vec<String> = disasm::disassemble_range(0xF000, 0xF000 + code.len() as u32, &read).iter().map(|d| d.to_string()).collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "F000  A9 00     LDA #$00",
+                "F002  85 02     STA $02",
+                "F004  BD 34 12  LDA $1234,X",
+                "F007  D0 FC     BNE $F005",
+                "F009  6C FC FF  JMP ($FFFC)",
+                "F00C  B1 80     LDA ($80),Y",
+                "F00E  0A        ASL A",
+                "F00F  AA        TAX",
+                "F010  A7 81     LAX $81  ; undocumented",
+                "F012  02        JAM  ; undocumented",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_with_symbols() {
+        let mut symbols = symbols::Symbols::new();
+        symbols.add("MainLoop", 0xF002);
+        symbols.add("PlayerX", 0x80);
+
+        let code = [0xB5, 0x80, 0xD0, 0xFE, 0xA9, 0x80];
+        let read = |address: u16| code[(address - 0xF000) as usize];
+        assert_eq!(disasm::disassemble(0xF000, &read).text_with_symbols(&symbols), "LDA PlayerX,X");
+        assert_eq!(disasm::disassemble(0xF002, &read).line_with_symbols(&symbols), "MainLoop:\nF002  D0 FE     BNE MainLoop");
+        // Immediate values aren't addresses.
+        assert_eq!(disasm::disassemble(0xF004, &read).text_with_symbols(&symbols), "LDA #$80");
+    }
+
+    #[test]
+    fn test_disassembly_length_matches_execution() {
+        // For all (non flow control) op-codes, the length is the amount the CPU advances the PC.
+        for op_code in 0..=0xFF_u8 {
+            let mut memory = flat_memory::FlatMemory::new();
+            memory.ram[0x0200] = op_code;
+            let disassembly = disasm::disassemble(0x0200, &|address| memory.ram[address as usize]);
+            if matches!(disassembly.mnemonic, "JSR" | "JMP" | "RTS" | "RTI" | "BRK" | "JAM") || disassembly.mode == disasm::Mode::Relative {
+                continue;
+            }
+
+            let mut clock = clocks::Clock::new();
+            let mut pc_state = pc_state::PcState::new();
+            let mut ports = ports::Ports::new();
+            pc_state.set_pc(0x0200);
+            instructions::Instruction::execute(op_code, &mut clock, &mut memory, &mut pc_state, &mut ports);
+            assert_eq!(pc_state.get_pc(), 0x0200 + disassembly.length(), "Op-code 0x{:02X} {}", op_code, disassembly);
+        }
+    }
+}
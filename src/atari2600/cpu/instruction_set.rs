@@ -1,48 +1,36 @@
 use super::super::clocks;
 use super::super::memory::addressing;
 use super::super::memory::addressing::Address16;
-use super::super::memory::memory;
+use super::super::memory::bus;
 use super::pc_state;
 
-// Creating a struct for 'convinience' simply groups mutable state data to
-// reduce the number of arguments required for subsequent function calls.
-// TODO: Use more widely/consistently.
-pub struct PcStateCollection<'a> {
-    pub clock: &'a mut clocks::Clock,
-    pub pc_state: &'a mut pc_state::PcState,
-    pub memory: &'a mut memory::Memory,
-}
-
-impl<'a> PcStateCollection<'a> {
-    pub fn new(clock: &'a mut clocks::Clock, pc_state: &'a mut pc_state::PcState, memory: &'a mut memory::Memory) -> Self {
-        Self { clock, pc_state, memory }
-    }
-}
-
-pub fn nop(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn nop(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     data
 }
 
-pub fn single_byte_instruction<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut memory::Memory, u8) -> u8>(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, read: R, write: W, instruction: I)
+// The instructions perform each bus access on its own cycle (see 'addressing::cycle_read'), so that
+// the TIA/RIOT/cartridge see the access (including any 'dummy' accesses) at the correct clock.
+// Cycle 'T1' is the op-code fetch, a 'cycle_read' by the caller before 'Instruction::execute' (see 'Core::step').
+
+pub fn single_byte_instruction<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut dyn bus::Bus, u8) -> u8>(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, read: R, write: W, instruction: I)
 where
     R: Fn(&pc_state::PcState) -> u8,
     W: Fn(&mut pc_state::PcState, u8),
 {
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
 
     let data = read(pc_state);
     let result = instruction(clock, pc_state, memory, data);
     write(pc_state, result);
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-
     pc_state.increment_pc(1);
 }
 
-pub fn read_write_instruction<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut memory::Memory, u8) -> u8>(
+pub fn read_write_instruction<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut dyn bus::Bus, u8) -> u8>(
     clock: &mut clocks::Clock,
     pc_state: &mut pc_state::PcState,
-    memory: &mut memory::Memory,
+    memory: &mut dyn bus::Bus,
     address: &addressing::Addressing,
     read: R,
     write: W,
@@ -51,300 +39,313 @@ pub fn read_write_instruction<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcS
     R: addressing::ReadData,
     W: addressing::WriteData,
 {
-    let mut my_pc_state = PcStateCollection::new(clock, pc_state, memory);
-    read_write_instruction_additional_delay(&mut my_pc_state, address, read, write, instruction, 0);
+    // Addressing cycles (including any dummy reads).
+    let addr = address.address16(clock, pc_state, memory);
+    let value = read.read(clock, pc_state, memory, addr);
+    let data = instruction(clock, pc_state, memory, value);
+    write.write(clock, pc_state, memory, addr, value, data);
+
+    pc_state.increment_pc((address.get_addressing_size() + 1) as i16);
 }
 
-pub fn read_write_instruction_additional_delay<R, W, I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut memory::Memory, u8) -> u8>(
-    pc_state_collection: &mut PcStateCollection,
+pub fn high_byte_store_instruction<I: Fn(&mut clocks::Clock, &mut pc_state::PcState, &mut dyn bus::Bus, u8) -> u8>(
+    clock: &mut clocks::Clock,
+    pc_state: &mut pc_state::PcState,
+    memory: &mut dyn bus::Bus,
     address: &addressing::Addressing,
-    read: R,
-    write: W,
     instruction: I,
-    additional_delay: u8,
-) where
-    R: addressing::ReadData,
-    W: addressing::WriteData,
-{
-    let addr = address.address16(pc_state_collection.clock, pc_state_collection.pc_state, pc_state_collection.memory);
-    let mut execute_time = address.get_addressing_time();
-
-    let value = read.read(pc_state_collection.clock, pc_state_collection.pc_state, pc_state_collection.memory, addr);
-    execute_time += read.get_reading_time();
-
-    execute_time += write.get_writing_time();
+) {
+    // The unstable 'SHA/SHX/SHY/TAS' stores, 'and' the value with the (un-indexed) high byte of the address + 1.
+    // If the index crosses a page, the 'fixed' high byte of the address is replaced with the stored value.
+    let (base_address, index) = match address {
+        addressing::Addressing::Abx => (addressing::cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1)), pc_state.get_x()),
+        addressing::Addressing::Aby => (addressing::cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1)), pc_state.get_y()),
+        _ => {
+            let tmp8 = addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+            let low = addressing::cycle_read(clock, memory, tmp8 as u16);
+            let high = addressing::cycle_read(clock, memory, tmp8.wrapping_add(1) as u16);
+            (((high as u16) << 8) | low as u16, pc_state.get_y())
+        }
+    };
 
-    let data = instruction(pc_state_collection.clock, pc_state_collection.pc_state, pc_state_collection.memory, value);
+    // Dummy read (always, as for the other indexed stores).
+    addressing::cycle_read(clock, memory, addressing::AllAddressingModes::uncorrected_address(base_address, index));
 
-    pc_state_collection.clock.increment(execute_time as u32);
-    pc_state_collection.clock.increment(additional_delay as u32);
+    let mut addr = base_address.wrapping_add(index as u16);
+    let data = instruction(clock, pc_state, memory, ((base_address >> 8) as u8).wrapping_add(1));
 
-    write.write(pc_state_collection.clock, pc_state_collection.pc_state, pc_state_collection.memory, addr, data);
+    if addressing::did_index_cross_page(base_address, addr) {
+        addr = ((data as u16) << 8) | (addr & 0xFF);
+    }
+    addressing::cycle_write(clock, memory, addr, data);
 
-    pc_state_collection.pc_state.increment_pc((address.get_addressing_size() + 1) as i16);
+    pc_state.increment_pc((address.get_addressing_size() + 1) as i16);
 }
 
-pub fn break_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
+pub fn jam_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState) {
+    // The CPU locks up (the program counter isn't incremented), only a reset will recover.
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    pc_state.increment_pc(1);
+    pc_state.set_halted(true);
+}
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    let adl = memory.read(clock, 0xFFFE);
+// The 'B' and unused bits don't exist in the status register, they're set when pushed by 'BRK'/'PHP'.
+const PUSHED_STATUS_FLAGS: u8 = 0x30;
 
+pub fn break_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (the padding byte, discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+    pc_state.increment_pc(2);
+
+    // T3, T4 - Push the return address
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.write(clock, pc_state.get_s() as u16, pc_state.get_pch());
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_pch());
     pc_state.increment_s(-1);
 
-    pc_state.increment_pc(1);
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.write(clock, pc_state.get_s() as u16, pc_state.get_pcl());
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_pcl());
     pc_state.increment_s(-1);
 
-    // The 'B' flag, only alters the value on the stack, not ongoing status.
-    pc_state.set_flag_b(true);
+    // T5 - Push the status
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.write(clock, pc_state.get_s() as u16, pc_state.get_p());
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_p() | PUSHED_STATUS_FLAGS);
     pc_state.increment_s(-1);
-    pc_state.set_flag_b(false);
+    pc_state.set_flag_i(true);
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    let adh = memory.read(clock, 0xFFFF);
+    // T6, T7 - Interrupt vector
+    let adl = addressing::cycle_read(clock, memory, 0xFFFE);
+    let adh = addressing::cycle_read(clock, memory, 0xFFFF);
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.set_pc(adl as u16 + ((adh as u16) << 8));
 }
 
-pub fn jump_sub_routine_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn jump_sub_routine_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
     pc_state.increment_pc(1);
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    let adl = memory.read(clock, pc_state.get_pc());
+    // T2 - PC + 1 -> ADL
+    let adl = addressing::cycle_read(clock, memory, pc_state.get_pc());
 
+    // T3 - Stack Ptr (discarded)
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    memory.read_sp(clock, pc_state.get_s());
 
+    // T4 - Push PCH
     // Increment before store, to catch low to high carry.
     pc_state.increment_pc(1);
-    memory.write(clock, pc_state.get_s() as u16, pc_state.get_pch());
+    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_pch());
     pc_state.increment_s(-1);
 
+    // T5 - Push PCL
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.write(clock, pc_state.get_s() as u16, pc_state.get_pcl());
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_pcl());
     pc_state.increment_s(-1);
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    let adh = memory.read(clock, pc_state.get_pc());
+    // T6 - PC + 2 -> ADH
+    let adh = addressing::cycle_read(clock, memory, pc_state.get_pc());
 
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.set_pc(adl as u16 + ((adh as u16) << 8));
 }
 
-pub fn return_from_interrupt(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
+pub fn return_from_interrupt(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+    // T3 - Stack Ptr (discarded)
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    pc_state.increment_pc(1);
+    memory.read_sp(clock, pc_state.get_s());
 
+    // T4 - Stack Ptr + 1 -> P
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
-    pc_state.set_p(memory.read(clock, pc_state.get_s() as u16));
+    pc_state.set_p(memory.read_sp(clock, pc_state.get_s()));
 
+    // T5 - Stack Ptr + 1 -> PCL
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
-    pc_state.set_pcl(memory.read(clock, pc_state.get_s() as u16));
+    pc_state.set_pcl(memory.read_sp(clock, pc_state.get_s()));
 
+    // T6 - Stack Ptr + 1 -> PCH
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
-    pc_state.set_pch(memory.read(clock, pc_state.get_s() as u16));
-
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.read(clock, pc_state.get_pc());
-
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    pc_state.set_pch(memory.read_sp(clock, pc_state.get_s()));
 }
 
-pub fn return_from_sub_routine_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    // T1 - PC + 1
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    pc_state.increment_pc(1);
-    // T2 - Stack Ptr
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    // T3 - Stack Ptr + 1 -> PCL
+pub fn return_from_sub_routine_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+    // T3 - Stack Ptr (discarded)
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    pc_state.increment_s(1);
-    pc_state.set_pcl(memory.read(clock, pc_state.get_s() as u16));
+    memory.read_sp(clock, pc_state.get_s());
     // T4 - Stack Ptr + 1 -> PCL
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
-    pc_state.set_pch(memory.read(clock, pc_state.get_s() as u16));
-    // T5 - discarded
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    memory.read(clock, pc_state.get_pc());
-    // T0 - Next instruction
+    pc_state.set_pcl(memory.read_sp(clock, pc_state.get_s()));
+    // T5 - Stack Ptr + 1 -> PCH
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    pc_state.increment_s(1);
+    pc_state.set_pch(memory.read_sp(clock, pc_state.get_s()));
+    // T6 - PC (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc());
     pc_state.increment_pc(1);
 }
 
-pub fn jump_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: &addressing::Addressing) {
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn jump_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: &addressing::Addressing) {
     let addr = address.address16(clock, pc_state, memory);
-    let execute_time = address.get_addressing_time();
-    clock.increment(execute_time as u32);
     pc_state.set_pc(addr);
 }
 
-pub fn branch_page_delay(clock: &mut clocks::Clock, start_address: u16, end_address: u16) {
-    // If pages don't match, add a cycle.
-    if (start_address & 0xF00) != (end_address & 0xF00) {
-        clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    }
-}
-
-pub fn branch_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, condition_mask: u8, condition: u8) {
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn branch_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, condition_mask: u8, condition: u8) {
+    // T2 - PC + 1 -> Offset
+    let delta = addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+    pc_state.increment_pc(2);
 
     if (pc_state.get_p() & condition_mask) == condition {
-        let initial_pc = pc_state.get_pc();
-        let delta = memory.read(clock, pc_state.get_pc().wrapping_add(1));
-        if delta & 0x80 != 0 {
-            pc_state.increment_pc((delta as i8) as i16);
-        } else {
-            pc_state.increment_pc(delta as i16);
+        let next_pc = pc_state.get_pc();
+        let target = next_pc.wrapping_add((delta as i8) as u16);
+
+        // T3 - Next op-code (discarded), whilst the offset is added.
+        addressing::cycle_read(clock, memory, next_pc);
+
+        // T4 - If the branch crosses a page, read (and discard) with the uncorrected high byte.
+        if addressing::did_index_cross_page(next_pc, target) {
+            addressing::cycle_read(clock, memory, (next_pc & 0xFF00) | (target & 0xFF));
         }
-        // If branch to same page add 1, else add 2
-        // TODO: Confirm if starting address is 'tmp16' or 'tmp16+2'
-        branch_page_delay(clock, initial_pc.wrapping_add(2), pc_state.get_pc().wrapping_add(2));
 
-        clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+        pc_state.set_pc(target);
     }
-
-    pc_state.increment_pc(2);
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
 }
 
-pub fn asl(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn asl(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_c(0 != (data >> 7) & 0x1);
     let left_shift = data << 1;
     pc_state::set_status_nz(pc_state, left_shift);
     left_shift
 }
 
-pub fn lsr(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn lsr(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_c(0 != data & 0x1);
     let right_shift = data >> 1;
     pc_state::set_status_nz(pc_state, right_shift);
     right_shift
 }
 
-pub fn rol(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn rol(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let t8 = (data << 1) | pc_state.get_flag_c() as u8;
     pc_state.set_flag_c(1 == (data >> 7) & 1);
     pc_state::set_status_nz(pc_state, t8);
     t8
 }
 
-pub fn ror(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn ror(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let t8 = (data >> 1) | ((pc_state.get_flag_c() as u8) << 7);
     pc_state.set_flag_c(1 == data & 1);
     pc_state::set_status_nz(pc_state, t8);
     t8
 }
 
-pub fn ldx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn ldx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_x(data);
     pc_state::set_status_nz(pc_state, pc_state.get_x());
     0
 }
 
-pub fn ldy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn ldy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_y(data);
     pc_state::set_status_nz(pc_state, pc_state.get_y());
     0
 }
 
-pub fn lda(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn lda(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_a(data);
     pc_state::set_status_nz(pc_state, data);
     0
 }
 
-pub fn and(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn and(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_a(pc_state.get_a() & data);
     pc_state::set_status_nz(pc_state, pc_state.get_a());
     0
 }
 
-pub fn eor(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn eor(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_a(pc_state.get_a() ^ data);
     pc_state::set_status_nz(pc_state, pc_state.get_a());
     0
 }
 
-pub fn or(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn or(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_a(pc_state.get_a() | data);
     pc_state::set_status_nz(pc_state, pc_state.get_a());
     0
 }
 
 pub fn add_carry(pc_state: &mut pc_state::PcState, a: u8, b: u8, c: u8) -> u8 {
+    let binary = a.wrapping_add(b).wrapping_add(c);
+
     if !pc_state.get_flag_d() {
-        let r = (a as u16).wrapping_add(b as u16) + c as u16;
-        let rc = a.wrapping_add(b).wrapping_add(c);
-        pc_state.set_flag_n(0 != rc & 0x80);
-        pc_state.set_flag_z(rc == 0x0);
-        pc_state.set_flag_v(rc as i8 as u16 != r); // Overflow
+        let r = (a as u16) + (b as u16) + c as u16;
+        pc_state::set_status_nz(pc_state, binary);
+        pc_state.set_flag_v(0 != !(a ^ b) & (a ^ binary) & 0x80); // Overflow (same sign inputs, different sign result)
         pc_state.set_flag_c(0x100 == (r & 0x100));
 
-        a.wrapping_add(b).wrapping_add(c)
+        binary
     } else {
-        // Decimal Addition
-        // FIXME need to fix flags
-        let r = (((a >> 4) & 0xF) * 10 + ((a & 0xF) % 10) + ((b >> 4) & 0xF) * 10 + ((b & 0xF) % 10) + c) as u16;
-        let rc = a.wrapping_add(b).wrapping_add(c) as u16; // ???? TODO
-        pc_state.set_flag_n(false);
-        pc_state.set_flag_z(rc == 0x0);
-        // TODO: Check not needed        pc_state.set_flag_v(rc != r); // Overflow
-        pc_state.set_flag_c(r > 99);
-        ((((r / 10 % 10) << 4) & 0xf0) + (r % 10)) as u8
+        // Decimal Addition (NMOS), each nibble is adjusted in turn.
+        // The flags are from the intermediate values, 'Z' from the binary addition,
+        // 'N' and 'V' from the sum before the high nibble is adjusted.
+        let mut low = (a & 0xF) + (b & 0xF) + c;
+        if low > 0x9 {
+            low = ((low + 0x6) & 0xF) + 0x10;
+        }
+        let high = (a & 0xF0) as u16 + (b & 0xF0) as u16 + low as u16;
+        let signed_high = (a & 0xF0) as i8 as i16 + (b & 0xF0) as i8 as i16 + low as i16;
+
+        pc_state.set_flag_z(binary == 0x0);
+        pc_state.set_flag_n(0 != high & 0x80);
+        pc_state.set_flag_v(!(-128..=127).contains(&signed_high));
+
+        let r = if high > 0x9F { high + 0x60 } else { high };
+        pc_state.set_flag_c(r > 0xFF);
+        r as u8
     }
 }
 
-pub fn adc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn adc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let result = add_carry(pc_state, pc_state.get_a(), data, pc_state.get_flag_c() as u8);
     pc_state.set_a(result);
     0
 }
 
-pub fn sbc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sbc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let result = sub_carry(pc_state, pc_state.get_a(), data, !pc_state.get_flag_c() as u8);
     pc_state.set_a(result);
     0
 }
 
 pub fn sub_carry(pc_state: &mut pc_state::PcState, a: u8, b: u8, c: u8) -> u8 {
+    // 'c' is the borrow (not carry).
+    // The flags are the same as for binary subtraction (including in decimal mode).
+    let r = (a as i8 as i16) - (b as i8 as i16) - (c as i8 as i16);
+    let rs = a.wrapping_sub(b).wrapping_sub(c) as i8;
+    pc_state.set_flag_n(rs < 0); // Negative
+    pc_state.set_flag_z(rs == 0); // Zero
+    pc_state.set_flag_v(r != rs as i16); // Overflow
+
+    let r = (a as i16) - (b as i16) - (c as i16);
+    pc_state.set_flag_c(0x100 != (r as u16 & 0x100)); // Carry (not borrow)
+
     if !pc_state.get_flag_d() {
-        let mut r = (a as i8 as i16) - (b as i8 as i16) - (c as i8 as i16);
-        let rs = a.wrapping_sub(b).wrapping_sub(c) as i8;
-        pc_state.set_flag_n(rs < 0); // Negative
-        pc_state.set_flag_z(rs == 0); // Zero
-        pc_state.set_flag_v(r != rs as i16); // Overflow
-
-        r = (a as i16) - (b as i16) - (c as i16);
-        pc_state.set_flag_c(0x100 != (r as u16 & 0x100)); // Carry (not borrow
         a.wrapping_sub(b).wrapping_sub(c)
     } else {
-        // Decimal subtraction
-        // FIXME need to fix flags
-
-        let r = (((a >> 4) & 0xF) * 10 + ((a & 0xF) % 10)) as i16 - (((b >> 4) & 0xF) * 10 + ((b & 0xF) % 10)) as i16 - c as i16;
-
-        // rc = a + b + c
-        pc_state.set_flag_n(r < 0);
-        pc_state.set_flag_z(r == 0x0);
-        //  Need to check/fix conditions for V
-        // self.pc_state.P.V = (rc != r) ? 1:0;   # Overflow
-        pc_state.set_flag_v(true); // Overflow
-
-        pc_state.set_flag_c((0..=99).contains(&r));
-        (((((r / 10) % 10) << 4) & 0xf0) + (r % 10)) as u8
+        // Decimal subtraction (NMOS), each nibble is adjusted in turn.
+        let mut low = (a & 0xF) as i16 - (b & 0xF) as i16 - c as i16;
+        if low < 0 {
+            low = ((low - 0x6) & 0xF) - 0x10;
+        }
+        let mut r = (a & 0xF0) as i16 - (b & 0xF0) as i16 + low;
+        if r < 0 {
+            r -= 0x60;
+        }
+        r as u8
     }
 }
 
@@ -357,177 +358,407 @@ pub fn compare(pc_state: &mut pc_state::PcState, a: u8, b: u8) {
     pc_state.set_flag_c(0x100 != (r & 0x100)); // Carry (not borrow)
 }
 
-pub fn cpx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn cpx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     compare(pc_state, pc_state.get_x(), data);
     0
 }
 
-pub fn cpy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn cpy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     compare(pc_state, pc_state.get_y(), data);
     0
 }
 
-pub fn cmp(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn cmp(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     compare(pc_state, pc_state.get_a(), data);
     0
 }
-pub fn bit(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn bit(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_n(0x80 == (data & 0x80));
     pc_state.set_flag_v(0x40 == (data & 0x40));
     pc_state.set_flag_z((pc_state.get_a() & data) == 0x0);
     0
 }
 
-pub fn sta(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sta(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.get_a()
 }
 
-pub fn dcp(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn dcp(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let decremented = data.wrapping_sub(1);
     pc_state::set_status_nz(pc_state, decremented);
     compare(pc_state, pc_state.get_a(), decremented);
     decremented
 }
 
-pub fn sty(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sty(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.get_y()
 }
 
-pub fn stx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn stx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.get_x()
 }
 
-pub fn sax(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sax(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.get_a() & pc_state.get_x()
 }
 
-pub fn dec(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn dec(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let incremented = data.wrapping_sub(1);
     pc_state::set_status_nz(pc_state, incremented);
     incremented
 }
 
-pub fn clc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn clc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_c(false);
     0
 }
 
-pub fn cld(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn cld(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_d(false);
     0
 }
 
-pub fn cli(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn cli(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_i(false);
     0
 }
 
-pub fn clv(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn clv(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_v(false);
     0
 }
 
-pub fn sec(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sec(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_c(true);
     0
 }
 
-pub fn sei(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sei(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_i(true);
     0
 }
 
-pub fn sed(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn sed(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state.set_flag_d(true);
     0
 }
 
-pub fn inc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn inc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let incremented = data.wrapping_add(1);
     pc_state::set_status_nz(pc_state, incremented);
     incremented
 }
 
 // Undocumented instruction
-pub fn isc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn isc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let increment = inc(clock, pc_state, memory, data);
     sbc(clock, pc_state, memory, increment);
     increment
 }
 
-pub fn slo(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn slo(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     let shift = asl(clock, pc_state, memory, data);
     or(clock, pc_state, memory, shift);
     shift
 }
 
-pub fn lax(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn lax(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     lda(clock, pc_state, memory, data);
     ldx(clock, pc_state, memory, data)
 }
 
-pub fn t_no_status(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn rla(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    let rotate = rol(clock, pc_state, memory, data);
+    and(clock, pc_state, memory, rotate);
+    rotate
+}
+
+pub fn sre(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    let shift = lsr(clock, pc_state, memory, data);
+    eor(clock, pc_state, memory, shift);
+    shift
+}
+
+pub fn rra(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    // The carry out of the rotate is the carry in for the addition.
+    let rotate = ror(clock, pc_state, memory, data);
+    adc(clock, pc_state, memory, rotate);
+    rotate
+}
+
+pub fn anc(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    and(clock, pc_state, memory, data);
+    pc_state.set_flag_c(pc_state.get_flag_n());
+    0
+}
+
+pub fn alr(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    let shift = lsr(clock, pc_state, memory, pc_state.get_a() & data);
+    pc_state.set_a(shift);
+    0
+}
+
+pub fn arr(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    let t8 = pc_state.get_a() & data;
+    let mut result = (t8 >> 1) | ((pc_state.get_flag_c() as u8) << 7);
+
+    if !pc_state.get_flag_d() {
+        pc_state::set_status_nz(pc_state, result);
+        pc_state.set_flag_c(0 != result & 0x40);
+        pc_state.set_flag_v(0 != ((result >> 6) ^ (result >> 5)) & 0x1);
+    } else {
+        // Decimal mode 'fixes' each nibble of the rotated value (based on the value before the rotate).
+        pc_state.set_flag_n(pc_state.get_flag_c());
+        pc_state.set_flag_z(0 == result);
+        pc_state.set_flag_v(0 != (result ^ t8) & 0x40);
+
+        if (t8 & 0xF) + (t8 & 0x1) > 0x5 {
+            result = (result & 0xF0) | (result.wrapping_add(0x6) & 0xF);
+        }
+
+        let carry = (t8 >> 4) + ((t8 >> 4) & 0x1) > 0x5;
+        pc_state.set_flag_c(carry);
+        if carry {
+            result = result.wrapping_add(0x60);
+        }
+    }
+
+    pc_state.set_a(result);
+    0
+}
+
+// 'Magic' constant for the unstable 'ANE/LXA' op-codes, chip dependent (0xEE is the commonly used value).
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
+pub fn ane(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    pc_state.set_a((pc_state.get_a() | UNSTABLE_MAGIC) & pc_state.get_x() & data);
+    pc_state::set_status_nz(pc_state, pc_state.get_a());
+    0
+}
+
+pub fn lxa(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    lax(clock, pc_state, memory, (pc_state.get_a() | UNSTABLE_MAGIC) & data)
+}
+
+pub fn sbx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    // Compare style subtraction (carry/borrow is ignored and decimal mode isn't used).
+    let a_and_x = pc_state.get_a() & pc_state.get_x();
+    compare(pc_state, a_and_x, data);
+    pc_state.set_x(a_and_x.wrapping_sub(data));
+    0
+}
+
+pub fn las(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    let value = data & pc_state.get_s();
+    pc_state.set_s(value);
+    lax(clock, pc_state, memory, value)
+}
+
+// For the 'high byte' stores, 'data' is the high byte of the address + 1.
+pub fn sha(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    pc_state.get_a() & pc_state.get_x() & data
+}
+
+pub fn shx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    pc_state.get_x() & data
+}
+
+pub fn shy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    pc_state.get_y() & data
+}
+
+pub fn tas(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
+    pc_state.set_s(pc_state.get_a() & pc_state.get_x());
+    pc_state.get_s() & data
+}
+
+pub fn t_no_status(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     data
 }
 
-pub fn t_status(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, data: u8) -> u8 {
+pub fn t_status(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, data: u8) -> u8 {
     pc_state::set_status_nz(pc_state, data);
     data
 }
 
-pub fn php_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    // T1 - PC + 1
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn php_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
     pc_state.increment_pc(1);
-    // T2 - PC + 1
+    // T3 - Push P
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-    pc_state.set_flag_b(true);
-    pc_state.set_flag_x1(true);
-    memory.write_sp(clock, pc_state.get_s(), pc_state.get_p());
+    memory.write_sp(clock, pc_state.get_s(), pc_state.get_p() | PUSHED_STATUS_FLAGS);
     pc_state.increment_s(-1);
-    // T0 - Next kid
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
 }
 
-pub fn plp_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    // T1 - PC + 1
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn plp_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
     pc_state.increment_pc(1);
-    // T2 Stack Ptr. (Discard data)
+    // T3 Stack Ptr. (Discard data)
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     memory.read_sp(clock, pc_state.get_s());
-    // T3 Stack Ptr + 1.
+    // T4 Stack Ptr + 1.
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
     pc_state.set_p(memory.read_sp(clock, pc_state.get_s()));
-    // T0 - Next instruction
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
 }
 
-pub fn pha_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    // T1 - PC + 1
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn pha_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
     pc_state.increment_pc(1);
-    // T2 - PC + 1
+    // T3 - Push A
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     memory.write_sp(clock, pc_state.get_s(), pc_state.get_a());
     pc_state.increment_s(-1);
-    // T0 - Next kid
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
 }
 
-pub fn pla_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) {
-    // T1 - PC + 1
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+pub fn pla_instruction(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) {
+    // T2 - PC + 1 (discarded)
+    addressing::cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
     pc_state.increment_pc(1);
-    // T2 Stack Ptr. (Discard data)
+    // T3 Stack Ptr. (Discard data)
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     memory.read_sp(clock, pc_state.get_s());
-    // T3 Stack Ptr + 1.
+    // T4 Stack Ptr + 1.
     clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
     pc_state.increment_s(1);
     pc_state.set_a(memory.read_sp(clock, pc_state.get_s()));
     pc_state::set_status_nz(pc_state, pc_state.get_a());
-    // T0 - Next instruction
-    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::cpu::instruction_set::{add_carry, sub_carry};
+    use crate::atari2600::cpu::pc_state::PcState;
+
+    fn to_bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    fn flags(pc_state: &PcState) -> (bool, bool, bool, bool) {
+        (pc_state.get_flag_n(), pc_state.get_flag_v(), pc_state.get_flag_z(), pc_state.get_flag_c())
+    }
+
+    // Reference NMOS decimal mode behaviour, an independent model in the style of VICE's 6502 core (which was checked
+    // against hardware): unsigned (wrapping) arithmetic, the low digit's carry/borrow from bit 4 and the high digit's
+    // from bits 8+, 'V' from the sign bits rather than a signed sum.
+    // Returns (result, (N, V, Z, C)).
+    fn reference_decimal_adc(a: u8, b: u8, carry: bool) -> (u8, (bool, bool, bool, bool)) {
+        let (a, b, c) = (a as u32, b as u32, carry as u32);
+
+        let mut low = (a & 0x0F) + (b & 0x0F) + c;
+        if low > 0x09 {
+            low += 0x06;
+        }
+        let mut sum = (low & 0x0F) + (a & 0xF0) + (b & 0xF0) + if low > 0x0F { 0x10 } else { 0x00 };
+
+        let z = 0 == (a + b + c) & 0xFF;
+        let n = 0 != sum & 0x80;
+        let v = 0 != (a ^ sum) & 0x80 && 0 == (a ^ b) & 0x80;
+        if (sum & 0x1F0) > 0x90 {
+            sum += 0x60;
+        }
+        ((sum & 0xFF) as u8, (n, v, z, (sum & 0xFF0) > 0xF0))
+    }
+
+    fn reference_decimal_sbc(a: u8, b: u8, carry: bool) -> (u8, (bool, bool, bool, bool)) {
+        let (a, b, borrow) = (a as u32, b as u32, !carry as u32);
+
+        // The flags are from the binary subtraction.
+        let binary = a.wrapping_sub(b).wrapping_sub(borrow);
+        let mut difference = (a & 0x0F).wrapping_sub(b & 0x0F).wrapping_sub(borrow);
+        difference = if 0 != difference & 0x10 {
+            (difference.wrapping_sub(0x06) & 0x0F) | (a & 0xF0).wrapping_sub(b & 0xF0).wrapping_sub(0x10)
+        } else {
+            (difference & 0x0F) | (a & 0xF0).wrapping_sub(b & 0xF0)
+        };
+        if 0 != difference & 0x100 {
+            difference = difference.wrapping_sub(0x60);
+        }
+
+        let n = 0 != binary & 0x80;
+        let v = 0 != (a ^ binary) & 0x80 && 0 != (a ^ b) & 0x80;
+        let z = 0 == binary & 0xFF;
+        ((difference & 0xFF) as u8, (n, v, z, binary < 0x100))
+    }
+
+    #[test]
+    fn test_binary_adc_sbc() {
+        let mut pc_state = PcState::new();
+        for a in 0..=0xFF_u8 {
+            for b in 0..=0xFF_u8 {
+                for carry in [false, true] {
+                    let signed = a as i8 as i32 + b as i8 as i32 + carry as i32;
+                    let unsigned = a as u32 + b as u32 + carry as u32;
+                    let result = add_carry(&mut pc_state, a, b, carry as u8);
+                    assert_eq!(result, unsigned as u8);
+                    assert_eq!(flags(&pc_state), (0 != result & 0x80, !(-128..=127).contains(&signed), result == 0, unsigned > 0xFF), "ADC 0x{:02X} 0x{:02X} {}", a, b, carry);
+
+                    let signed = a as i8 as i32 - b as i8 as i32 - !carry as i32;
+                    let unsigned = a as i32 - b as i32 - !carry as i32;
+                    let result = sub_carry(&mut pc_state, a, b, !carry as u8);
+                    assert_eq!(result, unsigned as u8);
+                    assert_eq!(flags(&pc_state), (0 != result & 0x80, !(-128..=127).contains(&signed), result == 0, unsigned >= 0), "SBC 0x{:02X} 0x{:02X} {}", a, b, carry);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decimal_valid_bcd() {
+        let mut pc_state = PcState::new();
+        pc_state.set_flag_d(true);
+        for a in 0..100_u8 {
+            for b in 0..100_u8 {
+                for carry in [false, true] {
+                    let sum = a as u32 + b as u32 + carry as u32;
+                    assert_eq!(add_carry(&mut pc_state, to_bcd(a), to_bcd(b), carry as u8), to_bcd((sum % 100) as u8), "{} + {} + {}", a, b, carry);
+                    assert_eq!(pc_state.get_flag_c(), sum >= 100);
+
+                    let difference = a as i32 - b as i32 - !carry as i32;
+                    assert_eq!(sub_carry(&mut pc_state, to_bcd(a), to_bcd(b), !carry as u8), to_bcd(difference.rem_euclid(100) as u8), "{} - {} - {}", a, b, !carry);
+                    assert_eq!(pc_state.get_flag_c(), difference >= 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decimal_nmos_exhaustive() {
+        // All values (including invalid BCD) and all flags.
+        let mut pc_state = PcState::new();
+        pc_state.set_flag_d(true);
+        for a in 0..=0xFF_u8 {
+            for b in 0..=0xFF_u8 {
+                for carry in [false, true] {
+                    let result = add_carry(&mut pc_state, a, b, carry as u8);
+                    assert_eq!((result, flags(&pc_state)), reference_decimal_adc(a, b, carry), "ADC 0x{:02X} 0x{:02X} {}", a, b, carry);
+
+                    let result = sub_carry(&mut pc_state, a, b, !carry as u8);
+                    assert_eq!((result, flags(&pc_state)), reference_decimal_sbc(a, b, carry), "SBC 0x{:02X} 0x{:02X} {}", a, b, carry);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decimal_undocumented_flags() {
+        let mut pc_state = PcState::new();
+        pc_state.set_flag_d(true);
+
+        // 99 + 1, 'N' is from the intermediate (0xA0) result, 'Z' from the binary (0x9A) result.
+        assert_eq!(add_carry(&mut pc_state, 0x99, 0x01, 0), 0x00);
+        assert_eq!(flags(&pc_state), (true, false, false, true));
+
+        // 79 + 0 + carry, 'V' is set (0x70 + 0x10 overflows).
+        assert_eq!(add_carry(&mut pc_state, 0x79, 0x00, 1), 0x80);
+        assert_eq!(flags(&pc_state), (true, true, false, false));
+
+        // Invalid BCD.
+        assert_eq!(add_carry(&mut pc_state, 0x0F, 0x0F, 0), 0x14);
+        assert_eq!(sub_carry(&mut pc_state, 0x00, 0x01, 0), 0x99);
+        assert_eq!(flags(&pc_state), (true, false, false, false));
+    }
 }
@@ -1,11 +1,13 @@
 use super::super::clocks;
 use super::super::memory::addressing;
 use super::super::memory::addressing::Addressing;
-use super::super::memory::memory;
+use super::super::memory::bus;
 use super::super::ports;
 use super::instruction_set;
 use super::pc_state;
 
+use std::sync::OnceLock;
+
 pub struct Instruction {}
 
 // Page Delay version of addressing modes (only applicable to some indexed modes, that can carry).)
@@ -21,8 +23,8 @@ use AddressMode::*;
 use OpName::*;
 use RegisterName::*;
 
-#[derive(Debug, Copy, Clone)]
-enum RegisterName {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RegisterName {
     N,
     X,
     Y,
@@ -30,8 +32,8 @@ enum RegisterName {
     S,
 } // 'N - Null/No register
 
-#[derive(Debug, Copy, Clone)]
-enum OpName {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpName {
     Adc,
     And,
     Asl,
@@ -76,14 +78,29 @@ enum OpName {
     Pha,
     Pla,
     Br(u8, bool),
+    // Undocumented op-codes
     Isc,
     Slo,
-    Lax, // Undocumented op-codes
-    NoOP,
+    Lax,
+    Rla,
+    Sre,
+    Rra,
+    Anc,
+    Alr,
+    Arr,
+    Ane,
+    Lxa,
+    Sbx,
+    Las,
+    Sha,
+    Shx,
+    Shy,
+    Tas,
+    Jam,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum AddressMode {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AddressMode {
     Imp(RegisterName, RegisterName), // (Read, Write)
     IzxR,
     IzyR,
@@ -98,194 +115,237 @@ enum AddressMode {
     AbxRD,
     AbyRD,
     IzxW,
-    IzyW,
     ZpW,
     ZpxW,
     AbsW,
-    AbxW,
+    IzyWD,
     AbxWD,
-    AbyW,
+    AbyWD,
     Acc,
     IzxRegW,
     ZpRegW,
     ZpxRegW,
     ZpyRegW,
-    IzyRegW,
     AbsRegW,
     None,
+    IzyRegWD,
     AbxRegWD,
     AbyRegWD,
-    NoA,
 }
 
+type OpFn = fn(&mut clocks::Clock, &mut pc_state::PcState, &mut dyn bus::Bus, u8) -> u8;
+type RegisterReadFn = fn(&pc_state::PcState) -> u8;
+type RegisterWriteFn = fn(&mut pc_state::PcState, u8);
+
+// How the (memory) operand is read/written.
+#[derive(Copy, Clone)]
+enum Access {
+    Read,
+    ReadWrite,
+    Accumulator,
+    RegisterWrite,
+}
+
+/// A decoded op-code, everything needed to execute the instruction (without decoding it again).
+#[derive(Copy, Clone)]
+enum Execution {
+    SingleByte(RegisterReadFn, RegisterWriteFn, OpFn),
+    ReadWrite(&'static Addressing, Access, OpFn),
+    HighByteStore(&'static Addressing, OpFn),
+    Jsr,
+    Brk,
+    Rti,
+    Rts,
+    Jump(&'static Addressing),
+    Php,
+    Plp,
+    Pha,
+    Pla,
+    Jam,
+    Branch(u8, u8),
+}
+
+// Decoded once (on first use), rather than on every step.
+static EXECUTION_TABLE: OnceLock<[Execution; 256]> = OnceLock::new();
+
 impl Instruction {
-    pub fn execute(op_code: u8, clock: &mut clocks::Clock, memory: &mut memory::Memory, pc_state: &mut pc_state::PcState, ports: &mut ports::Ports) {
+    pub fn execute(op_code: u8, clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, pc_state: &mut pc_state::PcState, ports: &mut ports::Ports) {
+        let table = EXECUTION_TABLE.get_or_init(|| std::array::from_fn(|op_code| Self::decode_execution(op_code as u8)));
+        Self::execute_decoded(table[op_code as usize], clock, memory, pc_state, ports);
+    }
+
+    /// Decode the op-code on every call (rather than using the table), for comparison/benchmarking.
+    pub fn execute_without_table(op_code: u8, clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, pc_state: &mut pc_state::PcState, ports: &mut ports::Ports) {
+        Self::execute_decoded(Self::decode_execution(op_code), clock, memory, pc_state, ports);
+    }
+
+    fn decode_execution(op_code: u8) -> Execution {
+        let (op_name, address_mode) = Self::decode(op_code);
+        match Self::execution(op_name, address_mode) {
+            Ok(execution) => execution,
+            Err(err) => panic!("Ocode not implemented: 0x{:x}. ({})", op_code, err),
+        }
+    }
+
+    fn execute_decoded(execution: Execution, clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, pc_state: &mut pc_state::PcState, ports: &mut ports::Ports) {
+        match execution {
+            Execution::SingleByte(read, write, op) => instruction_set::single_byte_instruction(clock, pc_state, memory, read, write, op),
+            Execution::ReadWrite(address, Access::Read, op) => instruction_set::read_write_instruction(clock, pc_state, memory, address, MEMORY_READ, MEMORY_NULL, op),
+            Execution::ReadWrite(address, Access::ReadWrite, op) => instruction_set::read_write_instruction(clock, pc_state, memory, address, MEMORY_READ, MEMORY_WRITE, op),
+            Execution::ReadWrite(address, Access::Accumulator, op) => instruction_set::read_write_instruction(clock, pc_state, memory, address, ACCUMULATOR_READ, ACCUMULATOR_WRITE, op),
+            Execution::ReadWrite(address, Access::RegisterWrite, op) => instruction_set::read_write_instruction(clock, pc_state, memory, address, NULL_READ, REG_WRITE, op),
+            Execution::HighByteStore(address, op) => instruction_set::high_byte_store_instruction(clock, pc_state, memory, address, op),
+            Execution::Jsr => instruction_set::jump_sub_routine_instruction(clock, pc_state, memory),
+            Execution::Brk => instruction_set::break_instruction(clock, pc_state, memory),
+            Execution::Rti => instruction_set::return_from_interrupt(clock, pc_state, memory),
+            Execution::Rts => instruction_set::return_from_sub_routine_instruction(clock, pc_state, memory),
+            Execution::Jump(address) => instruction_set::jump_instruction(clock, pc_state, memory, address),
+            Execution::Php => instruction_set::php_instruction(clock, pc_state, memory),
+            Execution::Plp => instruction_set::plp_instruction(clock, pc_state, memory),
+            Execution::Pha => instruction_set::pha_instruction(clock, pc_state, memory),
+            Execution::Pla => instruction_set::pla_instruction(clock, pc_state, memory),
+            Execution::Jam => instruction_set::jam_instruction(clock, pc_state),
+            Execution::Branch(condition_mask, condition) => instruction_set::branch_instruction(clock, pc_state, memory, condition_mask, condition),
+        }
+    }
+
+    fn execution(op_arg: OpName, addr: AddressMode) -> Result<Execution, String> {
         use instruction_set::*;
 
-        let op_fn = |op| match op {
-            Adc => adc,
-            And => and,
-            Asl => asl,
-            Bit => bit,
-            Clc => clc,
-            Cld => cld,
-            Cli => cli,
-            Clv => clv,
-            Cmp => cmp,
-            Cpx => cpx,
-            Cpy => cpy,
-            Dcp => dcp,
-            Dec => dec,
-            Eor => eor,
-            Inc => inc,
-            Isc => isc,
-            Lax => lax,
-            Lda => lda,
-            Ldx => ldx,
-            Ldy => ldy,
-            Lsr => lsr,
-            Nop => nop,
-            Or => or,
-            Rol => rol,
-            Ror => ror,
-            Sax => sax,
-            Sbc => sbc,
-            Sec => sec,
-            Sed => sed,
-            Sei => sei,
-            Slo => slo,
-            Sta => sta,
-            Stx => stx,
-            Sty => sty,
-            TNoStat => t_no_status,
-            TStat => t_status,
-            _ => {
-                panic!("Unexpected operator {:?}", op);
+        let op_fn = |op| -> OpFn {
+            match op {
+                Adc => adc,
+                And => and,
+                Asl => asl,
+                Bit => bit,
+                Clc => clc,
+                Cld => cld,
+                Cli => cli,
+                Clv => clv,
+                Cmp => cmp,
+                Cpx => cpx,
+                Cpy => cpy,
+                Dcp => dcp,
+                Dec => dec,
+                Eor => eor,
+                Inc => inc,
+                Isc => isc,
+                Lax => lax,
+                Rla => rla,
+                Sre => sre,
+                Rra => rra,
+                Anc => anc,
+                Alr => alr,
+                Arr => arr,
+                Ane => ane,
+                Lxa => lxa,
+                Sbx => sbx,
+                Las => las,
+                Sha => sha,
+                Shx => shx,
+                Shy => shy,
+                Tas => tas,
+                Lda => lda,
+                Ldx => ldx,
+                Ldy => ldy,
+                Lsr => lsr,
+                Nop => nop,
+                Or => or,
+                Rol => rol,
+                Ror => ror,
+                Sax => sax,
+                Sbc => sbc,
+                Sec => sec,
+                Sed => sed,
+                Sei => sei,
+                Slo => slo,
+                Sta => sta,
+                Stx => stx,
+                Sty => sty,
+                TNoStat => t_no_status,
+                TStat => t_status,
+                _ => {
+                    panic!("Unexpected operator {:?}", op);
+                }
             }
         };
 
-        let addressing_fn = |addr| match addr {
-            IzxR | IzxRegW | IzxW => &Addressing::Izx,
-            IzyR | IzyRegW | IzyW => &Addressing::Izy,
-            ImmR => &Addressing::Imm,
-            ZpR | ZpW | ZpRegW => &Addressing::Zp,
-            ZpxR | ZpxW | ZpxRegW => &Addressing::Zpx,
-            ZpyR | ZpyRegW => &Addressing::Zpy,
-            IzyRD => &Addressing::IZYPageDelay,
-            AbsR | AbsW | AbsRegW => &Addressing::Abs,
-            AbxR | AbxW | AbyW => &Addressing::Abx,
-            AbyR => &Addressing::Aby,
-            AbxRD | AbxWD => &Addressing::AbxPageDelay,
-            AbyRD => &Addressing::AbyPageDelay,
-            Acc => &Addressing::Accumulator,
-
-            _ => {
-                panic!("Unexpected addressing mode {:?}", addr);
+        let addressing_fn = |addr| Self::addressing(addr).unwrap_or_else(|| panic!("Unexpected addressing mode {:?}", addr));
+
+        let read_fn = |read_type| -> RegisterReadFn {
+            match read_type {
+                N => pc_state::read_null,
+                X => pc_state::read_x,
+                Y => pc_state::read_y,
+                A => pc_state::read_a,
+                S => pc_state::read_s,
             }
         };
 
-        let read_fn = |read_type| match read_type {
-            N => pc_state::read_null,
-            X => pc_state::read_x,
-            Y => pc_state::read_y,
-            A => pc_state::read_a,
-            S => pc_state::read_s,
+        let write_fn = |write_type| -> RegisterWriteFn {
+            match write_type {
+                N => pc_state::write_null,
+                X => pc_state::write_x,
+                Y => pc_state::write_y,
+                A => pc_state::write_a,
+                S => pc_state::write_s,
+            }
         };
 
-        let write_fn = |write_type| match write_type {
-            N => pc_state::write_null,
-            X => pc_state::write_x,
-            Y => pc_state::write_y,
-            A => pc_state::write_a,
-            S => pc_state::write_s,
-        };
+        match (addr, op_arg) {
+            (Imp(r, w), o) => Ok(Execution::SingleByte(read_fn(r), write_fn(w), op_fn(o))),
 
-        let mut op = |op_arg, addr| {
-            match (addr, op_arg) {
-                (Imp(r, w), o) => {
-                    instruction_set::single_byte_instruction(clock, pc_state, memory, read_fn(r), write_fn(w), op_fn(o));
-                    Ok(())
-                }
+            (read_null @ (IzxR | IzyR | ImmR | ZpR | ZpxR | ZpyR | IzyRD | AbsR | AbxR | AbyR | AbxRD | AbyRD), o) => Ok(Execution::ReadWrite(addressing_fn(read_null), Access::Read, op_fn(o))),
+            (read_write @ (IzxW | ZpW | ZpxW | AbsW), o) => Ok(Execution::ReadWrite(addressing_fn(read_write), Access::ReadWrite, op_fn(o))),
+            // Indexed read-modify-write always takes the extra cycle (the dummy read, regardless of page crossing).
+            (read_write @ (IzyWD | AbxWD | AbyWD), o) => Ok(Execution::ReadWrite(addressing_fn(read_write), Access::ReadWrite, op_fn(o))),
+            (Acc, o) => Ok(Execution::ReadWrite(&Addressing::Accumulator, Access::Accumulator, op_fn(o))),
+            (reg_write @ (IzxRegW | ZpRegW | ZpxRegW | AbsRegW | ZpyRegW), o) => Ok(Execution::ReadWrite(addressing_fn(reg_write), Access::RegisterWrite, op_fn(o))),
 
-                (read_null @ (IzxR | IzyR | ImmR | ZpR | ZpxR | ZpyR | IzyRD | AbsR | AbxR | AbyR | AbxRD | AbyRD), o) => {
-                    instruction_set::read_write_instruction(clock, pc_state, memory, addressing_fn(read_null), MEMORY_READ, MEMORY_NULL, op_fn(o));
-                    Ok(())
-                }
-                (read_write @ (IzxW | IzyW | ZpW | ZpxW | AbsW | AbxWD | AbxW | AbyW), o) => {
-                    instruction_set::read_write_instruction(clock, pc_state, memory, addressing_fn(read_write), MEMORY_READ, MEMORY_WRITE, op_fn(o));
-                    Ok(())
-                }
-                (Acc, o) => {
-                    instruction_set::read_write_instruction(clock, pc_state, memory, &Addressing::Accumulator, ACCUMULATOR_READ, ACCUMULATOR_WRITE, op_fn(o));
-                    Ok(())
-                }
-                (reg_write @ (IzxRegW | ZpRegW | ZpxRegW | IzyRegW | AbsRegW | ZpyRegW), o) => {
-                    instruction_set::read_write_instruction(clock, pc_state, memory, addressing_fn(reg_write), NULL_READ, REG_WRITE, op_fn(o));
-                    Ok(())
-                }
+            // Unstable stores, the stored value (and on a page cross, the address) is 'anded' with the high byte of the address + 1.
+            (reg_write @ (IzyRegWD | AbxRegWD | AbyRegWD), o @ (Sha | Shx | Shy | Tas)) => Ok(Execution::HighByteStore(addressing_fn(reg_write), op_fn(o))),
+            (reg_write @ (IzyRegWD | AbxRegWD | AbyRegWD), o) => Ok(Execution::ReadWrite(addressing_fn(reg_write), Access::RegisterWrite, op_fn(o))),
 
-                (AbxRegWD, o) => {
-                    let mut my_pc_state = PcStateCollection::new(clock, pc_state, memory);
-                    instruction_set::read_write_instruction_additional_delay(&mut my_pc_state, &Addressing::Abx, NULL_READ, REG_WRITE, op_fn(o), pc_state::PcState::CYCLES_TO_CLOCK);
-                    Ok(())
-                }
-                (AbyRegWD, o) => {
-                    let mut my_pc_state = PcStateCollection::new(clock, pc_state, memory);
-                    instruction_set::read_write_instruction_additional_delay(&mut my_pc_state, &Addressing::Aby, NULL_READ, REG_WRITE, op_fn(o), pc_state::PcState::CYCLES_TO_CLOCK);
-                    Ok(())
-                }
-
-                (None, Jsr) => {
-                    instruction_set::jump_sub_routine_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Brk) => {
-                    instruction_set::break_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Rti) => {
-                    instruction_set::return_from_interrupt(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Rts) => {
-                    instruction_set::return_from_sub_routine_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, JmpAbs) => {
-                    instruction_set::jump_instruction(clock, pc_state, memory, &Addressing::Abs);
-                    Ok(())
-                }
-                (None, JmpInd) => {
-                    instruction_set::jump_instruction(clock, pc_state, memory, &Addressing::Indirect);
-                    Ok(())
-                }
-                (None, Php) => {
-                    instruction_set::php_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Plp) => {
-                    instruction_set::plp_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Pha) => {
-                    instruction_set::pha_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
-                (None, Pla) => {
-                    instruction_set::pla_instruction(clock, pc_state, memory);
-                    Ok(())
-                }
+            (None, Jsr) => Ok(Execution::Jsr),
+            (None, Brk) => Ok(Execution::Brk),
+            (None, Rti) => Ok(Execution::Rti),
+            (None, Rts) => Ok(Execution::Rts),
+            (None, JmpAbs) => Ok(Execution::Jump(&Addressing::Abs)),
+            (None, JmpInd) => Ok(Execution::Jump(&Addressing::Indirect)),
+            (None, Php) => Ok(Execution::Php),
+            (None, Plp) => Ok(Execution::Plp),
+            (None, Pha) => Ok(Execution::Pha),
+            (None, Pla) => Ok(Execution::Pla),
+            (None, Jam) => Ok(Execution::Jam),
+            (None, Br(m, v)) => Ok(Execution::Branch(1 << m, (1 << m) * (v as u8))), // N == 1
+            _ => Err(format!("Unexpected address operator combination {:?} {:?}", addr, op_arg)),
+        }
+    }
 
-                (None, Br(m, v)) => {
-                    instruction_set::branch_instruction(clock, pc_state, memory, 1 << m, (1 << m) * (v as u8));
-                    Ok(())
-                } // N == 1
-                _ => Err(format!("Unexpected address operator combination {:?} {:?}", addr, op_arg)),
-            }
-        };
+    /// The memory addressing used by the address mode ('None' for implied/accumulator/stack/flow control modes).
+    pub fn addressing(addr: AddressMode) -> Option<&'static Addressing> {
+        match addr {
+            IzxR | IzxRegW | IzxW => Some(&Addressing::Izx),
+            IzyR | IzyRegWD | IzyWD => Some(&Addressing::Izy),
+            ImmR => Some(&Addressing::Imm),
+            ZpR | ZpW | ZpRegW => Some(&Addressing::Zp),
+            ZpxR | ZpxW | ZpxRegW => Some(&Addressing::Zpx),
+            ZpyR | ZpyRegW => Some(&Addressing::Zpy),
+            IzyRD => Some(&Addressing::IZYPageDelay),
+            AbsR | AbsW | AbsRegW => Some(&Addressing::Abs),
+            AbxR | AbxWD | AbxRegWD => Some(&Addressing::Abx),
+            AbyR | AbyWD | AbyRegWD => Some(&Addressing::Aby),
+            AbxRD => Some(&Addressing::AbxPageDelay),
+            AbyRD => Some(&Addressing::AbyPageDelay),
+            Acc => Some(&Addressing::Accumulator),
+            Imp(..) | None => Option::None,
+        }
+    }
 
+    /// The op-code table, shared by execution and the disassembler.
+    pub fn decode(op_code: u8) -> (OpName, AddressMode) {
         // Mnemonic simplifications
-        const NA: (OpName, AddressMode) = (NoOP, NoA); // Not applicable/operation not implemented.
+        const JAM: (OpName, AddressMode) = (Jam, None); // Halts the CPU (also known as 'KIL').
 
         // Bpl: 0x80, 0x00, N == 0, Bmi: 0x80, 0x80, N == 1, Bvc: 0x40, 0x00, V == 0, Bvs: 0x40, 0x40, V == 1,
         // Bcc: 0x01, 0x00, C == 0, Bcs: 0x01, 0x01, C == 1, Bne: 0x02, 0x00, Z == 0, Beo: 0x02, 0x02, Z == 1,
@@ -300,58 +360,248 @@ impl Instruction {
             (Br(1, true), None),
         );
 
-        let mut low = |(op0, a0), (op1, a1), (op2, a2), (op3, a3), (op4, a4), (op5, a5), (op6, a6), (op7, a7)| match op_code & 0x7 {
-            0 => op(op0, a0),
-            1 => op(op1, a1),
-            2 => op(op2, a2),
-            3 => op(op3, a3),
-            4 => op(op4, a4),
-            5 => op(op5, a5),
-            6 => op(op6, a6),
-            7 => op(op7, a7),
+        let low = |op0, op1, op2, op3, op4, op5, op6, op7| match op_code & 0x7 {
+            0 => op0,
+            1 => op1,
+            2 => op2,
+            3 => op3,
+            4 => op4,
+            5 => op5,
+            6 => op6,
+            7 => op7,
             _ => unreachable!("Me panic? Not Possible."),
         };
 
-        if let Err(err) = match op_code & 0xF8 {
-            0x00 => low((Brk, None), (Or, IzxR), NA, (Slo, IzxW), (Nop, ZpR), (Or, ZpR), (Asl, ZpW), (Slo, ZpW)),
-            0x08 => low((Php, None), (Or, ImmR), (Asl, Imp(A, A)), NA, (Nop, AbsR), (Or, AbsR), (Asl, AbsW), (Slo, AbsW)),
-            0x10 => low(bpl, (Or, IzyRD), NA, (Slo, IzyW), (Nop, ZpxR), (Or, ZpxR), (Asl, ZpxW), (Slo, ZpxW)),
-            0x18 => low((Clc, Imp(N, N)), (Or, AbyRD), (Nop, Imp(N, N)), (Slo, AbyW), (Nop, AbxR), (Or, AbxRD), (Asl, AbxWD), (Slo, AbxW)),
-            0x20 => low((Jsr, None), (And, IzxR), NA, NA, (Bit, ZpR), (And, ZpR), (Rol, ZpW), NA),
-            0x28 => low((Plp, None), (And, ImmR), (Rol, Acc), NA, (Bit, AbsR), (And, AbsR), (Rol, AbsW), NA),
-            0x30 => low(bmi, (And, IzyRD), NA, NA, (Nop, ZpxR), (And, ZpxR), (Rol, ZpxW), NA),
-            0x38 => low((Sec, Imp(N, N)), (And, AbyRD), (Nop, Imp(N, N)), NA, (Nop, AbxR), (And, AbxRD), (Rol, AbxW), NA),
-            0x40 => low((Rti, None), (Eor, IzxR), NA, NA, (Nop, ZpR), (Eor, ZpR), (Lsr, ZpW), NA),
-            0x48 => low((Pha, None), (Eor, ImmR), (Lsr, Imp(A, A)), NA, (JmpAbs, None), (Eor, AbsR), (Lsr, AbsW), NA),
-            0x50 => low(bvc, (Eor, IzyR), NA, NA, (Nop, ZpxR), (Eor, ZpxR), (Lsr, ZpxW), NA),
-            0x58 => low((Cli, Imp(N, N)), (Eor, AbyR), (Nop, Imp(N, N)), NA, (Nop, AbxR), (Eor, AbxR), (Lsr, AbxWD), NA),
-            0x60 => low((Rts, None), (Adc, IzxR), NA, NA, (Nop, ZpR), (Adc, ZpR), (Ror, ZpW), NA),
-            0x68 => low((Pla, None), (Adc, ImmR), (Ror, Acc), NA, (JmpInd, None), (Adc, AbsR), (Ror, AbsW), NA),
-            0x70 => low(bvs, (Adc, IzyRD), NA, NA, (Nop, ZpxR), (Adc, ZpxR), (Ror, ZpxW), NA),
-            0x78 => low((Sei, Imp(N, N)), (Adc, AbyRD), (Nop, Imp(N, N)), NA, (Nop, AbxR), (Adc, AbxRD), (Ror, AbxW), NA),
+        match op_code & 0xF8 {
+            0x00 => low((Brk, None), (Or, IzxR), JAM, (Slo, IzxW), (Nop, ZpR), (Or, ZpR), (Asl, ZpW), (Slo, ZpW)),
+            0x08 => low((Php, None), (Or, ImmR), (Asl, Imp(A, A)), (Anc, ImmR), (Nop, AbsR), (Or, AbsR), (Asl, AbsW), (Slo, AbsW)),
+            0x10 => low(bpl, (Or, IzyRD), JAM, (Slo, IzyWD), (Nop, ZpxR), (Or, ZpxR), (Asl, ZpxW), (Slo, ZpxW)),
+            0x18 => low((Clc, Imp(N, N)), (Or, AbyRD), (Nop, Imp(N, N)), (Slo, AbyWD), (Nop, AbxRD), (Or, AbxRD), (Asl, AbxWD), (Slo, AbxWD)),
+            0x20 => low((Jsr, None), (And, IzxR), JAM, (Rla, IzxW), (Bit, ZpR), (And, ZpR), (Rol, ZpW), (Rla, ZpW)),
+            0x28 => low((Plp, None), (And, ImmR), (Rol, Acc), (Anc, ImmR), (Bit, AbsR), (And, AbsR), (Rol, AbsW), (Rla, AbsW)),
+            0x30 => low(bmi, (And, IzyRD), JAM, (Rla, IzyWD), (Nop, ZpxR), (And, ZpxR), (Rol, ZpxW), (Rla, ZpxW)),
+            0x38 => low((Sec, Imp(N, N)), (And, AbyRD), (Nop, Imp(N, N)), (Rla, AbyWD), (Nop, AbxRD), (And, AbxRD), (Rol, AbxWD), (Rla, AbxWD)),
+            0x40 => low((Rti, None), (Eor, IzxR), JAM, (Sre, IzxW), (Nop, ZpR), (Eor, ZpR), (Lsr, ZpW), (Sre, ZpW)),
+            0x48 => low((Pha, None), (Eor, ImmR), (Lsr, Imp(A, A)), (Alr, ImmR), (JmpAbs, None), (Eor, AbsR), (Lsr, AbsW), (Sre, AbsW)),
+            0x50 => low(bvc, (Eor, IzyRD), JAM, (Sre, IzyWD), (Nop, ZpxR), (Eor, ZpxR), (Lsr, ZpxW), (Sre, ZpxW)),
+            0x58 => low((Cli, Imp(N, N)), (Eor, AbyRD), (Nop, Imp(N, N)), (Sre, AbyWD), (Nop, AbxRD), (Eor, AbxRD), (Lsr, AbxWD), (Sre, AbxWD)),
+            0x60 => low((Rts, None), (Adc, IzxR), JAM, (Rra, IzxW), (Nop, ZpR), (Adc, ZpR), (Ror, ZpW), (Rra, ZpW)),
+            0x68 => low((Pla, None), (Adc, ImmR), (Ror, Acc), (Arr, ImmR), (JmpInd, None), (Adc, AbsR), (Ror, AbsW), (Rra, AbsW)),
+            0x70 => low(bvs, (Adc, IzyRD), JAM, (Rra, IzyWD), (Nop, ZpxR), (Adc, ZpxR), (Ror, ZpxW), (Rra, ZpxW)),
+            0x78 => low((Sei, Imp(N, N)), (Adc, AbyRD), (Nop, Imp(N, N)), (Rra, AbyWD), (Nop, AbxRD), (Adc, AbxRD), (Ror, AbxWD), (Rra, AbxWD)),
             0x80 => low((Nop, ImmR), (Sta, IzxRegW), (Nop, ImmR), (Sax, IzxRegW), (Sty, ZpRegW), (Sta, ZpRegW), (Stx, ZpRegW), (Sax, ZpRegW)),
-            0x88 => low((Dec, Imp(Y, Y)), (Nop, ImmR), (TStat, Imp(X, A)), NA, (Sty, AbsRegW), (Sta, AbsRegW), (Stx, AbsRegW), (Sax, AbsRegW)),
-            0x90 => low(bcc, (Sta, IzyRegW), NA, NA, (Sty, ZpxRegW), (Sta, ZpxRegW), (Stx, ZpyRegW), (Sax, ZpyRegW)),
-            0x98 => low((TStat, Imp(Y, A)), (Sta, AbyRegWD), (TNoStat, Imp(X, S)), NA, NA, (Sta, AbxRegWD), NA, NA),
+            0x88 => low((Dec, Imp(Y, Y)), (Nop, ImmR), (TStat, Imp(X, A)), (Ane, ImmR), (Sty, AbsRegW), (Sta, AbsRegW), (Stx, AbsRegW), (Sax, AbsRegW)),
+            0x90 => low(bcc, (Sta, IzyRegWD), JAM, (Sha, IzyRegWD), (Sty, ZpxRegW), (Sta, ZpxRegW), (Stx, ZpyRegW), (Sax, ZpyRegW)),
+            0x98 => low((TStat, Imp(Y, A)), (Sta, AbyRegWD), (TNoStat, Imp(X, S)), (Tas, AbyRegWD), (Shy, AbxRegWD), (Sta, AbxRegWD), (Shx, AbyRegWD), (Sha, AbyRegWD)),
             0xA0 => low((Ldy, ImmR), (Lda, IzxR), (Ldx, ImmR), (Lax, IzxR), (Ldy, ZpR), (Lda, ZpR), (Ldx, ZpR), (Lax, ZpR)),
-            0xA8 => low((TStat, Imp(A, Y)), (Lda, ImmR), (TStat, Imp(A, X)), NA, (Ldy, AbsR), (Lda, AbsR), (Ldx, AbsR), (Lax, AbsR)),
-            0xB0 => low(bcs, (Lda, IzyRD), NA, (Lax, IzyR), (Ldy, ZpxR), (Lda, ZpxR), (Ldx, ZpyR), (Lax, ZpyR)),
-            0xB8 => low((Clv, Imp(N, N)), (Lda, AbyRD), (TNoStat, Imp(S, X)), NA, (Ldy, AbxRD), (Lda, AbxRD), (Ldx, AbyRD), (Lax, AbyR)),
-            0xC0 => low((Cpy, ImmR), (Cmp, IzxR), (Nop, ImmR), (Dcp, IzxR), (Cpy, ZpR), (Cmp, ZpR), (Dec, ZpW), (Dcp, ZpR)),
-            0xC8 => low((Inc, Imp(Y, Y)), (Cmp, ImmR), (Dec, Imp(X, X)), NA, (Cpy, AbsR), (Cmp, AbsR), (Dec, AbsW), (Dcp, AbsR)),
-            0xD0 => low(bne, (Cmp, IzyRD), NA, (Dcp, IzyRD), (Nop, ZpxR), (Cmp, ZpxR), (Dec, ZpxW), (Dcp, ZpxR)),
-            0xD8 => low((Cld, Imp(N, N)), (Cmp, AbyRD), (Nop, Imp(N, N)), (Dcp, AbyRD), (Nop, AbxR), (Cmp, AbxRD), (Dec, AbxWD), (Dcp, AbxRD)),
+            0xA8 => low((TStat, Imp(A, Y)), (Lda, ImmR), (TStat, Imp(A, X)), (Lxa, ImmR), (Ldy, AbsR), (Lda, AbsR), (Ldx, AbsR), (Lax, AbsR)),
+            0xB0 => low(bcs, (Lda, IzyRD), JAM, (Lax, IzyRD), (Ldy, ZpxR), (Lda, ZpxR), (Ldx, ZpyR), (Lax, ZpyR)),
+            0xB8 => low((Clv, Imp(N, N)), (Lda, AbyRD), (TStat, Imp(S, X)), (Las, AbyRD), (Ldy, AbxRD), (Lda, AbxRD), (Ldx, AbyRD), (Lax, AbyRD)),
+            0xC0 => low((Cpy, ImmR), (Cmp, IzxR), (Nop, ImmR), (Dcp, IzxW), (Cpy, ZpR), (Cmp, ZpR), (Dec, ZpW), (Dcp, ZpW)),
+            0xC8 => low((Inc, Imp(Y, Y)), (Cmp, ImmR), (Dec, Imp(X, X)), (Sbx, ImmR), (Cpy, AbsR), (Cmp, AbsR), (Dec, AbsW), (Dcp, AbsW)),
+            0xD0 => low(bne, (Cmp, IzyRD), JAM, (Dcp, IzyWD), (Nop, ZpxR), (Cmp, ZpxR), (Dec, ZpxW), (Dcp, ZpxW)),
+            0xD8 => low((Cld, Imp(N, N)), (Cmp, AbyRD), (Nop, Imp(N, N)), (Dcp, AbyWD), (Nop, AbxRD), (Cmp, AbxRD), (Dec, AbxWD), (Dcp, AbxWD)),
             0xE0 => low((Cpx, ImmR), (Sbc, IzxR), (Nop, ImmR), (Isc, IzxW), (Cpx, ZpR), (Sbc, ZpR), (Inc, ZpW), (Isc, ZpW)),
-            0xE8 => low((Inc, Imp(X, X)), (Sbc, ImmR), (Nop, Imp(A, A)), NA, (Cpx, AbsR), (Sbc, AbsR), (Inc, AbsW), (Isc, AbsW)),
-            0xF0 => low(beo, (Sbc, IzyRD), NA, (Isc, IzyW), (Nop, ZpxR), (Sbc, ZpxR), (Inc, ZpxW), (Isc, ZpxW)),
-            0xF8 => low((Sed, Imp(N, N)), (Sbc, AbyRD), (Nop, Imp(N, N)), (Isc, AbyW), (Nop, AbxR), (Sbc, AbxRD), (Inc, AbxWD), (Isc, AbxW)),
+            0xE8 => low((Inc, Imp(X, X)), (Sbc, ImmR), (Nop, Imp(A, A)), (Sbc, ImmR), (Cpx, AbsR), (Sbc, AbsR), (Inc, AbsW), (Isc, AbsW)),
+            0xF0 => low(beo, (Sbc, IzyRD), JAM, (Isc, IzyWD), (Nop, ZpxR), (Sbc, ZpxR), (Inc, ZpxW), (Isc, ZpxW)),
+            0xF8 => low((Sed, Imp(N, N)), (Sbc, AbyRD), (Nop, Imp(N, N)), (Isc, AbyWD), (Nop, AbxRD), (Sbc, AbxRD), (Inc, AbxWD), (Isc, AbxWD)),
 
-            _ => Err(format!("Ocode not implemented: 0x{:x}", op_code)),
-        } {
-            panic!("Ocode not implemented: 0x{:x}. ({})", op_code, err)
-        };
+            _ => unreachable!("All op-codes are covered."),
+        }
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::atari2600::clocks;
+    use crate::atari2600::cpu::instructions;
+    use crate::atari2600::cpu::pc_state;
+    use crate::atari2600::cpu::single_step;
+    use crate::atari2600::memory::bus::Bus;
+    use crate::atari2600::memory::flat_memory;
+    use crate::atari2600::ports;
+
+    // The 'JAM' op-codes lock up the CPU, the vectors describe the bus activity of a locked CPU, rather than an instruction.
+    const SKIPPED_OP_CODES: [u8; 12] = [0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2];
+    const MAX_REPORTED_FAILURES: usize = 5;
+
+    // Klaus Dormann's 6502 functional test (assembled with the default options), the binary is a full 64K image.
+    const FUNCTIONAL_TEST_ENV: &str = "FUNCTIONAL_TEST_BIN";
+    const DEFAULT_FUNCTIONAL_TEST_BIN: &str = "test_data/6502_functional_test.bin";
+    const FUNCTIONAL_TEST_START: u16 = 0x0400;
+    const FUNCTIONAL_TEST_SUCCESS_ENV: &str = "FUNCTIONAL_TEST_SUCCESS";
+    const DEFAULT_FUNCTIONAL_TEST_SUCCESS: u16 = 0x3469;
+    const FUNCTIONAL_TEST_MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+    const SAMPLE_VECTORS: &str = r#"[
+        {"name": "a9 80 00", "initial": {"pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 128]]},
+         "final": {"pc": 514, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164, "ram": [[512, 169], [513, 128]]},
+         "cycles": [[512, 169, "read"], [513, 128, "read"]]},
+        {"name": "85 10 00", "initial": {"pc": 61440, "s": 255, "a": 66, "x": 1, "y": 2, "p": 32, "ram": [[61440, 133], [61441, 16], [16, 0]]},
+         "final": {"pc": 61442, "s": 255, "a": 66, "x": 1, "y": 2, "p": 32, "ram": [[61440, 133], [61441, 16], [16, 66]]},
+         "cycles": [[61440, 133, "read"], [61441, 16, "read"], [16, 66, "write"]]}
+    ]"#;
+
+    // INC abs,x (page cross dummy read and the read-modify-write double write), JMP (ind) (page wrap),
+    // BRK (status push/interrupt disable), taken branch across a page, LDA (zp,x) (zero page wrap) and PHP.
+    const BUS_CYCLE_VECTORS: &str = r#"[
+        {"name": "fe ff 10", "initial": {"pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36, "ram": [[512, 254], [513, 255], [514, 16], [4096, 5], [4352, 127]]},
+         "final": {"pc": 515, "s": 253, "a": 0, "x": 1, "y": 0, "p": 164, "ram": [[4096, 5], [4352, 128]]},
+         "cycles": [[512, 254, "read"], [513, 255, "read"], [514, 16, "read"], [4096, 5, "read"], [4352, 127, "read"], [4352, 127, "write"], [4352, 128, "write"]]},
+        {"name": "6c ff 02", "initial": {"pc": 768, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[768, 108], [769, 255], [770, 2], [767, 52], [512, 18]]},
+         "final": {"pc": 4660, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": []},
+         "cycles": [[768, 108, "read"], [769, 255, "read"], [770, 2, "read"], [767, 52, "read"], [512, 18, "read"]]},
+        {"name": "00 44 00", "initial": {"pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 1, "ram": [[512, 0], [513, 68], [65534, 0], [65535, 48]]},
+         "final": {"pc": 12288, "s": 250, "a": 0, "x": 0, "y": 0, "p": 5, "ram": [[509, 2], [508, 2], [507, 49]]},
+         "cycles": [[512, 0, "read"], [513, 68, "read"], [509, 2, "write"], [508, 2, "write"], [507, 49, "write"], [65534, 0, "read"], [65535, 48, "read"]]},
+        {"name": "d0 05 ea", "initial": {"pc": 765, "s": 253, "a": 0, "x": 0, "y": 0, "p": 0, "ram": [[765, 208], [766, 5], [767, 234]]},
+         "final": {"pc": 772, "s": 253, "a": 0, "x": 0, "y": 0, "p": 0, "ram": []},
+         "cycles": [[765, 208, "read"], [766, 5, "read"], [767, 234, "read"], [516, 0, "read"]]},
+        {"name": "a1 fe 00", "initial": {"pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36, "ram": [[512, 161], [513, 254], [254, 9], [255, 52], [0, 18], [4660, 119]]},
+         "final": {"pc": 514, "s": 253, "a": 119, "x": 1, "y": 0, "p": 36, "ram": []},
+         "cycles": [[512, 161, "read"], [513, 254, "read"], [254, 9, "read"], [255, 52, "read"], [0, 18, "read"], [4660, 119, "read"]]},
+        {"name": "08 00 00", "initial": {"pc": 512, "s": 255, "a": 0, "x": 0, "y": 0, "p": 129, "ram": [[512, 8]]},
+         "final": {"pc": 513, "s": 254, "a": 0, "x": 0, "y": 0, "p": 129, "ram": [[511, 177]]},
+         "cycles": [[512, 8, "read"], [513, 0, "read"], [511, 177, "write"]]}
+    ]"#;
+
+    #[test]
+    fn test_single_step_sample_vectors() {
+        let tests = single_step::SingleStepTest::load(SAMPLE_VECTORS).unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[1].cycles.len(), 3);
+
+        for test in &tests {
+            assert_eq!(test.run(), Ok(()));
+        }
+
+        // Differences (register, RAM and timing) are reported.
+        let mut bad_test = tests[1].clone();
+        bad_test.expected.a = 0;
+        bad_test.expected.ram[2] = (16, 0);
+        bad_test.cycles.push(single_step::BusCycle { address: 16, data: 66, write: true });
+        let error = bad_test.run().unwrap_err();
+        assert!(error.contains("A: 0x42 (expected 0x0)"), "{}", error);
+        assert!(error.contains("RAM[0x0010]: 0x42 (expected 0x0)"), "{}", error);
+        assert!(error.contains("cycles: 0x3 (expected 0x4)"), "{}", error);
+        assert!(error.contains("cycle 4: missing write 0x0010 0x42"), "{}", error);
+    }
+
+    #[test]
+    fn test_single_step_bus_cycles() {
+        // Each bus access (including the dummy accesses) happens on its own cycle.
+        for test in single_step::SingleStepTest::load(BUS_CYCLE_VECTORS).unwrap() {
+            assert_eq!(test.run(), Ok(()));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the single step vectors (see SINGLE_STEP_TESTS_DIR)"]
+    fn test_single_step_vectors() {
+        let dir = single_step::single_step_dir();
+        assert!(dir.is_dir(), "No single step vectors found in {:?} (set {}).", dir, single_step::SINGLE_STEP_DIR_ENV);
+
+        let mut total = 0;
+        let mut failed_op_codes = Vec::new();
+        for op_code in (0..=0xFF_u8).filter(|op_code| !SKIPPED_OP_CODES.contains(op_code)) {
+            if let Some((count, failures)) = single_step::run_op_code(&dir, op_code) {
+                total += count;
+                if !failures.is_empty() {
+                    println!("Op-code 0x{:02X}: {}/{} failed", op_code, failures.len(), count);
+                    for failure in failures.iter().take(MAX_REPORTED_FAILURES) {
+                        println!("    {}", failure);
+                    }
+                    failed_op_codes.push(op_code);
+                }
+            }
+        }
+
+        println!("Single step vectors run: {}", total);
+        assert!(total > 0, "No single step vectors found in {:?}", dir);
+        assert!(failed_op_codes.is_empty(), "Failing op-codes: {:02X?}", failed_op_codes);
+    }
+
+    #[test]
+    fn test_functional_test_binary() {
+        let filename = std::env::var(FUNCTIONAL_TEST_ENV).unwrap_or(DEFAULT_FUNCTIONAL_TEST_BIN.to_string());
+        let binary = match std::fs::read(&filename) {
+            Ok(binary) => binary,
+            Err(_) => {
+                println!("Skipping functional test, '{}' not found (set {}).", filename, FUNCTIONAL_TEST_ENV);
+                return;
+            }
+        };
+        let success = std::env::var(FUNCTIONAL_TEST_SUCCESS_ENV).ok().and_then(|address| u16::from_str_radix(address.trim_start_matches("0x"), 16).ok()).unwrap_or(DEFAULT_FUNCTIONAL_TEST_SUCCESS);
+
+        let mut clock = clocks::Clock::new();
+        let mut memory = flat_memory::FlatMemory::new();
+        let mut pc_state = pc_state::PcState::new();
+        let mut ports = ports::Ports::new();
+        memory.load(0, &binary);
+        pc_state.set_pc(FUNCTIONAL_TEST_START);
+
+        // The test signals success/failure by jumping to itself (a 'trap').
+        let mut instructions = 0;
+        loop {
+            let pc = pc_state.get_pc();
+            let op_code = memory.peek(&clock, pc);
+            instructions::Instruction::execute(op_code, &mut clock, &mut memory, &mut pc_state, &mut ports);
+            instructions += 1;
+            if pc_state.get_pc() == pc || pc_state.get_halted() || instructions >= FUNCTIONAL_TEST_MAX_INSTRUCTIONS {
+                break;
+            }
+        }
+
+        assert_eq!(pc_state.get_pc(), success, "Trapped at 0x{:04X} after {} instructions, {}", pc_state.get_pc(), instructions, pc_state);
+    }
+
+    #[test]
+    fn test_flat_memory_stack() {
+        // Stack accesses go to page one, not the zero page.
+        let mut clock = clocks::Clock::new();
+        let mut memory = flat_memory::FlatMemory::new();
+        let mut pc_state = pc_state::PcState::new();
+        let mut ports = ports::Ports::new();
+        memory.load(0x0200, &[0x20, 0x00, 0x03]); // JSR $0300
+        pc_state.set_pc(0x0200);
+        pc_state.set_s(0xFF);
+
+        instructions::Instruction::execute(0x20, &mut clock, &mut memory, &mut pc_state, &mut ports);
+        assert_eq!(pc_state.get_pc(), 0x0300);
+        assert_eq!(pc_state.get_s(), 0xFD);
+        assert_eq!((memory.peek(&clock, 0x01FF), memory.peek(&clock, 0x01FE)), (0x02, 0x02));
+        assert_eq!((memory.peek(&clock, 0x00FF), memory.peek(&clock, 0x00FE)), (0x00, 0x00));
+    }
+
+    #[test]
+    fn test_dispatch_table_matches_decode() {
+        // Each op-code, executed via the table and by decoding it, gives the same state, memory and timing.
+        let run = |op_code: u8, table: bool| {
+            let mut clock = clocks::Clock::new();
+            let mut memory = flat_memory::FlatMemory::new();
+            let mut pc_state = pc_state::PcState::new();
+            let mut ports = ports::Ports::new();
+            for (i, value) in memory.ram.iter_mut().enumerate() {
+                *value = (i as u32).wrapping_mul(0x9E37_79B9).rotate_left(7) as u8;
+            }
+            memory.ram[0x0200] = op_code;
+            pc_state.set_pc(0x0200);
+            pc_state.set_s(0xF0);
+            pc_state.set_a(0x5A);
+            pc_state.set_x(0x81);
+            pc_state.set_y(0x37);
+            pc_state.set_p(0xC3);
+
+            if table {
+                instructions::Instruction::execute(op_code, &mut clock, &mut memory, &mut pc_state, &mut ports);
+            } else {
+                instructions::Instruction::execute_without_table(op_code, &mut clock, &mut memory, &mut pc_state, &mut ports);
+            }
+            (format!("{} S:{:X} P:{:X}", pc_state, pc_state.get_s(), pc_state.get_p()), clock.ticks, memory.ram)
+        };
+
+        for op_code in 0..=0xFF_u8 {
+            assert!(run(op_code, true) == run(op_code, false), "Op-code 0x{:02X} differs", op_code);
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod core;
+pub mod disasm;
 pub mod instruction_set;
 pub mod instructions;
 pub mod pc_state;
+#[cfg(test)]
+pub mod single_step;
+pub mod trace;
@@ -1,3 +1,4 @@
+use super::super::state;
 use bitfield::bitfield;
 use std::fmt;
 
@@ -43,6 +44,9 @@ pub struct PcState {
 
     pub s_reg: Reg8,
     pub p_reg: PcStatusFlagFields,
+
+    // Set by the 'JAM' op-codes, the CPU stops fetching instructions.
+    halted: bool,
 }
 
 impl fmt::Display for PcState {
@@ -62,9 +66,31 @@ impl PcState {
             pc_reg: 0,
             s_reg: 0,
             p_reg: PcStatusFlagFields(0),
+            halted: false,
         }
     }
 
+    pub fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_u8(self.a_reg);
+        state.write_u8(self.x_reg);
+        state.write_u8(self.y_reg);
+        state.write_u16(self.pc_reg);
+        state.write_u8(self.s_reg);
+        state.write_u8(self.p_reg.0);
+        state.write_bool(self.halted);
+    }
+
+    pub fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        self.a_reg = state.read_u8()?;
+        self.x_reg = state.read_u8()?;
+        self.y_reg = state.read_u8()?;
+        self.pc_reg = state.read_u16()?;
+        self.s_reg = state.read_u8()?;
+        self.p_reg = PcStatusFlagFields(state.read_u8()?);
+        self.halted = state.read_bool()?;
+        Ok(())
+    }
+
     pub fn get_a(&self) -> u8 {
         self.a_reg
     }
@@ -95,6 +121,10 @@ impl PcState {
         self.p_reg.0
     }
 
+    pub fn get_halted(&self) -> bool {
+        self.halted
+    }
+
     pub fn get_flags(&self) -> PcStatusFlagFields {
         PcStatusFlagFields(self.p_reg.0)
     }
@@ -193,6 +223,10 @@ impl PcState {
         self.p_reg.0 = input;
     }
 
+    pub fn set_halted(&mut self, input: bool) {
+        self.halted = input;
+    }
+
     pub fn increment_reg8(register: &mut Reg8, increment: i8) {
         *register = (*register as i8).wrapping_add(increment) as u8;
     }
@@ -0,0 +1,391 @@
+//! Per op-code CPU conformance tests.
+//!
+//! Runs the widely used 'single step' JSON test vectors (one file per op-code,
+//! eg 'a9.json') against 'Instruction::execute'.  Each vector has an initial
+//! register/RAM state, the expected final register/RAM state and the list of
+//! bus cycles (each access, and the clock it happens on, is checked).
+//!
+//! The vectors aren't part of the repository, set 'SINGLE_STEP_TESTS_DIR' to
+//! the directory containing them (eg 'ProcessorTests/6502/v1'), otherwise
+//! 'test_data/single_step' is used.  The test is ignored by default, run it with '--ignored' (it fails if there are
+//! no vectors).
+
+use super::super::clocks;
+use super::super::memory::addressing;
+use super::super::memory::bus;
+use super::super::memory::flat_memory;
+use super::super::ports;
+use super::instructions;
+use super::pc_state;
+
+use std::fs;
+use std::path;
+
+pub const SINGLE_STEP_DIR_ENV: &str = "SINGLE_STEP_TESTS_DIR";
+pub const DEFAULT_SINGLE_STEP_DIR: &str = "test_data/single_step";
+
+/// Minimal JSON representation, just enough to read the test vectors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+pub struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    pub fn parse(text: &'a str) -> Result<JsonValue, String> {
+        let mut parser = Self { data: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.data.len() {
+            return Err(format!("Unexpected trailing data at {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at {}", c as char, self.pos))
+        }
+    }
+
+    fn literal(&mut self, text: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.data[self.pos..].starts_with(text.as_bytes()) {
+            self.pos += text.len();
+            Ok(value)
+        } else {
+            Err(format!("Unexpected literal at {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(JsonValue::String(self.string()?)),
+            Some(b't') => self.literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.literal("null", JsonValue::Null),
+            Some(_) => self.number(),
+            None => Err("Unexpected end of data".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        let mut entries = Vec::new();
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                _ => return Err(format!("Expected ',' or '}}' at {}", self.pos)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        let mut values = Vec::new();
+        self.expect(b'[')?;
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                _ => return Err(format!("Expected ',' or ']' at {}", self.pos)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut result = String::new();
+        while let Some(&c) = self.data.get(self.pos) {
+            self.pos += 1;
+            match c {
+                b'"' => return Ok(result),
+                b'\\' => {
+                    let escaped = self.data.get(self.pos).copied().ok_or("Unterminated escape")?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => result.push('\n'),
+                        b't' => result.push('\t'),
+                        b'r' => result.push('\r'),
+                        b'u' => {
+                            let hex = std::str::from_utf8(&self.data[self.pos..self.pos + 4]).map_err(|e| e.to_string())?;
+                            result.push(char::from_u32(u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?).unwrap_or('?'));
+                            self.pos += 4;
+                        }
+                        other => result.push(other as char),
+                    }
+                }
+                other => result.push(other as char),
+            }
+        }
+        Err("Unterminated string".to_string())
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        while self.pos < self.data.len() && (self.data[self.pos].is_ascii_digit() || b"+-.eE".contains(&self.data[self.pos])) {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.data[start..self.pos]).map_err(|e| e.to_string())?;
+        text.parse::<f64>().map(JsonValue::Number).map_err(|e| format!("Bad number '{}' at {}: {}", text, start, e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuState {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub ram: Vec<(u16, u8)>,
+}
+
+impl CpuState {
+    fn from_json(value: &JsonValue) -> Result<Self, String> {
+        let field = |name: &str| value.get(name).and_then(|v| v.as_u64()).ok_or(format!("Missing field '{}'", name));
+
+        let mut ram = Vec::new();
+        for entry in value.get("ram").and_then(|v| v.as_array()).ok_or("Missing field 'ram'")? {
+            let pair = entry.as_array().ok_or("Bad 'ram' entry")?;
+            let address = pair.first().and_then(|v| v.as_u64()).ok_or("Bad 'ram' address")?;
+            let data = pair.get(1).and_then(|v| v.as_u64()).ok_or("Bad 'ram' data")?;
+            ram.push((address as u16, data as u8));
+        }
+
+        Ok(Self {
+            pc: field("pc")? as u16,
+            s: field("s")? as u8,
+            a: field("a")? as u8,
+            x: field("x")? as u8,
+            y: field("y")? as u8,
+            p: field("p")? as u8,
+            ram,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusCycle {
+    pub address: u16,
+    pub data: u8,
+    pub write: bool,
+}
+
+impl BusCycle {
+    fn from_json(value: &JsonValue) -> Result<Self, String> {
+        let cycle = value.as_array().ok_or("Bad 'cycles' entry")?;
+        Ok(Self {
+            address: cycle.first().and_then(|v| v.as_u64()).ok_or("Bad cycle address")? as u16,
+            data: cycle.get(1).and_then(|v| v.as_u64()).ok_or("Bad cycle data")? as u8,
+            write: cycle.get(2).and_then(|v| v.as_str()).ok_or("Bad cycle type")? == "write",
+        })
+    }
+}
+
+impl std::fmt::Display for BusCycle {
+    fn fmt(&self, dest: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(dest, "{} 0x{:04X} 0x{:02X}", if self.write { "write" } else { "read" }, self.address, self.data)
+    }
+}
+
+/// Flat RAM, recording each access (and the clock it happened on).
+struct RecordingMemory {
+    memory: flat_memory::FlatMemory,
+    cycles: Vec<(clocks::ClockType, BusCycle)>,
+}
+
+impl bus::Bus for RecordingMemory {
+    fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
+        let data = self.memory.read(clock, address);
+        self.cycles.push((clock.ticks, BusCycle { address, data, write: false }));
+        data
+    }
+
+    fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
+        self.cycles.push((clock.ticks, BusCycle { address, data, write: true }));
+        self.memory.write(clock, address, data);
+    }
+
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.memory.peek(clock, address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingleStepTest {
+    pub name: String,
+    pub initial: CpuState,
+    pub expected: CpuState,
+    pub cycles: Vec<BusCycle>,
+}
+
+impl SingleStepTest {
+    // The 'B' and unused status bits don't exist in the CPU, they're only seen when pushed to the stack (which is checked via RAM).
+    const STATUS_COMPARE_MASK: u8 = 0xCF;
+
+    pub fn load(text: &str) -> Result<Vec<Self>, String> {
+        let json = JsonParser::parse(text)?;
+        let mut tests = Vec::new();
+        for test in json.as_array().ok_or("Expected an array of tests")? {
+            tests.push(Self {
+                name: test.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                initial: CpuState::from_json(test.get("initial").ok_or("Missing 'initial'")?)?,
+                expected: CpuState::from_json(test.get("final").ok_or("Missing 'final'")?)?,
+                cycles: test.get("cycles").and_then(|v| v.as_array()).ok_or("Missing 'cycles'")?.iter().map(BusCycle::from_json).collect::<Result<_, _>>()?,
+            });
+        }
+        Ok(tests)
+    }
+
+    /// Execute the single instruction, returning a description of all differences.
+    pub fn run(&self) -> Result<(), String> {
+        let mut clock = clocks::Clock::new();
+        let mut memory = RecordingMemory {
+            memory: flat_memory::FlatMemory::new(),
+            cycles: Vec::new(),
+        };
+        let mut pc_state = pc_state::PcState::new();
+        let mut ports = ports::Ports::new();
+
+        pc_state.set_pc(self.initial.pc);
+        pc_state.set_s(self.initial.s);
+        pc_state.set_a(self.initial.a);
+        pc_state.set_x(self.initial.x);
+        pc_state.set_y(self.initial.y);
+        pc_state.set_p(self.initial.p);
+        for (address, data) in &self.initial.ram {
+            memory.memory.ram[*address as usize] = *data;
+        }
+
+        // The op-code fetch is the first cycle (as in 'Core::step').
+        let op_code = addressing::cycle_read(&mut clock, &mut memory, self.initial.pc);
+        instructions::Instruction::execute(op_code, &mut clock, &mut memory, &mut pc_state, &mut ports);
+
+        let mut errors = Vec::new();
+        let mut check = |name: &str, actual: u16, expected: u16| {
+            if actual != expected {
+                errors.push(format!("{}: 0x{:X} (expected 0x{:X})", name, actual, expected));
+            }
+        };
+
+        check("PC", pc_state.get_pc(), self.expected.pc);
+        check("S", pc_state.get_s() as u16, self.expected.s as u16);
+        check("A", pc_state.get_a() as u16, self.expected.a as u16);
+        check("X", pc_state.get_x() as u16, self.expected.x as u16);
+        check("Y", pc_state.get_y() as u16, self.expected.y as u16);
+        check("P", (pc_state.get_p() & Self::STATUS_COMPARE_MASK) as u16, (self.expected.p & Self::STATUS_COMPARE_MASK) as u16);
+        check("cycles", (clock.ticks / pc_state::PcState::CYCLES_TO_CLOCK as clocks::ClockType) as u16, self.cycles.len() as u16);
+        for (address, data) in &self.expected.ram {
+            check(&format!("RAM[0x{:04X}]", address), memory.memory.ram[*address as usize] as u16, *data as u16);
+        }
+
+        // Each access happens at the end of its cycle.
+        for (i, (ticks, cycle)) in memory.cycles.iter().enumerate() {
+            let expected_ticks = (i as clocks::ClockType + 1) * pc_state::PcState::CYCLES_TO_CLOCK as clocks::ClockType;
+            match self.cycles.get(i) {
+                Some(expected) if expected != cycle => errors.push(format!("cycle {}: {} (expected {})", i + 1, cycle, expected)),
+                Some(_) if *ticks != expected_ticks => errors.push(format!("cycle {}: {} at clock {} (expected {})", i + 1, cycle, ticks, expected_ticks)),
+                Some(_) => {}
+                None => errors.push(format!("cycle {}: unexpected {}", i + 1, cycle)),
+            }
+        }
+        for (i, expected) in self.cycles.iter().enumerate().skip(memory.cycles.len()) {
+            errors.push(format!("cycle {}: missing {}", i + 1, expected));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("'{}' {}", self.name, errors.join(", ")))
+        }
+    }
+}
+
+pub fn single_step_dir() -> path::PathBuf {
+    path::PathBuf::from(std::env::var(SINGLE_STEP_DIR_ENV).unwrap_or(DEFAULT_SINGLE_STEP_DIR.to_string()))
+}
+
+/// Run the vectors for a single op-code, returning (number run, failure descriptions).
+/// Returns 'None' if there's no vector file for the op-code.
+pub fn run_op_code(dir: &path::Path, op_code: u8) -> Option<(usize, Vec<String>)> {
+    let filename = dir.join(format!("{:02x}.json", op_code));
+    let text = fs::read_to_string(filename).ok()?;
+    let tests = SingleStepTest::load(&text).unwrap_or_else(|e| panic!("Failed to parse vectors for op-code 0x{:02X}: {}", op_code, e));
+    let failures = tests.iter().filter_map(|test| test.run().err()).collect();
+    Some((tests.len(), failures))
+}
@@ -0,0 +1,130 @@
+//! CPU trace log.
+//!
+//! Writes a line per instruction (before it's executed), in a similar layout to the common 'nestest' style logs, so
+//! traces can be diffed against other emulators:
+//!
+//! `F000  A9 00     LDA #$00                        A:00 X:00 Y:00 P:24 SP:FD SL:  0 CLK: 68 CYC:7`
+//!
+//! Undocumented op-codes are prefixed with '*', operand addresses are shown as symbols (if loaded).  'SL' is the scanline (since vsync) and 'CLK' the colour clock within
+//! the scanline, 'CYC' is the total CPU cycle count.
+//!
+//! Optional start/stop trigger addresses limit the trace, it starts when the PC reaches 'start' and stops after the
+//! instruction at 'stop' (re-starting if 'start' is reached again).
+
+use super::super::clocks;
+use super::super::memory::memory;
+use super::super::symbols;
+use super::disasm;
+use super::pc_state;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+pub struct Trace {
+    output: Box<dyn Write>,
+    start: Option<u16>,
+    stop: Option<u16>,
+    active: bool,
+}
+
+impl Trace {
+    pub fn new(output: Box<dyn Write>, start: Option<u16>, stop: Option<u16>) -> Self {
+        Self { output, start, stop, active: start.is_none() }
+    }
+
+    /// Trace to the file 'file_name' (created/truncated).
+    pub fn create(file_name: &str, start: Option<u16>, stop: Option<u16>) -> io::Result<Self> {
+        let file = fs::File::create(file_name)?;
+        Ok(Self::new(Box::new(io::BufWriter::new(file)), start, stop))
+    }
+
+    /// Update the start/stop triggers for the instruction at 'pc', returns true if it should be traced.
+    pub fn update_trigger(&mut self, pc: u16) -> bool {
+        if self.start == Some(pc) {
+            self.active = true;
+        }
+
+        let traced = self.active;
+        if self.stop == Some(pc) {
+            self.active = false;
+        }
+        traced
+    }
+
+    pub fn line(disassembly: &disasm::Disassembly, symbols: &symbols::Symbols, pc_state: &pc_state::PcState, cycles: clocks::ClockType, scanline: clocks::ClockType, colour_clock: clocks::ClockType) -> String {
+        format!(
+            "{:04X}  {:<8} {}{:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} SL:{:>3} CLK:{:>3} CYC:{}",
+            disassembly.address,
+            disassembly.bytes_text(),
+            if disassembly.undocumented { '*' } else { ' ' },
+            disassembly.text_with_symbols(symbols),
+            pc_state.get_a(),
+            pc_state.get_x(),
+            pc_state.get_y(),
+            pc_state.get_p(),
+            pc_state.get_s(),
+            scanline,
+            colour_clock,
+            cycles
+        )
+    }
+
+    /// Trace the instruction about to be executed (if triggered).
+    pub fn instruction(&mut self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &memory::Memory, symbols: &symbols::Symbols) {
+        if !self.update_trigger(pc_state.get_pc()) {
+            return;
+        }
+
+        let disassembly = disasm::disassemble(pc_state.get_pc(), &|address| memory.peek(clock, address));
+        let (scanline, colour_clock) = memory.stella.beam_position(clock);
+        let line = Trace::line(&disassembly, symbols, pc_state, clock.ticks / pc_state::PcState::CYCLES_TO_CLOCK as clocks::ClockType, scanline, colour_clock);
+
+        if let Err(e) = writeln!(self.output, "{}", line) {
+            // Don't keep failing on every instruction.
+            println!("Error writing trace, tracing stopped: {}", e);
+            self.start = None;
+            self.active = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::cpu::disasm;
+    use crate::atari2600::cpu::pc_state;
+    use crate::atari2600::cpu::trace;
+    use crate::atari2600::symbols;
+    use std::io;
+
+    #[test]
+    fn test_trace_line() {
+        let mut pc_state = pc_state::PcState::new();
+        pc_state.set_a(0x12);
+        pc_state.set_s(0xFD);
+        pc_state.set_p(0x24);
+
+        let code = [0xA9, 0x00, 0x04, 0xA9];
+        let read = |address: u16| code[(address - 0xF000) as usize];
+        assert_eq!(
+            trace::Trace::line(&disasm::disassemble(0xF000, &read), &symbols::Symbols::new(), &pc_state, 7, 0, 68),
+            "F000  A9 00     LDA #$00                        A:12 X:00 Y:00 P:24 SP:FD SL:  0 CLK: 68 CYC:7"
+        );
+        let mut symbols = symbols::Symbols::new();
+        symbols.add("Timer", 0xA9);
+        assert_eq!(
+            trace::Trace::line(&disasm::disassemble(0xF002, &read), &symbols, &pc_state, 1234, 37, 3),
+            "F002  04 A9    *NOP Timer                       A:12 X:00 Y:00 P:24 SP:FD SL: 37 CLK:  3 CYC:1234"
+        );
+    }
+
+    #[test]
+    fn test_trace_triggers() {
+        let mut trace = trace::Trace::new(Box::new(io::sink()), Some(0xF010), Some(0xF020));
+        let traced: Vec<bool> = [0xF000, 0xF010, 0xF015, 0xF020, 0xF025, 0xF010].iter().map(|pc| trace.update_trigger(*pc)).collect();
+        assert_eq!(traced, vec![false, true, true, true, false, true]);
+
+        let mut untriggered = trace::Trace::new(Box::new(io::sink()), None, None);
+        assert!(untriggered.update_trigger(0xF000));
+    }
+}
@@ -0,0 +1,378 @@
+//! Interactive (text console) debugger.
+//!
+//! Sits on top of 'Core::step', 'before_step' is called before each instruction and stops at the prompt when a
+//! breakpoint/watchpoint is hit or a step has completed.  Values/addresses are entered in hex, or as symbols (if loaded),
+//! counts/lengths in decimal.
+//!
+//! Watchpoints match any CPU access (including dummy reads/writes) to the address (or its mirrors) and stop after the
+//! instruction that made the access.
+
+use super::cpu;
+use super::memory;
+use super::symbols;
+
+use std::io;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    S,
+    P,
+    Pc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Continue,
+    Step(u32),
+    StepScanline,
+    StepFrame,
+    Break(u16),
+    Delete(u16),
+    Watch(u16, bool, bool),
+    Unwatch(u16),
+    List,
+    Registers,
+    Memory(u16, u16),
+    Disassemble(Option<u16>, u16),
+    SetRegister(Register, u16),
+    SetMemory(u16, u8),
+    Help,
+    Quit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum RunMode {
+    Paused,
+    Running,
+    Step(u32),
+    Scanline(u64),
+    Frame(u64),
+}
+
+pub struct Debugger {
+    breakpoints: Vec<u16>,
+    mode: RunMode,
+    last_command: Option<Command>,
+    // Don't re-break on a breakpoint at the instruction being resumed from.
+    resumed: bool,
+}
+
+impl Debugger {
+    const HELP: &'static str = "\
+  c, continue            run until a breakpoint/watchpoint
+  s, step [n]            step n instructions (default 1)
+  line                   step to the next scanline
+  frame                  step to the next frame (vsync)
+  b, break <addr>        set a PC breakpoint
+  d, delete <addr>       delete a PC breakpoint
+  w, watch <addr> [r|w|rw]  watch reads/writes (default rw) of TIA/RIOT/RAM/cartridge address
+  unwatch <addr>         delete a watchpoint
+  l, list                list breakpoints and watchpoints
+  r, regs                show registers, beam position and cycles
+  m, mem <addr> [len]    show memory (without side effects)
+  dis [addr] [count]     disassemble (default from the PC)
+  set <a|x|y|s|p|pc> <value>  set a register
+  poke <addr> <value>    write memory (as a CPU write)
+  h, help                show this help
+  q, quit                quit the emulator
+  (an empty line repeats the last command)";
+
+    /// 'paused' starts at the prompt (ie at reset).
+    pub fn new(paused: bool) -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            mode: if paused { RunMode::Paused } else { RunMode::Running },
+            last_command: None,
+            resumed: false,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    pub fn parse_value(text: &str) -> Result<u16, String> {
+        let digits = text.trim_start_matches("0x").trim_start_matches("0X").trim_start_matches('$');
+        u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid hex value '{}'", text))
+    }
+
+    pub fn parse_count(text: &str) -> Result<u16, String> {
+        text.parse::<u16>().map_err(|_| format!("Invalid count '{}'", text))
+    }
+
+    /// A symbol's value, otherwise hex.
+    pub fn parse_address(text: &str, symbols: &symbols::Symbols) -> Result<u16, String> {
+        symbols.address(text).map_or_else(|| Debugger::parse_value(text), Ok)
+    }
+
+    pub fn parse_command(line: &str, symbols: &symbols::Symbols) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let parse = |word: &&str| Debugger::parse_address(word, symbols);
+        let arg = |index: usize| -> Result<u16, String> { words.get(index).map_or(Err(format!("'{}' requires a value", words[0])), parse) };
+        let optional_arg = |index: usize, default: u16| -> Result<u16, String> { words.get(index).map_or(Ok(default), parse) };
+        let optional_count = |index: usize, default: u16| -> Result<u16, String> { words.get(index).map_or(Ok(default), |word| Debugger::parse_count(word)) };
+        let byte = |value: u16| -> Result<u8, String> { u8::try_from(value).map_err(|_| format!("Value 0x{:X} is larger than a byte", value)) };
+
+        match words.first().copied().unwrap_or("") {
+            "c" | "continue" => Ok(Command::Continue),
+            "s" | "step" => Ok(Command::Step(optional_count(1, 1)? as u32)),
+            "line" => Ok(Command::StepScanline),
+            "frame" => Ok(Command::StepFrame),
+            "b" | "break" => Ok(Command::Break(arg(1)?)),
+            "d" | "delete" => Ok(Command::Delete(arg(1)?)),
+            "w" | "watch" => match words.get(2).copied().unwrap_or("rw") {
+                "r" => Ok(Command::Watch(arg(1)?, true, false)),
+                "w" => Ok(Command::Watch(arg(1)?, false, true)),
+                "rw" => Ok(Command::Watch(arg(1)?, true, true)),
+                kind => Err(format!("Invalid watch type '{}' (r, w or rw)", kind)),
+            },
+            "unwatch" => Ok(Command::Unwatch(arg(1)?)),
+            "l" | "list" => Ok(Command::List),
+            "r" | "regs" => Ok(Command::Registers),
+            "m" | "mem" => Ok(Command::Memory(arg(1)?, optional_count(2, 0x10)?)),
+            "dis" => Ok(Command::Disassemble(words.get(1).map(parse).transpose()?, optional_count(2, 10)?)),
+            "set" => {
+                let register = match words.get(1).copied().unwrap_or("") {
+                    "a" => Register::A,
+                    "x" => Register::X,
+                    "y" => Register::Y,
+                    "s" => Register::S,
+                    "p" => Register::P,
+                    "pc" => Register::Pc,
+                    name => return Err(format!("Invalid register '{}' (a, x, y, s, p or pc)", name)),
+                };
+                let value = if Register::Pc == register { arg(2)? } else { byte(arg(2)?)? as u16 };
+                Ok(Command::SetRegister(register, value))
+            }
+            "poke" => Ok(Command::SetMemory(arg(1)?, byte(arg(2)?)?)),
+            "h" | "help" => Ok(Command::Help),
+            "q" | "quit" => Ok(Command::Quit),
+            word => Err(format!("Unknown command '{}' (try 'help')", word)),
+        }
+    }
+
+    /// Call before each instruction, returns false if the user has quit.
+    pub fn before_step(&mut self, core: &mut cpu::core::Core, input: &mut dyn io::BufRead, output: &mut dyn io::Write) -> bool {
+        let hits = core.memory.take_watch_hits();
+        let pc = core.pc_state().get_pc();
+        let resumed = std::mem::replace(&mut self.resumed, false);
+
+        // The instruction being resumed from always executes.
+        let stop = !resumed
+            && match self.mode {
+                RunMode::Paused => true,
+                RunMode::Running => false,
+                RunMode::Step(0) => true,
+                RunMode::Step(remaining) => {
+                    self.mode = RunMode::Step(remaining - 1);
+                    false
+                }
+                RunMode::Scanline(scanline) => core.memory.stella.beam_position(&core.clock).0 != scanline,
+                RunMode::Frame(vsync_clock) => core.memory.stella.debug_clock() != vsync_clock,
+            };
+
+        for hit in &hits {
+            let _ = writeln!(output, "Watchpoint: {}", hit);
+        }
+        let breakpoint = !resumed && self.breakpoints.contains(&pc);
+        if breakpoint {
+            let _ = writeln!(output, "Breakpoint: 0x{:04X}{}", pc, core.symbols().name(pc).map_or(String::new(), |name| format!(" ({})", name)));
+        }
+
+        if !(stop || breakpoint || !hits.is_empty()) {
+            return true;
+        }
+
+        self.mode = RunMode::Paused;
+        let _ = writeln!(output, "{}", Debugger::current_line(core));
+        let running = self.prompt(core, input, output);
+        core.resync_realtime();
+        running
+    }
+
+    fn current_line(core: &mut cpu::core::Core) -> String {
+        let pc = core.pc_state().get_pc();
+        let disassembly = cpu::disasm::disassemble(pc, &|address| core.memory.peek(&core.clock, address));
+        let (scanline, colour_clock) = core.memory.stella.beam_position(&core.clock);
+        let line = cpu::trace::Trace::line(&disassembly, core.symbols(), core.pc_state(), core.clock.ticks / cpu::pc_state::PcState::CYCLES_TO_CLOCK as u64, scanline, colour_clock);
+        match core.symbols().name(pc) {
+            Some(label) => format!("{}:\n{}", label, line),
+            None => line,
+        }
+    }
+
+    fn prompt(&mut self, core: &mut cpu::core::Core, input: &mut dyn io::BufRead, output: &mut dyn io::Write) -> bool {
+        loop {
+            let _ = write!(output, "(debug) ");
+            let _ = output.flush();
+
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return false, // End of input.
+                Ok(_) => {}
+            }
+
+            let command = if line.trim().is_empty() {
+                match &self.last_command {
+                    Some(command) => Ok(command.clone()),
+                    None => continue,
+                }
+            } else {
+                Debugger::parse_command(&line, core.symbols())
+            };
+
+            match command {
+                Ok(command) => {
+                    self.last_command = Some(command.clone());
+                    if let Some(running) = self.execute(&command, core, output) {
+                        return running;
+                    }
+                }
+                Err(e) => {
+                    let _ = writeln!(output, "{}", e);
+                }
+            }
+        }
+    }
+
+    /// Execute the command, returns 'Some(running)' when the prompt should be left.
+    fn execute(&mut self, command: &Command, core: &mut cpu::core::Core, output: &mut dyn io::Write) -> Option<bool> {
+        match *command {
+            Command::Continue => return Some(self.resume(RunMode::Running)),
+            Command::Step(count) => return Some(self.resume(RunMode::Step(count.max(1) - 1))),
+            Command::StepScanline => {
+                let scanline = core.memory.stella.beam_position(&core.clock).0;
+                return Some(self.resume(RunMode::Scanline(scanline)));
+            }
+            Command::StepFrame => {
+                let vsync_clock = core.memory.stella.debug_clock();
+                return Some(self.resume(RunMode::Frame(vsync_clock)));
+            }
+            Command::Break(address) => self.add_breakpoint(address),
+            Command::Delete(address) => self.breakpoints.retain(|breakpoint| *breakpoint != address),
+            Command::Watch(address, read, write) => core.memory.set_watchpoint(address, read, write),
+            Command::Unwatch(address) => core.memory.set_watchpoint(address, false, false),
+            Command::List => {
+                for breakpoint in &self.breakpoints {
+                    let _ = writeln!(output, "break 0x{:04X}", breakpoint);
+                }
+                for watchpoint in core.memory.watchpoints() {
+                    let _ = writeln!(output, "watch 0x{:04X} {}{}", watchpoint.address, if watchpoint.read { "r" } else { "" }, if watchpoint.write { "w" } else { "" });
+                }
+            }
+            Command::Registers => {
+                let _ = writeln!(output, "{}", Debugger::current_line(core));
+            }
+            Command::Memory(address, length) => {
+                for row in (0..length).step_by(0x10) {
+                    let row_address = address.wrapping_add(row);
+                    let values: Vec<String> = (row..length.min(row + 0x10)).map(|offset| format!("{:02X}", core.memory.peek(&core.clock, address.wrapping_add(offset)))).collect();
+                    let _ = writeln!(output, "{:04X}: {}", row_address, values.join(" "));
+                }
+            }
+            Command::Disassemble(address, count) => {
+                let mut address = address.unwrap_or(core.pc_state().get_pc());
+                for _ in 0..count {
+                    let disassembly = cpu::disasm::disassemble(address, &|address| core.memory.peek(&core.clock, address));
+                    let _ = writeln!(output, "{}", disassembly.line_with_symbols(core.symbols()));
+                    address = address.wrapping_add(disassembly.length());
+                }
+            }
+            Command::SetRegister(register, value) => {
+                let pc_state = core.pc_state_mut();
+                match register {
+                    Register::A => pc_state.set_a(value as u8),
+                    Register::X => pc_state.set_x(value as u8),
+                    Register::Y => pc_state.set_y(value as u8),
+                    Register::S => pc_state.set_s(value as u8),
+                    Register::P => pc_state.set_p(value as u8),
+                    Register::Pc => pc_state.set_pc(value),
+                }
+            }
+            Command::SetMemory(address, value) if !core.memory.is_write_mapped(address) => {
+                let _ = writeln!(output, "Address 0x{:04X} can't be written", address);
+            }
+            Command::SetMemory(address, value) => {
+                memory::bus::Bus::write(&mut core.memory, &mut core.clock, address, value);
+                // Editing isn't an access to stop on.
+                core.memory.take_watch_hits();
+            }
+            Command::Help => {
+                let _ = writeln!(output, "{}", Debugger::HELP);
+            }
+            Command::Quit => return Some(false),
+        }
+        None
+    }
+
+    fn resume(&mut self, mode: RunMode) -> bool {
+        self.mode = mode;
+        self.resumed = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::debugger;
+    use crate::atari2600::symbols;
+    use crate::atari2600::testing;
+    use std::io;
+
+    #[test]
+    fn test_parse_command() {
+        use debugger::{Command, Debugger, Register};
+
+        let mut symbols = symbols::Symbols::new();
+        symbols.add("MainLoop", 0xF004);
+
+        assert_eq!(Debugger::parse_command("break F004", &symbols), Ok(Command::Break(0xF004)));
+        assert_eq!(Debugger::parse_command("s", &symbols), Ok(Command::Step(1)));
+        // Counts are decimal, addresses/values hex.
+        assert_eq!(Debugger::parse_command("step 10", &symbols), Ok(Command::Step(10)));
+        assert_eq!(Debugger::parse_command("mem 80 20", &symbols), Ok(Command::Memory(0x80, 20)));
+        assert_eq!(Debugger::parse_command("poke 80 FF", &symbols), Ok(Command::SetMemory(0x80, 0xFF)));
+        assert!(Debugger::parse_command("poke 80 1FF", &symbols).is_err());
+        assert!(Debugger::parse_command("set a 100", &symbols).is_err());
+        assert!(Debugger::parse_command("step F", &symbols).is_err());
+        assert_eq!(Debugger::parse_command("watch $80 w", &symbols), Ok(Command::Watch(0x80, false, true)));
+        assert_eq!(Debugger::parse_command("set pc 0xF000", &symbols), Ok(Command::SetRegister(Register::Pc, 0xF000)));
+        assert_eq!(Debugger::parse_command("dis", &symbols), Ok(Command::Disassemble(None, 10)));
+        assert!(Debugger::parse_command("break", &symbols).is_err());
+        assert!(Debugger::parse_command("watch 80 x", &symbols).is_err());
+        assert!(Debugger::parse_command("jump F000", &symbols).is_err());
+        assert_eq!(Debugger::parse_command("b MainLoop", &symbols), Ok(Command::Break(0xF004)));
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        // LDA #$00, STA $80, INC $80, JMP $F004 (padded to 4K, with the reset vector at 0xF000).
+        let mut core = testing::machine(&testing::rom(&[0xA9, 0x00, 0x85, 0x80, 0xE6, 0x80, 0x4C, 0x04, 0xF0], 0xEA)).into_core();
+        let mut debugger = debugger::Debugger::new(true);
+        let mut input = io::Cursor::new("break F006\ncontinue\nset a 42\nregs\ndelete F006\nwatch 80 w\nc\nmem 80 2\n\npoke 200 00\nq\n");
+        let mut output = Vec::new();
+
+        let mut steps = 0;
+        while debugger.before_step(&mut core, &mut input, &mut output) {
+            core.step(false, false);
+            steps += 1;
+        }
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("Breakpoint: 0xF006"), "{}", output);
+        assert!(output.contains("F006  4C 04 F0  JMP $F004                       A:42 X:00 Y:00"), "{}", output);
+        // Breaks after 'INC $80' (dummy write of the old value, then the incremented value).
+        assert!(output.contains("Watchpoint: write 0x0080 0x01\nWatchpoint: write 0x0080 0x02"), "{}", output);
+        assert!(output.contains("0080: 02 00"), "{}", output);
+        // Repeated 'mem 80 2' (empty line).
+        assert_eq!(output.matches("0080: 02 00").count(), 2, "{}", output);
+        // Unmapped addresses aren't written.
+        assert!(output.contains("Address 0x0200 can't be written"), "{}", output);
+        assert_eq!(steps, 5);
+    }
+}
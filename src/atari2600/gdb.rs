@@ -0,0 +1,459 @@
+//! GDB remote serial protocol stub (loopback TCP only).
+//!
+//! Allows an external (gdb-style) debugger to attach to the running core.  Supported packets:
+//!  - '?', 'g'/'G', 'p'/'P' (registers), 'm'/'M' (memory), 'c'/'s' (continue/step), 'Z'/'z' (breakpoints 0/1 and
+//!    watchpoints 2/3/4), 'qSupported', 'qAttached', 'D' (detach) and 'k' (kill), plus the '0x03' interrupt.
+//!
+//! There's no standard 6502 target description, so the register order is:
+//!  - 0: A, 1: X, 2: Y, 3: S, 4: P (8 bits each), 5: PC (16 bits, little endian).
+
+use super::cpu;
+use super::memory;
+
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::net;
+
+#[derive(Debug, PartialEq)]
+pub enum Response {
+    Reply(String),
+    // Continue running (true if single stepping).
+    Resume(bool),
+    Detach,
+    Kill,
+}
+
+enum Packet {
+    Data(String),
+    Interrupt,
+}
+
+pub struct GdbStub {
+    reader: io::BufReader<net::TcpStream>,
+    writer: net::TcpStream,
+    breakpoints: Vec<u16>,
+    // Watched addresses, to clear on detach.
+    watchpoints: Vec<u16>,
+    running: bool,
+    stepping: bool,
+    detached: bool,
+    poll_counter: u32,
+}
+
+impl GdbStub {
+    const INTERRUPT: u8 = 0x03;
+    const SIGTRAP: &'static str = "S05";
+    const POLL_STEPS: u32 = 1000; // Number of instructions between checks for an interrupt (whilst running).
+    const REGISTER_COUNT: usize = 6;
+
+    /// Listen (on loopback only) for a debugger to attach, use port 0 for any free port.
+    pub fn listen(port: u16) -> io::Result<net::TcpListener> {
+        net::TcpListener::bind((net::Ipv4Addr::LOCALHOST, port))
+    }
+
+    /// Wait for a debugger to attach, the core is stopped until it resumes.
+    pub fn accept(listener: &net::TcpListener) -> io::Result<Self> {
+        let (stream, _address) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: io::BufReader::new(stream.try_clone()?),
+            writer: stream,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            running: false,
+            stepping: false,
+            detached: false,
+            poll_counter: 0,
+        })
+    }
+
+    fn checksum(data: &str) -> u8 {
+        data.bytes().fold(0, |sum, b| sum.wrapping_add(b))
+    }
+
+    fn hex_bytes(data: &str) -> Option<Vec<u8>> {
+        (0..data.len()).step_by(2).map(|i| data.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok())).collect()
+    }
+
+    fn hex_value(data: &str) -> Option<u16> {
+        u16::from_str_radix(data, 16).ok()
+    }
+
+    fn registers(pc_state: &cpu::pc_state::PcState) -> [u8; GdbStub::REGISTER_COUNT + 1] {
+        [pc_state.get_a(), pc_state.get_x(), pc_state.get_y(), pc_state.get_s(), pc_state.get_p(), pc_state.get_pcl(), pc_state.get_pch()]
+    }
+
+    fn register_text(register: usize, pc_state: &cpu::pc_state::PcState) -> Option<String> {
+        let registers = GdbStub::registers(pc_state);
+        match register {
+            0..=4 => Some(format!("{:02x}", registers[register])),
+            5 => Some(format!("{:02x}{:02x}", registers[5], registers[6])),
+            _ => None,
+        }
+    }
+
+    fn set_register(register: usize, value: &[u8], pc_state: &mut cpu::pc_state::PcState) -> bool {
+        match (register, value) {
+            (0, [a]) => pc_state.set_a(*a),
+            (1, [x]) => pc_state.set_x(*x),
+            (2, [y]) => pc_state.set_y(*y),
+            (3, [s]) => pc_state.set_s(*s),
+            (4, [p]) => pc_state.set_p(*p),
+            (5, [pcl, pch]) => pc_state.set_pc(*pcl as u16 | ((*pch as u16) << 8)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handle a (checked) packet's data.
+    pub fn handle(&mut self, packet: &str, core: &mut cpu::core::Core) -> Response {
+        const OK: &str = "OK";
+        const ERROR: &str = "E01";
+
+        let reply = |text: &str| Response::Reply(text.to_string());
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, |c| c.len_utf8()));
+
+        match command {
+            "?" => reply(GdbStub::SIGTRAP),
+            "g" => reply(&(0..GdbStub::REGISTER_COUNT).filter_map(|register| GdbStub::register_text(register, core.pc_state())).collect::<String>()),
+            "G" => match GdbStub::hex_bytes(arguments) {
+                Some(values) if values.len() == GdbStub::REGISTER_COUNT + 1 => {
+                    for (register, value) in [&values[0..1], &values[1..2], &values[2..3], &values[3..4], &values[4..5], &values[5..7]].iter().enumerate() {
+                        GdbStub::set_register(register, value, core.pc_state_mut());
+                    }
+                    reply(OK)
+                }
+                _ => reply(ERROR),
+            },
+            "p" => match usize::from_str_radix(arguments, 16).ok().and_then(|register| GdbStub::register_text(register, core.pc_state())) {
+                Some(text) => reply(&text),
+                None => reply(ERROR),
+            },
+            "P" => {
+                let set = arguments.split_once('=').and_then(|(register, value)| Some((usize::from_str_radix(register, 16).ok()?, GdbStub::hex_bytes(value)?)));
+                match set {
+                    Some((register, value)) if GdbStub::set_register(register, &value, core.pc_state_mut()) => reply(OK),
+                    _ => reply(ERROR),
+                }
+            }
+            "m" => match arguments.split_once(',').and_then(|(address, length)| Some((GdbStub::hex_value(address)?, GdbStub::hex_value(length)?))) {
+                Some((address, length)) => reply(&(0..length).map(|offset| format!("{:02x}", core.memory.peek(&core.clock, address.wrapping_add(offset)))).collect::<String>()),
+                None => reply(ERROR),
+            },
+            "M" => {
+                let write = arguments.split_once(':').and_then(|(location, data)| {
+                    let (address, length) = location.split_once(',')?;
+                    Some((GdbStub::hex_value(address)?, GdbStub::hex_value(length)?, GdbStub::hex_bytes(data)?))
+                });
+                match write {
+                    // Only if every address can be written (nothing is written otherwise).
+                    Some((address, length, data)) if data.len() == length as usize && (0..length).all(|offset| core.memory.is_write_mapped(address.wrapping_add(offset))) => {
+                        for (offset, value) in data.iter().enumerate() {
+                            memory::bus::Bus::write(&mut core.memory, &mut core.clock, address.wrapping_add(offset as u16), *value);
+                        }
+                        // Editing isn't an access to stop on.
+                        core.memory.take_watch_hits();
+                        reply(OK)
+                    }
+                    _ => reply(ERROR),
+                }
+            }
+            "c" | "s" => {
+                if let Some(address) = GdbStub::hex_value(arguments) {
+                    core.pc_state_mut().set_pc(address);
+                }
+                Response::Resume(command == "s")
+            }
+            "Z" | "z" => {
+                let point = arguments.split(',').collect::<Vec<&str>>();
+                match (point.first().copied(), point.get(1).and_then(|address| GdbStub::hex_value(address))) {
+                    (Some("0" | "1"), Some(address)) => {
+                        self.breakpoints.retain(|breakpoint| *breakpoint != address);
+                        if command == "Z" {
+                            self.breakpoints.push(address);
+                        }
+                        reply(OK)
+                    }
+                    (Some(kind @ ("2" | "3" | "4")), Some(address)) => {
+                        // Write, read and access watchpoints.
+                        let insert = command == "Z";
+                        core.memory.set_watchpoint(address, insert && kind != "2", insert && kind != "3");
+                        self.watchpoints.retain(|watchpoint| *watchpoint != address);
+                        if insert {
+                            self.watchpoints.push(address);
+                        }
+                        reply(OK)
+                    }
+                    _ => reply(""),
+                }
+            }
+            "q" if arguments.starts_with("Supported") => reply("PacketSize=1000"),
+            "q" if arguments == "Attached" => reply("1"),
+            "D" => Response::Detach,
+            "k" => Response::Kill,
+            _ => reply(""), // Unsupported.
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        write!(self.writer, "${}#{:02x}", data, GdbStub::checksum(data))?;
+        self.writer.flush()
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_packet(&mut self) -> io::Result<Packet> {
+        loop {
+            match self.read_byte()? {
+                GdbStub::INTERRUPT => return Ok(Packet::Interrupt),
+                b'$' => {
+                    let mut data = Vec::new();
+                    self.reader.read_until(b'#', &mut data)?;
+                    data.pop();
+                    let checksum = [self.read_byte()?, self.read_byte()?];
+
+                    let data = String::from_utf8_lossy(&data).to_string();
+                    let valid = std::str::from_utf8(&checksum).ok().and_then(|checksum| u8::from_str_radix(checksum, 16).ok()) == Some(GdbStub::checksum(&data));
+                    self.writer.write_all(if valid { b"+" } else { b"-" })?;
+                    if valid {
+                        return Ok(Packet::Data(data));
+                    }
+                }
+                _ => {} // Acknowledgements ('+'/'-') or noise.
+            }
+        }
+    }
+
+    /// Check (without blocking) for an interrupt from the debugger.
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let available = self.reader.fill_buf().map(|buffer| !buffer.is_empty());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match available {
+                Ok(true) => {}
+                Ok(false) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(matches!(self.read_packet()?, Packet::Interrupt))
+    }
+
+    fn stop_reply(&mut self, core: &mut cpu::core::Core) -> io::Result<Option<String>> {
+        if let Some(hit) = core.memory.take_watch_hits().first() {
+            let watched = core.memory.watchpoints().iter().find(|watchpoint| watchpoint.address == hit.address).copied();
+            let kind = match watched {
+                Some(memory::memory::Watchpoint { read: true, write: true, .. }) => "awatch",
+                Some(memory::memory::Watchpoint { read: true, .. }) => "rwatch",
+                _ => "watch",
+            };
+            return Ok(Some(format!("T05{}:{:04x};", kind, hit.address)));
+        }
+        if self.stepping || self.breakpoints.contains(&core.pc_state().get_pc()) {
+            return Ok(Some(GdbStub::SIGTRAP.to_string()));
+        }
+
+        self.poll_counter += 1;
+        if self.poll_counter >= GdbStub::POLL_STEPS {
+            self.poll_counter = 0;
+            if self.poll_interrupt()? {
+                return Ok(Some(GdbStub::SIGTRAP.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn serve(&mut self, core: &mut cpu::core::Core) -> io::Result<bool> {
+        if self.running {
+            match self.stop_reply(core)? {
+                Some(reply) => {
+                    self.running = false;
+                    self.send_packet(&reply)?;
+                }
+                None => return Ok(true),
+            }
+        }
+
+        loop {
+            let data = match self.read_packet()? {
+                Packet::Data(data) => data,
+                Packet::Interrupt => continue, // Already stopped.
+            };
+            match self.handle(&data, core) {
+                Response::Reply(reply) => self.send_packet(&reply)?,
+                Response::Resume(stepping) => {
+                    self.running = true;
+                    self.stepping = stepping;
+                    return Ok(true);
+                }
+                Response::Detach => {
+                    self.send_packet("OK")?;
+                    self.detach(core);
+                    return Ok(true);
+                }
+                Response::Kill => return Ok(false),
+            }
+        }
+    }
+
+    // Leaves nothing behind, as no one else drains the watch hits.
+    fn detach(&mut self, core: &mut cpu::core::Core) {
+        for address in self.watchpoints.drain(..) {
+            core.memory.set_watchpoint(address, false, false);
+        }
+        core.memory.take_watch_hits();
+        self.breakpoints.clear();
+        self.running = false;
+        self.detached = true;
+    }
+
+    /// Call before each instruction, returns false if the debugger has killed the emulator.
+    pub fn before_step(&mut self, core: &mut cpu::core::Core) -> bool {
+        if self.detached {
+            return true;
+        }
+
+        let was_running = self.running;
+        let result = self.serve(core);
+        if !(was_running && self.running) {
+            // Has been stopped.
+            core.resync_realtime();
+        }
+
+        match result {
+            Ok(running) => running,
+            Err(e) => {
+                // Keep the emulator running if the debugger goes away.
+                println!("GDB connection closed: {}", e);
+                self.detach(core);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::gdb;
+    use crate::atari2600::testing;
+    use std::io::{Read, Write};
+    use std::net;
+    use std::thread;
+
+    fn packet(data: &str) -> String {
+        format!("${}#{:02x}", data, data.bytes().fold(0_u8, |sum, b| sum.wrapping_add(b)))
+    }
+
+    // Minimal client: send each packet and collect the (acknowledged) replies.
+    fn client(port: u16, packets: Vec<&'static str>) -> Vec<String> {
+        let mut stream = net::TcpStream::connect((net::Ipv4Addr::LOCALHOST, port)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut replies = Vec::new();
+        for data in packets {
+            stream.write_all(packet(data).as_bytes()).unwrap();
+            if data == "k" {
+                break;
+            }
+
+            let mut received = Vec::new();
+            let mut byte = [0];
+            while !(received.len() > 3 && received[received.len() - 3] == b'#') {
+                stream.read_exact(&mut byte).unwrap();
+                received.push(byte[0]);
+            }
+            let received = String::from_utf8(received).unwrap();
+            let reply = received.trim_start_matches('+');
+            assert_eq!(reply, packet(&reply[1..reply.len() - 3]), "Checksum");
+            replies.push(reply[1..reply.len() - 3].to_string());
+            stream.write_all(b"+").unwrap();
+        }
+        replies
+    }
+
+    #[test]
+    fn test_scripted_client() {
+        // LDA #$00, STA $80, INC $80, JMP $F004 (padded to 4K, with the reset vector at 0xF000).
+        let mut core = testing::machine(&testing::rom(&[0xA9, 0x00, 0x85, 0x80, 0xE6, 0x80, 0x4C, 0x04, 0xF0], 0xEA)).into_core();
+
+        let listener = gdb::GdbStub::listen(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let script = vec![
+            "qSupported:multiprocess+",
+            "?",
+            "g",
+            "Z0,f006,1",
+            "c",
+            "p5",
+            "P0=42",
+            "g",
+            "m80,2",
+            "Z2,80,1",
+            "c",
+            "m80,1",
+            "z2,80,1",
+            "M80,1:10",
+            "M200,1:00",
+            "s",
+            "m80,1",
+            "k",
+        ];
+        let client = thread::spawn(move || client(port, script));
+
+        let mut stub = gdb::GdbStub::accept(&listener).unwrap();
+        let mut steps = 0;
+        while stub.before_step(&mut core) {
+            core.step(false, false);
+            steps += 1;
+        }
+
+        assert_eq!(
+            client.join().unwrap(),
+            vec![
+                "PacketSize=1000",
+                "S05",
+                "000000000000f0", // A X Y S P PC (at reset).
+                "OK",
+                "S05",  // Breakpoint at 0xF006 (after LDA, STA, INC).
+                "06f0", // PC
+                "OK",
+                "420000000006f0",
+                "0100",
+                "OK",
+                "T05watch:0080;", // After the next 'INC $80'.
+                "02",
+                "OK",
+                "OK",
+                "E01", // 0x0200 isn't mapped.
+                "S05",
+                "10", // 'JMP' doesn't change 0x80.
+            ]
+        );
+        assert_eq!(steps, 6);
+    }
+
+    #[test]
+    fn test_detach_clears_watchpoints() {
+        let mut core = testing::machine(&testing::rom(&[0xA9, 0x00, 0x85, 0x80, 0xE6, 0x80, 0x4C, 0x04, 0xF0], 0xEA)).into_core();
+
+        let listener = gdb::GdbStub::listen(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = thread::spawn(move || client(port, vec!["Z0,f006,1", "Z4,80,1", "Z2,81,1", "z2,81,1", "D"]));
+
+        let mut stub = gdb::GdbStub::accept(&listener).unwrap();
+        for _ in 0..10 {
+            assert!(stub.before_step(&mut core));
+            core.step(false, false);
+        }
+
+        assert_eq!(client.join().unwrap(), vec!["OK", "OK", "OK", "OK", "OK"]);
+        // Neither the watchpoint nor the breakpoint (at 0xF006) survive the detach.
+        assert!(stub.breakpoints.is_empty());
+        assert!(core.memory.watchpoints().is_empty());
+        assert!(core.memory.take_watch_hits().is_empty());
+    }
+}
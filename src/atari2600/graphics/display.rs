@@ -1,6 +1,10 @@
+#[cfg(feature = "sdl")]
 use sdl2::event;
+#[cfg(feature = "sdl")]
 use sdl2::pixels;
+#[cfg(feature = "sdl")]
 use sdl2::render;
+#[cfg(feature = "sdl")]
 use sdl2::video;
 
 use std::fmt;
@@ -74,8 +78,10 @@ impl fmt::Display for Colour {
     }
 }
 
+#[cfg(feature = "sdl")]
 pub struct SDLUtility {}
 
+#[cfg(feature = "sdl")]
 impl SDLUtility {
     pub const PIXEL_FORMAT: pixels::PixelFormatEnum = pixels::PixelFormatEnum::RGB888;
 
@@ -83,6 +89,14 @@ impl SDLUtility {
         SDLUtility::PIXEL_FORMAT.byte_size_per_pixel() as u16
     }
 
+    // Copy a frame, 3 bytes (RGB) per pixel, into a 'PIXEL_FORMAT' (texture) buffer.
+    pub fn copy_rgb24(rgb: &[u8], buffer: &mut [u8]) {
+        let bytes_per_pixel = SDLUtility::bytes_per_pixel() as usize;
+        for (pixel, dst) in rgb.chunks(3).zip(buffer.chunks_mut(bytes_per_pixel)) {
+            Colour::new(pixel[0], pixel[1], pixel[2]).convert_rgb888(dst);
+        }
+    }
+
     pub fn create_canvas(sdl_context: &mut sdl2::Sdl, name: &str, frame_width: u16, frame_height: u16, fullscreen: bool) -> Option<render::Canvas<video::Window>> {
         let video_subsystem = sdl_context.video().unwrap();
         let mut renderer = video_subsystem.window(name, frame_width as u32, frame_height as u32);
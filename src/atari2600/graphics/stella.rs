@@ -3,6 +3,7 @@ use super::super::clocks;
 use super::super::cpu::pc_state;
 use super::super::inputs;
 use super::super::io;
+use super::super::state;
 use super::display;
 use std;
 
@@ -21,13 +22,14 @@ impl DebugControl {
     pub const SHOW_M1: bool = true;
 
     pub const DEBUG_COLOURS: bool = false;
-    pub const DEBUG_BACKGROUND_COLOUR: display::Colour = display::Colour::new(255, 255, 255);
-    pub const DEBUG_BL_COLOUR: display::Colour = display::Colour::new(255, 0, 0);
-    pub const DEBUG_PF_COLOUR: display::Colour = display::Colour::new(0, 255, 0);
-    pub const DEBUG_M0_COLOUR: display::Colour = display::Colour::new(0, 0, 255);
-    pub const DEBUG_M1_COLOUR: display::Colour = display::Colour::new(0, 255, 255);
-    pub const DEBUG_P0_COLOUR: display::Colour = display::Colour::new(255, 0, 255);
-    pub const DEBUG_P1_COLOUR: display::Colour = display::Colour::new(255, 255, 0);
+    // Palette entries (so the frame is still palette indices): white, red, green, blue, cyan, magenta, yellow.
+    pub const DEBUG_BACKGROUND_COLOUR: u8 = Colours::palette_index(0x0E);
+    pub const DEBUG_BL_COLOUR: u8 = Colours::palette_index(0x46);
+    pub const DEBUG_PF_COLOUR: u8 = Colours::palette_index(0xC8);
+    pub const DEBUG_M0_COLOUR: u8 = Colours::palette_index(0x86);
+    pub const DEBUG_M1_COLOUR: u8 = Colours::palette_index(0xAC);
+    pub const DEBUG_P0_COLOUR: u8 = Colours::palette_index(0x58);
+    pub const DEBUG_P1_COLOUR: u8 = Colours::palette_index(0x1E);
 }
 
 pub struct Constants {}
@@ -42,7 +44,7 @@ impl Constants {
     pub const VSYNC_ON: u8 = 0x2;
     pub const VSYNC_OFF: u8 = 0x0;
 
-    pub const DEFAULT_COLOUR: display::Colour = display::Colour::new(0, 0, 0);
+    pub const DEFAULT_COLOUR: u8 = 0;
 }
 
 pub struct PlayfieldState {
@@ -138,6 +140,19 @@ impl PlayfieldState {
         &self.pf_lookup
     }
 
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.pf0, self.pf1, self.pf2, self.ctrlpf]);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut registers = [0; 4];
+        state.read_bytes_into(&mut registers)?;
+        [self.pf0, self.pf1, self.pf2, self.ctrlpf] = registers;
+        // The scan is derived from the registers.
+        self.update();
+        Ok(())
+    }
+
     pub fn update_pf0(&mut self, data: u8) {
         self.pf0 = data;
         self.update();
@@ -248,6 +263,24 @@ impl BallState {
     fn get_ball_scan(&self) -> &Vec<bool> {
         &self.scan_line
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.enabl, self.enabl_old, self.vdelbl, self.resbl, self.ctrlpf]);
+        state.write_u16(self.x_min);
+        state.write_u16(self.x_max);
+        state.write_bool(self.enabled);
+        state.write_bools(&self.scan_line);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut registers = [0; 5];
+        state.read_bytes_into(&mut registers)?;
+        [self.enabl, self.enabl_old, self.vdelbl, self.resbl, self.ctrlpf] = registers;
+        self.x_min = state.read_u16()?;
+        self.x_max = state.read_u16()?;
+        self.enabled = state.read_bool()?;
+        state.read_bools_into(&mut self.scan_line)
+    }
 }
 
 pub struct MissileState {
@@ -328,6 +361,18 @@ impl MissileState {
     fn get_missile_scan(&self) -> &Vec<bool> {
         &self.scan_line
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.nusiz, self.enam, self.resm, self.number, self.gap]);
+        state.write_bools(&self.scan_line);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut registers = [0; 5];
+        state.read_bytes_into(&mut registers)?;
+        [self.nusiz, self.enam, self.resm, self.number, self.gap] = registers;
+        state.read_bools_into(&mut self.scan_line)
+    }
 }
 
 pub struct PlayerState {
@@ -489,13 +534,28 @@ impl PlayerState {
     fn get_player_scan(&self) -> &Vec<bool> {
         &self.scan_line
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.nusiz, self.p, self.p_old, self.refp, self.resp, self.vdelp, self.reflect, self.grp, self.number, self.size, self.gap]);
+        state.write_u16(self.pos_start);
+        state.write_bools(&self.scan_line);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut registers = [0; 11];
+        state.read_bytes_into(&mut registers)?;
+        [self.nusiz, self.p, self.p_old, self.refp, self.resp, self.vdelp, self.reflect, self.grp, self.number, self.size, self.gap] = registers;
+        self.pos_start = state.read_u16()?;
+        state.read_bools_into(&mut self.scan_line)
+    }
 }
 
 pub struct LineState {
     // Line state used per stella line.
-    p_colour: (display::Colour, display::Colour),
-    background_colour: display::Colour,
-    playfield_colour: display::Colour,
+    // Palette indices.
+    p_colour: (u8, u8),
+    background_colour: u8,
+    playfield_colour: u8,
     ctrlpf: u8,
     hmp: (u8, u8),
     hmm: (u8, u8),
@@ -514,6 +574,39 @@ impl LineState {
             hmbl: 0,
         }
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[
+            self.p_colour.0,
+            self.p_colour.1,
+            self.background_colour,
+            self.playfield_colour,
+            self.ctrlpf,
+            self.hmp.0,
+            self.hmp.1,
+            self.hmm.0,
+            self.hmm.1,
+            self.hmbl,
+        ]);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut registers = [0; 10];
+        state.read_bytes_into(&mut registers)?;
+        [
+            self.p_colour.0,
+            self.p_colour.1,
+            self.background_colour,
+            self.playfield_colour,
+            self.ctrlpf,
+            self.hmp.0,
+            self.hmp.1,
+            self.hmm.0,
+            self.hmm.1,
+            self.hmbl,
+        ] = registers;
+        Ok(())
+    }
 }
 
 pub struct CollisionState {
@@ -603,37 +696,48 @@ impl CollisionState {
         }
     }
 
-    pub fn get_cxmp_0(&mut self) -> u8 {
+    pub fn get_cxmp_0(&self) -> u8 {
         self.cxmp.0
     }
 
-    pub fn get_cxmp_1(&mut self) -> u8 {
+    pub fn get_cxmp_1(&self) -> u8 {
         self.cxmp.1
     }
 
-    pub fn get_cxpfb_0(&mut self) -> u8 {
+    pub fn get_cxpfb_0(&self) -> u8 {
         self.cxpfb.0
     }
 
-    pub fn get_cxpfb_1(&mut self) -> u8 {
+    pub fn get_cxpfb_1(&self) -> u8 {
         self.cxpfb.1
     }
 
-    pub fn get_cxmfb_0(&mut self) -> u8 {
+    pub fn get_cxmfb_0(&self) -> u8 {
         self.cxmfb.0
     }
 
-    pub fn get_cxmfb_1(&mut self) -> u8 {
+    pub fn get_cxmfb_1(&self) -> u8 {
         self.cxmfb.1
     }
 
-    pub fn get_cxblpf(&mut self) -> u8 {
+    pub fn get_cxblpf(&self) -> u8 {
         self.cxblpf
     }
 
-    pub fn get_cxppmm(&mut self) -> u8 {
+    pub fn get_cxppmm(&self) -> u8 {
         self.cxppmm
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.cxmp.0, self.cxmp.1, self.cxpfb.0, self.cxpfb.1, self.cxmfb.0, self.cxmfb.1, self.cxblpf, self.cxppmm]);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut latches = [0; 8];
+        state.read_bytes_into(&mut latches)?;
+        [self.cxmp.0, self.cxmp.1, self.cxpfb.0, self.cxpfb.1, self.cxmfb.0, self.cxmfb.1, self.cxblpf, self.cxppmm] = latches;
+        Ok(())
+    }
 }
 
 pub struct Colours {
@@ -650,27 +754,13 @@ impl Colours {
     }
 
     pub fn load(&mut self, pal_palette: bool) {
-        #[cfg(target_os = "emscripten")]
-        let buffer = {
-            if pal_palette {
-                include_bytes!("../../../palette_pal.dat").to_vec()
-            } else {
-                include_bytes!("../../../palette_ntsc.dat").to_vec()
-            }
-        };
-
-        #[cfg(not(target_os = "emscripten"))]
-        let buffer = {
-            let mut inner_buffer = Vec::new();
-            use std::fs::File;
-            use std::io::Read;
-            let palette_filename = if pal_palette { "palette_pal.dat" } else { "palette_ntsc.dat" };
-            let mut file = File::open(palette_filename).unwrap_or_else(|_| panic!("file not found! {}", palette_filename));
-            let _ = file.read_to_end(&mut inner_buffer);
-            inner_buffer
+        let buffer = if pal_palette {
+            include_bytes!("../../../palette_pal.dat").as_slice()
+        } else {
+            include_bytes!("../../../palette_ntsc.dat").as_slice()
         };
 
-        let lines: Vec<String> = std::str::from_utf8(&buffer).unwrap().split('\n').map(|x| x.to_string()).collect();
+        let lines: Vec<String> = std::str::from_utf8(buffer).unwrap().split('\n').map(|x| x.to_string()).collect();
         for (i, line) in lines.iter().enumerate() {
             if !line.is_empty() {
                 let line_without_comments = &line[0..line.find('#').unwrap_or(line.len())].trim_end_matches(' ');
@@ -680,8 +770,22 @@ impl Colours {
         }
     }
 
-    pub fn get_colour(&self, colour: u8) -> display::Colour {
-        self.colours[colour as usize >> 1]
+    // The palette entry for a colour register value (the lowest bit is unused).
+    pub const fn palette_index(colour: u8) -> u8 {
+        colour >> 1
+    }
+
+    pub fn get_colour(&self, index: u8) -> display::Colour {
+        self.colours[index as usize]
+    }
+
+    // 3 bytes (RGB) per palette entry.
+    pub fn rgb24(&self) -> Vec<u8> {
+        let mut buffer = vec![0; 3 * self.colours.len()];
+        for (colour, rgb) in self.colours.iter().zip(buffer.chunks_mut(3)) {
+            colour.convert_rgb24(rgb);
+        }
+        buffer
     }
 }
 
@@ -702,7 +806,8 @@ pub struct Stella {
 
     colours: Colours,
 
-    display_lines: Vec<Vec<display::Colour>>,
+    // Palette indices.
+    display_lines: Vec<Vec<u8>>,
 
     collision_state: CollisionState,
     playfield_state: PlayfieldState,
@@ -713,8 +818,6 @@ pub struct Stella {
     ball: BallState,
 
     scanline_debug: bool,
-
-    bytes_per_pixel: u16,
 }
 
 #[rustfmt::skip]
@@ -757,7 +860,7 @@ impl Stella {
             is_update_time: false,
             is_hmove_scan: false,
             colours,
-            display_lines: vec![vec![display::Colour::new(0, 0, 0); Stella::FRAME_WIDTH as usize]; (Stella::END_DRAW_Y) as usize],
+            display_lines: vec![vec![Constants::DEFAULT_COLOUR; Stella::FRAME_WIDTH as usize]; (Stella::END_DRAW_Y) as usize],
             collision_state: CollisionState::new(),
             playfield_state: PlayfieldState::new(),
             p0_state: PlayerState::new(),
@@ -766,9 +869,6 @@ impl Stella {
             missile1: MissileState::new(),
             ball: BallState::new(),
             scanline_debug,
-            // Gettign bytes_per_pixel once as an optimisation, to reduce call count (was taking ~10% of emulation time with no delay).
-            bytes_per_pixel: display::SDLUtility::bytes_per_pixel(),
-
         }
     }
 
@@ -814,6 +914,27 @@ impl Stella {
         }
     }
 
+    pub fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        // Same as 'read', but without updating the paddle inputs.
+        match address & 0xF {
+            0x0 => { self.collision_state.get_cxmp_0() }
+            0x1 => { self.collision_state.get_cxmp_1() }
+            0x2 => { self.collision_state.get_cxpfb_0() }
+            0x3 => { self.collision_state.get_cxpfb_1() }
+            0x4 => { self.collision_state.get_cxmfb_0() }
+            0x5 => { self.collision_state.get_cxmfb_1() }
+            0x6 => { self.collision_state.get_cxblpf() }
+            0x7 => { self.collision_state.get_cxppmm() }
+            0x8 => { self.input.input0 }
+            0x9 => { self.input.input1 }
+            0xA => { self.input.input2 }
+            0xB => { self.input.input3 }
+            0xC => { self.input.input4 }
+            0xD => { self.input.input5 }
+            _ => { 0 }
+        }
+    }
+
     fn get_write_delay(&mut self, address: u16) -> u8 {
         let default_future = 1;
         // Pulling out simulate the 'delay' of the particular command.  Leads to how far in advance the screen is 'written' to, before the register/value is changed.
@@ -973,19 +1094,19 @@ impl Stella {
     }
 
     fn write_colump0(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
-        self.next_line.p_colour.0 = self.colours.get_colour(data);
+        self.next_line.p_colour.0 = Colours::palette_index(data);
     }
 
     fn write_colump1(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
-        self.next_line.p_colour.1 = self.colours.get_colour(data);
+        self.next_line.p_colour.1 = Colours::palette_index(data);
     }
 
     fn write_colupf(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
-        self.next_line.playfield_colour = self.colours.get_colour(data);
+        self.next_line.playfield_colour = Colours::palette_index(data);
     }
 
     fn write_colubk(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
-        self.next_line.background_colour = self.colours.get_colour(data);
+        self.next_line.background_colour = Colours::palette_index(data);
     }
 
     fn write_ctrlpf(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
@@ -1220,10 +1341,10 @@ impl Stella {
                         let ps0 = self.p0_state.pos_start;
                         let ps1 = self.p1_state.pos_start;
                         if x as u16 == ps0 {
-                            pixel_colour = self.colours.get_colour(0x0E);
+                            pixel_colour = Colours::palette_index(0x0E);
                         }
                         if x as u16 == ps1 {
-                            pixel_colour = self.colours.get_colour(0x78);
+                            pixel_colour = Colours::palette_index(0x78);
                         }
                     }
 
@@ -1296,12 +1417,20 @@ impl io::ReadWriteMemory for Stella {
     fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
         self.read(clock, address)
     }
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.peek(clock, address)
+    }
 }
 
 impl io::DebugClock for Stella {
     fn debug_clock(&mut self) -> clocks::ClockType {
         self.vsync_debug_output_clock
     }
+
+    fn beam_position(&self, clock: &clocks::Clock) -> (clocks::ClockType, clocks::ClockType) {
+        let elapsed = clock.ticks.wrapping_sub(self.screen_start_clock);
+        (elapsed / Stella::HORIZONTAL_TICKS, elapsed % Stella::HORIZONTAL_TICKS)
+    }
 }
 
 impl io::StellaIO for Stella {
@@ -1326,6 +1455,35 @@ impl io::StellaIO for Stella {
         self.tiasound.step(clock);
     }
 
+    fn take_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        self.tiasound.take_audio()
+    }
+
+    fn set_audio_capture(&mut self, capture: bool) {
+        self.tiasound.set_audio_capture(capture);
+    }
+
+    fn take_captured_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        self.tiasound.take_captured_audio()
+    }
+
+    fn frame_rgb24(&self) -> Vec<u8> {
+        let mut buffer = vec![0; 3 * Stella::FRAME_WIDTH as usize * Stella::FRAME_HEIGHT as usize];
+        let lines = &self.display_lines[Stella::START_DRAW_Y as usize..(Stella::START_DRAW_Y + Stella::FRAME_HEIGHT) as usize];
+        for (index, rgb) in lines.iter().flatten().zip(buffer.chunks_mut(3)) {
+            self.colours.get_colour(*index).convert_rgb24(rgb);
+        }
+        buffer
+    }
+
+    fn frame_palette_indices(&self) -> Vec<u8> {
+        self.display_lines[Stella::START_DRAW_Y as usize..(Stella::START_DRAW_Y + Stella::FRAME_HEIGHT) as usize].concat()
+    }
+
+    fn palette_rgb24(&self) -> Vec<u8> {
+        self.colours.rgb24()
+    }
+
     fn export(&mut self) -> bool {
         // If it's time to update, then return the current value and clear it.
         let result = self.is_update_time;
@@ -1333,14 +1491,54 @@ impl io::StellaIO for Stella {
         result
     }
 
-    fn generate_display(&mut self, buffer: &mut [u8]) {
-        let mut index = 0;
-        for y in 0..Stella::FRAME_HEIGHT {
-            let display_line = &self.display_lines[(y + Stella::START_DRAW_Y) as usize];
-            for x in display_line {
-                x.convert_rgb888(&mut buffer[index..(index + self.bytes_per_pixel as usize)]);
-                index += self.bytes_per_pixel as usize;
-            }
+    fn save_state(&self, state: &mut state::StateWriter) {
+        self.input.save_state(state);
+        state.write_u64(self.vsync_debug_output_clock);
+        state.write_u64(self.screen_start_clock);
+        state.write_u64(self.paddle_start_clock);
+        state.write_u64(self.last_screen_update_clock);
+        self.next_line.save_state(state);
+        for flag in [self.is_vsync, self.is_blank, self.is_input_latched, self.is_update_time, self.is_hmove_scan] {
+            state.write_bool(flag);
+        }
+
+        // The partially drawn frame, so the next frame displayed is complete.
+        state.write_bytes(&self.display_lines.concat());
+
+        self.collision_state.save_state(state);
+        self.playfield_state.save_state(state);
+        self.p0_state.save_state(state);
+        self.p1_state.save_state(state);
+        self.missile0.save_state(state);
+        self.missile1.save_state(state);
+        self.ball.save_state(state);
+        self.tiasound.save_state(state);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        self.input.restore_state(state)?;
+        self.vsync_debug_output_clock = state.read_u64()?;
+        self.screen_start_clock = state.read_u64()?;
+        self.paddle_start_clock = state.read_u64()?;
+        self.last_screen_update_clock = state.read_u64()?;
+        self.next_line.restore_state(state)?;
+        for flag in [&mut self.is_vsync, &mut self.is_blank, &mut self.is_input_latched, &mut self.is_update_time, &mut self.is_hmove_scan] {
+            *flag = state.read_bool()?;
+        }
+
+        let mut pixels = vec![0; Stella::FRAME_WIDTH as usize * self.display_lines.len()];
+        state.read_bytes_into(&mut pixels)?;
+        for (line, indices) in self.display_lines.iter_mut().zip(pixels.chunks(Stella::FRAME_WIDTH as usize)) {
+            line.copy_from_slice(indices);
         }
+
+        self.collision_state.restore_state(state)?;
+        self.playfield_state.restore_state(state)?;
+        self.p0_state.restore_state(state)?;
+        self.p1_state.restore_state(state)?;
+        self.missile0.restore_state(state)?;
+        self.missile1.restore_state(state)?;
+        self.ball.restore_state(state)?;
+        self.tiasound.restore_state(state)
     }
 }
@@ -0,0 +1,129 @@
+//! Headless running, without initialising SDL (no window or audio device), ie for regression tests in CI.
+//!
+//! Runs a machine for a number of frames (and/or until the stop clock), then writes any of: the final frame (binary
+//! PPM, at the native 160 pixel width), all of the audio generated (WAV) and the console RAM (the 128 bytes at
+//! 0x80-0xFF).  Only needs the emulation, so is available without the 'sdl' feature (the 'rusted_atari2600_headless'
+//! binary), the SDL frontend runs it with its tools (debugger, movies, screenshots, etc.) for '--headless'.
+
+use super::audio::sound;
+use super::audio::soundchannel;
+use super::clocks;
+use super::cpu;
+use super::machine;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+#[derive(Default)]
+pub struct Headless {
+    pub frames: Option<u64>,
+    pub frame_file: Option<String>,
+    pub audio_file: Option<String>,
+    pub ram_file: Option<String>,
+}
+
+impl Headless {
+    /// Binary ('P6') PPM, 3 bytes (RGB) per pixel.
+    pub fn write_ppm(output: &mut dyn Write, rgb: &[u8], width: u16, height: u16) -> io::Result<()> {
+        write!(output, "P6\n{} {}\n255\n", width, height)?;
+        output.write_all(rgb)
+    }
+
+    /// Run the machine for the frames and/or until the stop clock (0 for none), then write the outputs.
+    pub fn run(&self, machine: &mut machine::Machine, stop_clock: clocks::ClockType) -> io::Result<()> {
+        self.run_with(machine, stop_clock, |machine| Some(machine.step()))
+    }
+
+    /// As 'run', with 'step' running each instruction (and anything around it), returning true at the end of a frame
+    /// (None to stop early).
+    pub fn run_with(&self, machine: &mut machine::Machine, stop_clock: clocks::ClockType, mut step: impl FnMut(&mut machine::Machine) -> Option<bool>) -> io::Result<()> {
+        let mut frames = 0;
+        let mut audio = Vec::new();
+        while self.frames.is_none_or(|stop_frames| frames < stop_frames) && !(stop_clock > 0 && machine.core().clock.ticks > stop_clock) {
+            match step(machine) {
+                None => break,
+                Some(true) => {
+                    frames += 1;
+                    // Collected each frame, so the generated audio doesn't build up.
+                    let mut frame_audio = machine.take_audio();
+                    if self.audio_file.is_some() {
+                        audio.append(&mut frame_audio);
+                    }
+                }
+                Some(false) => {}
+            }
+        }
+
+        println!("Ran {} frames, {} CPU cycles", frames, machine.core().clock.ticks / cpu::pc_state::PcState::CYCLES_TO_CLOCK as clocks::ClockType);
+        audio.append(&mut machine.take_audio());
+        self.write_outputs(machine, &audio)
+    }
+
+    /// Write the requested frame, audio and RAM files.
+    pub fn write_outputs(&self, machine: &machine::Machine, audio: &[soundchannel::PlaybackType]) -> io::Result<()> {
+        if let Some(frame_file) = &self.frame_file {
+            let mut output = io::BufWriter::new(fs::File::create(frame_file)?);
+            Headless::write_ppm(&mut output, &machine.frame_rgb24(), machine::Machine::FRAME_WIDTH, machine::Machine::FRAME_HEIGHT)?;
+            output.flush()?;
+            println!("Frame written to: {}", frame_file);
+        }
+        if let Some(audio_file) = &self.audio_file {
+            let mut output = sound::HoundOutput::new(audio_file).map_err(io::Error::other)?;
+            output.write(audio).map_err(io::Error::other)?;
+            output.finalize().map_err(io::Error::other)?;
+            println!("Audio ({} samples) written to: {}", audio.len(), audio_file);
+        }
+        if let Some(ram_file) = &self.ram_file {
+            fs::write(ram_file, machine.ram())?;
+            println!("RAM written to: {}", ram_file);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::headless;
+    use crate::atari2600::testing;
+    use std::fs;
+
+    #[test]
+    fn test_run_headless() {
+        // Frame loop: VSYNC for 3 lines, VBLANK off, INC $80, LDA #$1E, STA COLUBK, 200 lines of WSYNC, JMP $F000
+        let rom = testing::rom(
+            &[
+                0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xE6, 0x80, 0xA9, 0x1E, 0x85, 0x09, 0xA2, 0xC8, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00, 0xF0,
+            ],
+            0x00,
+        );
+        let file_name = |name: &str| testing::temp_path(&format!("headless_test.{}", name)).to_string_lossy().to_string();
+        let mut machine = testing::machine(&rom);
+
+        let headless = headless::Headless {
+            frames: Some(3),
+            frame_file: Some(file_name("ppm")),
+            audio_file: Some(file_name("wav")),
+            ram_file: Some(file_name("ram")),
+        };
+        headless.run(&mut machine, 0).unwrap();
+
+        // Stopped at the start (VSYNC) of the 3rd frame, after 2 increments.
+        let ram = fs::read(file_name("ram")).unwrap();
+        assert_eq!(ram.len(), 128);
+        assert_eq!(ram[0], 2);
+
+        let ppm = fs::read(file_name("ppm")).unwrap();
+        let header = b"P6\n160 220\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 3 * 160 * 220);
+        // The background colour has been drawn.
+        let pixel = header.len() + 3 * (100 * 160 + 80);
+        assert_ne!(&ppm[pixel..pixel + 3], &[0, 0, 0]);
+
+        assert!(fs::read(file_name("wav")).unwrap().starts_with(b"RIFF"));
+        for name in ["ppm", "wav", "ram"] {
+            fs::remove_file(file_name(name)).unwrap();
+        }
+    }
+}
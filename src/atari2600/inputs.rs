@@ -1,4 +1,7 @@
+use super::state;
+#[cfg(feature = "sdl")]
 use sdl2::event;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard; // Keycode // Keycode
 
 #[derive(Clone, Copy)]
@@ -30,6 +33,17 @@ impl Input {
             quit: 0x0,
         }
     }
+
+    pub fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&[self.swcha, self.swchb, self.input0, self.input1, self.input2, self.input3, self.input4, self.input5, self.quit]);
+    }
+
+    pub fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut values = [0; 9];
+        state.read_bytes_into(&mut values)?;
+        [self.swcha, self.swchb, self.input0, self.input1, self.input2, self.input3, self.input4, self.input5, self.quit] = values;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -103,8 +117,23 @@ impl Joystick {
     }
 }
 
+/// Emulator (rather than console) controls.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    // Held to step backwards, a frame at a time.
+    Rewind(bool),
+    // Save the frame as a PNG (once it's complete).
+    Screenshot,
+    // Start/stop capturing the video and audio.
+    Capture,
+}
+
+#[cfg(feature = "sdl")]
 pub struct UserInput {}
 
+#[cfg(feature = "sdl")]
 impl UserInput {
     const KEY_1_UP: keyboard::Keycode = keyboard::Keycode::Up;
     const KEY_1_DOWN: keyboard::Keycode = keyboard::Keycode::Down;
@@ -124,6 +153,12 @@ impl UserInput {
     const KEY_P1_DIFFICULTY: keyboard::Keycode = keyboard::Keycode::Num2;
     const KEY_QUIT: keyboard::Keycode = keyboard::Keycode::Escape;
 
+    const KEY_SAVE_STATE: keyboard::Keycode = keyboard::Keycode::F5;
+    const KEY_LOAD_STATE: keyboard::Keycode = keyboard::Keycode::F7;
+    const KEY_REWIND: keyboard::Keycode = keyboard::Keycode::Backspace;
+    const KEY_SCREENSHOT: keyboard::Keycode = keyboard::Keycode::F12;
+    const KEY_CAPTURE: keyboard::Keycode = keyboard::Keycode::F10;
+
     pub fn print_keys() {
         print!("Key mappings (Joystick 1): ");
         println!("Up: {}, Down: {}, Left: {}, Right: {}", UserInput::KEY_1_UP, UserInput::KEY_1_DOWN, UserInput::KEY_1_LEFT, UserInput::KEY_1_RIGHT);
@@ -136,9 +171,40 @@ impl UserInput {
         println!("P0 Difficulty: {}, P1 Difficulty: {}", UserInput::KEY_P0_DIFFICULTY, UserInput::KEY_P1_DIFFICULTY);
         println!("Reset: {}, Select: {}", UserInput::KEY_RESET, UserInput::KEY_SELECT);
         println!();
+        println!("Save state: {}, Load state: {}, Rewind (hold): {}", UserInput::KEY_SAVE_STATE, UserInput::KEY_LOAD_STATE, UserInput::KEY_REWIND);
+        println!("Screenshot: {}, Capture video/audio (start/stop): {}", UserInput::KEY_SCREENSHOT, UserInput::KEY_CAPTURE);
         println!("Quit: {}", UserInput::KEY_QUIT);
     }
 
+    // Return the emulator control for the event (if any), these are in addition to the console inputs.
+    pub fn hotkey(event: &event::Event) -> Option<Hotkey> {
+        match event {
+            event::Event::KeyDown {
+                keycode: Some(UserInput::KEY_SAVE_STATE),
+                repeat: false,
+                ..
+            } => Some(Hotkey::SaveState),
+            event::Event::KeyDown {
+                keycode: Some(UserInput::KEY_LOAD_STATE),
+                repeat: false,
+                ..
+            } => Some(Hotkey::LoadState),
+            event::Event::KeyDown {
+                keycode: Some(UserInput::KEY_SCREENSHOT),
+                repeat: false,
+                ..
+            } => Some(Hotkey::Screenshot),
+            event::Event::KeyDown {
+                keycode: Some(UserInput::KEY_CAPTURE),
+                repeat: false,
+                ..
+            } => Some(Hotkey::Capture),
+            event::Event::KeyDown { keycode: Some(UserInput::KEY_REWIND), .. } => Some(Hotkey::Rewind(true)),
+            event::Event::KeyUp { keycode: Some(UserInput::KEY_REWIND), .. } => Some(Hotkey::Rewind(false)),
+            _ => None,
+        }
+    }
+
     // Return 'true' if handled, otherwise 'false' (ie quit)
     pub fn handle_events(event: event::Event, joystick: &mut Joystick) -> bool {
         match event {
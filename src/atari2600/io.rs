@@ -1,24 +1,44 @@
 use super::audio::soundchannel;
 use super::clocks;
 use super::inputs;
+use super::state;
 
 pub trait ReadWriteMemory {
     fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8;
     fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8);
+    // Read without any side effects (for debugging/inspection).
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8;
 }
 
 pub trait DebugClock {
     fn debug_clock(&mut self) -> clocks::ClockType;
+    // Current beam position (scanline since vsync, colour clock within the line).
+    fn beam_position(&self, clock: &clocks::Clock) -> (clocks::ClockType, clocks::ClockType);
 }
 
 pub trait StellaIO: ReadWriteMemory + DebugClock {
     fn export(&mut self) -> bool;
-    fn generate_display(&mut self, buffer: &mut [u8]);
     fn set_inputs(&mut self, inputs: inputs::Input);
     fn get_next_audio_chunk(&mut self, length: u32) -> Vec<soundchannel::PlaybackType>;
     fn step_tia_sound(&mut self, clock: &clocks::Clock);
+    // All of the audio generated since the last call (for recording, rather than real-time playback).
+    fn take_audio(&mut self) -> Vec<soundchannel::PlaybackType>;
+    // Keep a copy of all of the audio generated (unaffected by real-time playback), see 'take_captured_audio'.
+    fn set_audio_capture(&mut self, capture: bool);
+    fn take_captured_audio(&mut self) -> Vec<soundchannel::PlaybackType>;
+    // The current frame, 3 bytes (RGB) per pixel, a line at a time.
+    fn frame_rgb24(&self) -> Vec<u8>;
+    // The current frame as palette indices (see 'palette_rgb24'), a byte per pixel.
+    fn frame_palette_indices(&self) -> Vec<u8>;
+    fn palette_rgb24(&self) -> Vec<u8>;
+
+    // Save states (see 'state'), restore returns an error if the state is invalid.
+    fn save_state(&self, state: &mut state::StateWriter);
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()>;
 }
 
 pub trait RiotIO: ReadWriteMemory {
     fn set_inputs(&mut self, inputs: inputs::Input);
+    fn save_state(&self, state: &mut state::StateWriter);
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()>;
 }
@@ -0,0 +1,247 @@
+//! The console, independent of any frontend (no display, audio device or event handling).
+//!
+//! Intended flow:
+//!  - Construct with a 'MachineConfig'.
+//!  - Insert a cartridge (the ROM image bytes).
+//!  - Set the console switches and controllers (at any time).
+//!  - 'run_frame' (repeatedly).
+//!  - Read back the frame (palette indices or RGB) and the audio samples generated.
+//!
+//! The SDL frontend ('atari2600'), headless runs and the emscripten build are all built on this.
+
+use super::audio::soundchannel;
+use super::clocks;
+use super::cpu;
+use super::graphics;
+use super::inputs;
+use super::memory;
+use super::ports;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MachineConfig {
+    // Print PC state debug info.
+    pub debug: bool,
+    // Delay to run at the speed of the console (otherwise run as fast as possible).
+    pub realtime: bool,
+    pub pal_palette: bool,
+}
+
+/// Console switches, the defaults are colour and both difficulties 'B'.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Switches {
+    pub reset: bool,
+    pub select: bool,
+    pub black_and_white: bool,
+    pub p0_difficulty_a: bool,
+    pub p1_difficulty_a: bool,
+}
+
+/// Joystick state.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Controller {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+pub struct Machine {
+    config: MachineConfig,
+    core: cpu::core::Core,
+}
+
+impl Machine {
+    pub const FRAME_WIDTH: u16 = graphics::stella::Stella::FRAME_WIDTH;
+    pub const FRAME_HEIGHT: u16 = graphics::stella::Stella::FRAME_HEIGHT;
+    // Give up on a frame (ie a cartridge that doesn't VSYNC) after a few frames worth of PAL (the longest) lines.
+    const MAX_FRAME_TICKS: clocks::ClockType = 4 * 312 * graphics::stella::Stella::HORIZONTAL_TICKS;
+
+    const RAM_START: u16 = 0x80;
+    const RAM_SIZE: u16 = 0x80;
+
+    /// A machine with an empty cartridge slot (see 'insert_cartridge').
+    pub fn new(config: MachineConfig) -> Self {
+        let clock = clocks::Clock::new();
+        let pc_state = cpu::pc_state::PcState::new();
+        let stella = graphics::stella::Stella::new(config.debug, config.realtime, config.pal_palette);
+        let riot = memory::riot::Riot::new();
+        let cartridge = memory::cartridge::new_cartridge(&[], &memory::cartridge::CartridgeType::Default);
+        let memory = memory::memory::Memory::new(cartridge, Box::new(stella), Box::new(riot));
+        let ports = ports::Ports::new();
+
+        let mut core = cpu::core::Core::new(clock, memory, pc_state, ports);
+        core.reset();
+
+        Self { config, core }
+    }
+
+    /// Insert the cartridge (ROM image) and reset the CPU.
+    pub fn insert_cartridge(&mut self, data: &[u8], cartridge_type: &memory::cartridge::CartridgeType) {
+        self.core.memory.insert_cartridge(memory::cartridge::new_cartridge(data, cartridge_type));
+        self.core.reset();
+    }
+
+    pub fn config(&self) -> &MachineConfig {
+        &self.config
+    }
+
+    pub fn core(&self) -> &cpu::core::Core {
+        &self.core
+    }
+
+    pub fn core_mut(&mut self) -> &mut cpu::core::Core {
+        &mut self.core
+    }
+
+    pub fn into_core(self) -> cpu::core::Core {
+        self.core
+    }
+
+    pub fn input(&self) -> inputs::Input {
+        self.core.ports.joysticks.input
+    }
+
+    /// Set all of the console inputs (switches and controllers) at once.
+    pub fn set_input(&mut self, input: inputs::Input) {
+        self.core.ports.joysticks.input = input;
+        self.core.memory.riot.set_inputs(input);
+        self.core.memory.stella.set_inputs(input);
+    }
+
+    pub fn set_switches(&mut self, switches: Switches) {
+        let joystick = &mut self.core.ports.joysticks;
+        joystick.reset(switches.reset);
+        joystick.select(switches.select);
+        inputs::Joystick::set_input(switches.black_and_white, &mut joystick.input.swchb, 0x08);
+        inputs::Joystick::set_input(!switches.p0_difficulty_a, &mut joystick.input.swchb, 0x40);
+        inputs::Joystick::set_input(!switches.p1_difficulty_a, &mut joystick.input.swchb, 0x80);
+        self.set_input(self.input());
+    }
+
+    /// Set the joystick plugged into 'port' (0 or 1).
+    pub fn set_controller(&mut self, port: usize, controller: Controller) {
+        let joystick = &mut self.core.ports.joysticks;
+        match port {
+            0 => {
+                joystick.j1_up(controller.up);
+                joystick.j1_down(controller.down);
+                joystick.j1_left(controller.left);
+                joystick.j1_right(controller.right);
+                joystick.j1_fire(controller.fire);
+            }
+            1 => {
+                joystick.j2_up(controller.up);
+                joystick.j2_down(controller.down);
+                joystick.j2_left(controller.left);
+                joystick.j2_right(controller.right);
+                joystick.j2_fire(controller.fire);
+            }
+            _ => panic!("Controller port {} doesn't exist (0 or 1)", port),
+        }
+        self.set_input(self.input());
+    }
+
+    /// Run a single CPU instruction (and the TIA sound), returns true at the end of a frame (the start of VSYNC).
+    pub fn step(&mut self) -> bool {
+        self.core.step(self.config.debug, self.config.realtime);
+        self.core.memory.stella.step_tia_sound(&self.core.clock);
+        self.core.memory.stella.export()
+    }
+
+    /// Run until the end of the frame, returns false if the cartridge didn't finish a frame.
+    pub fn run_frame(&mut self) -> bool {
+        let start = self.core.clock.ticks;
+        while self.core.clock.ticks - start < Machine::MAX_FRAME_TICKS {
+            if self.step() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The current frame ('FRAME_WIDTH' x 'FRAME_HEIGHT'), a palette index (see 'palette_rgb24') per pixel.
+    pub fn frame_palette_indices(&self) -> Vec<u8> {
+        self.core.memory.stella.frame_palette_indices()
+    }
+
+    /// The current frame, 3 bytes (RGB) per pixel.
+    pub fn frame_rgb24(&self) -> Vec<u8> {
+        self.core.memory.stella.frame_rgb24()
+    }
+
+    /// The colours of the palette (NTSC or PAL), 3 bytes (RGB) per entry.
+    pub fn palette_rgb24(&self) -> Vec<u8> {
+        self.core.memory.stella.palette_rgb24()
+    }
+
+    /// All of the audio samples generated since the last call.
+    pub fn take_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        self.core.memory.stella.take_audio()
+    }
+
+    /// Keep a copy of all of the audio generated, regardless of playback (which may drop samples to keep up).
+    pub fn set_audio_capture(&mut self, capture: bool) {
+        self.core.memory.stella.set_audio_capture(capture);
+    }
+
+    /// All of the audio captured since the last call (empty unless capturing).
+    pub fn take_captured_audio(&mut self) -> Vec<soundchannel::PlaybackType> {
+        self.core.memory.stella.take_captured_audio()
+    }
+
+    /// Audio for real-time playback, up to 'length' samples (per channel).
+    pub fn next_audio_chunk(&mut self, length: u32) -> Vec<soundchannel::PlaybackType> {
+        self.core.memory.stella.get_next_audio_chunk(length)
+    }
+
+    /// The console RAM (the 128 bytes at 0x80-0xFF).
+    pub fn ram(&self) -> Vec<u8> {
+        (Machine::RAM_START..Machine::RAM_START + Machine::RAM_SIZE).map(|address| self.core.memory.peek(&self.core.clock, address)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::machine;
+    use crate::atari2600::memory::cartridge;
+    use crate::atari2600::testing;
+
+    #[test]
+    fn test_run_frame() {
+        // VSYNC for 3 lines, VBLANK off, LDA SWCHA, STA $80, LDA SWCHB, STA $81, LDA #$1E, STA COLUBK, 200 lines of WSYNC, JMP $F000
+        let mut machine = testing::machine(&testing::rom(
+            &[
+                0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xAD, 0x80, 0x02, 0x85, 0x80, 0xAD, 0x82, 0x02, 0x85, 0x81, 0xA9, 0x1E, 0x85, 0x09, 0xA2, 0xC8, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00,
+                0xF0,
+            ],
+            0x00,
+        ));
+        machine.set_controller(0, machine::Controller { left: true, fire: true, ..Default::default() });
+        machine.set_switches(machine::Switches {
+            black_and_white: true,
+            p1_difficulty_a: true,
+            ..Default::default()
+        });
+
+        // The first frame ends at the first VSYNC, then a whole frame.
+        assert!(machine.run_frame());
+        assert!(machine.run_frame());
+        assert_eq!(machine.ram()[0..2], [0xBF, 0xB7]);
+
+        let indices = machine.frame_palette_indices();
+        assert_eq!(indices.len(), machine::Machine::FRAME_WIDTH as usize * machine::Machine::FRAME_HEIGHT as usize);
+        let pixel = 100 * machine::Machine::FRAME_WIDTH as usize + 80;
+        assert_eq!(indices[pixel], 0x1E >> 1);
+        let palette = machine.palette_rgb24();
+        assert_eq!(palette.len(), 3 * 128);
+        let colour = 3 * indices[pixel] as usize;
+        assert_eq!(machine.frame_rgb24()[3 * pixel..3 * pixel + 3], palette[colour..colour + 3]);
+
+        assert!(!machine.take_audio().is_empty());
+
+        // A cartridge that never finishes a frame.
+        machine.insert_cartridge(&testing::rom(&[0x4C, 0x00, 0xF0], 0x00), &cartridge::CartridgeType::Default);
+        assert!(!machine.run_frame());
+    }
+}
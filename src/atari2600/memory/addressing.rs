@@ -1,89 +1,120 @@
 use super::super::clocks;
 use super::super::cpu::pc_state;
-use super::memory;
+use super::bus;
 
 pub trait Address16 {
-    fn address16(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) -> u16;
+    fn address16(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) -> u16;
     fn get_addressing_size(&self) -> u8;
-    fn get_addressing_time(&self) -> u8;
 }
 
-fn did_index_cross_page(base_address: u16, result_address: u16) -> bool {
+pub fn did_index_cross_page(base_address: u16, result_address: u16) -> bool {
     (base_address & 0xFF00) != (result_address & 0xFF00)
 }
 
+// Each CPU cycle performs a single bus access.
+// The clock is advanced before the access, so it happens with the clock at the end of its cycle.
+pub fn cycle_read(clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, address: u16) -> u8 {
+    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    memory.read(clock, address)
+}
+
+pub fn cycle_write(clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, address: u16, data: u8) {
+    clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+    memory.write(clock, address, data);
+}
+
+pub fn cycle_read16(clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, address: u16) -> u16 {
+    let low = cycle_read(clock, memory, address);
+    let high = cycle_read(clock, memory, address.wrapping_add(1));
+    ((high as u16) << 8) | low as u16
+}
+
 pub struct AllAddressingModes {}
 
 impl AllAddressingModes {
     // A 'page cross', is when there was a carry during 'indexed addressing'
-    // If the initial address read was on the same page, then the 'pipepline' read will be valid.
-    // If the carry was needed, then a 're-read' of the 'correct' address is needed, adding an extra delay.
-    // The 'delay' is only applicable to op-codes that immediately use the 'result address' (ie LDA).  Generally, for functions that use the address to 'write',
-    // It appears as though there's generally time to 'fix' the address before it's needed for the 'write' operation (eg STA).
+    // The CPU reads from the 'uncorrected' address (the high byte without the carry) while it adds the carry.
+    // If the carry wasn't needed, then the read was valid (ie LDA), otherwise a 're-read' of the 'correct' address is needed, adding an extra cycle.
+    // Writes (eg STA) and read-modify-writes can't undo a write to the wrong address, so they always take the extra cycle (the 'dummy' read).
+    // The dummy reads reach the bus, so they can trigger hotspots/strobes.
 
-    pub fn address_accumulator(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        // TODO: Check implementation.
+    pub fn address_accumulator(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        // The accumulator read/write includes the (dummy) read.
         0
     }
-    pub fn address_imm(clock: &clocks::Clock, pc_state: &pc_state::PcState, _: &memory::Memory, page_delay: bool) -> u16 {
+
+    pub fn address_imm(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
         pc_state.get_pc().wrapping_add(1)
     }
 
-    pub fn address_zp(clock: &clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        memory.read(clock, pc_state.get_pc().wrapping_add(1)) as u16
+    pub fn address_zp(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1)) as u16
     }
 
-    pub fn address_izx(clock: &clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        let tmp8 = memory.read(clock, pc_state.get_pc().wrapping_add(1)).wrapping_add(pc_state.get_x());
-        memory.read16(clock, tmp8 as u16)
+    pub fn address_izx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let base = cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+        // Dummy read, whilst the index is added.
+        cycle_read(clock, memory, base as u16);
+        let tmp8 = base.wrapping_add(pc_state.get_x());
+        // The pointer wraps within the zero page.
+        let low = cycle_read(clock, memory, tmp8 as u16);
+        let high = cycle_read(clock, memory, tmp8.wrapping_add(1) as u16);
+        ((high as u16) << 8) | low as u16
     }
 
-    pub fn address_zpx(clock: &clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        (memory.read(clock, pc_state.get_pc().wrapping_add(1)).wrapping_add(pc_state.get_x())) as u16
+    pub fn address_zpx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let base = cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+        cycle_read(clock, memory, base as u16);
+        base.wrapping_add(pc_state.get_x()) as u16
     }
 
-    pub fn address_zpy(clock: &clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        (memory.read(clock, pc_state.get_pc().wrapping_add(1)).wrapping_add(pc_state.get_y())) as u16
+    pub fn address_zpy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let base = cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+        cycle_read(clock, memory, base as u16);
+        base.wrapping_add(pc_state.get_y()) as u16
     }
 
-    pub fn address_abs(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        memory.read16(clock, pc_state.get_pc().wrapping_add(1))
+    pub fn address_abs(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1))
     }
 
-    pub fn address_indirect(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        let indirect_addr = memory.read16(clock, pc_state.get_pc().wrapping_add(1));
-        memory.read16(clock, indirect_addr)
+    pub fn address_indirect(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let indirect_addr = cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1));
+        // The high byte is read without carrying into the next page (ie 'JMP ($10FF)' reads from 0x10FF and 0x1000).
+        let low = cycle_read(clock, memory, indirect_addr);
+        let high = cycle_read(clock, memory, (indirect_addr & 0xFF00) | (indirect_addr.wrapping_add(1) & 0xFF));
+        ((high as u16) << 8) | low as u16
     }
 
-    pub fn address_izy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        let tmp8 = memory.read(clock, pc_state.get_pc().wrapping_add(1));
-        let address_tmp = memory.read16(clock, tmp8 as u16);
-        let tmp16 = address_tmp.wrapping_add(pc_state.get_y() as u16);
-
-        if page_delay && did_index_cross_page(address_tmp, tmp16) {
-            clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-        }
+    pub fn address_izy(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let tmp8 = cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
+        let low = cycle_read(clock, memory, tmp8 as u16);
+        let high = cycle_read(clock, memory, tmp8.wrapping_add(1) as u16);
+        let address_tmp = ((high as u16) << 8) | low as u16;
 
-        tmp16
+        Self::index(clock, memory, address_tmp, pc_state.get_y(), page_delay)
     }
 
-    pub fn address_aby(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        let address_tmp = memory.read16(clock, pc_state.get_pc().wrapping_add(1));
-        let tmp16: u16 = address_tmp + pc_state.get_y() as u16;
+    pub fn address_aby(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let address_tmp = cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1));
+        Self::index(clock, memory, address_tmp, pc_state.get_y(), page_delay)
+    }
 
-        if page_delay && did_index_cross_page(address_tmp, tmp16) {
-            clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
-        }
+    pub fn address_abx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, page_delay: bool) -> u16 {
+        let address_tmp = cycle_read16(clock, memory, pc_state.get_pc().wrapping_add(1));
+        Self::index(clock, memory, address_tmp, pc_state.get_x(), page_delay)
+    }
 
-        tmp16
+    pub fn uncorrected_address(base_address: u16, index: u8) -> u16 {
+        (base_address & 0xFF00) | (base_address.wrapping_add(index as u16) & 0xFF)
     }
 
-    pub fn address_abx(clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, page_delay: bool) -> u16 {
-        let address_tmp = memory.read16(clock, pc_state.get_pc().wrapping_add(1));
-        let tmp16: u16 = address_tmp + pc_state.get_x() as u16;
+    fn index(clock: &mut clocks::Clock, memory: &mut dyn bus::Bus, base_address: u16, index: u8, page_delay: bool) -> u16 {
+        // 'page_delay', only re-read if the page was crossed, otherwise always do the dummy read.
+        let tmp16 = base_address.wrapping_add(index as u16);
 
-        if page_delay && did_index_cross_page(address_tmp, tmp16) {
-            clock.increment(pc_state::PcState::CYCLES_TO_CLOCK as u32);
+        if !page_delay || did_index_cross_page(base_address, tmp16) {
+            cycle_read(clock, memory, Self::uncorrected_address(base_address, index));
         }
 
         tmp16
@@ -108,7 +139,7 @@ pub enum Addressing {
 }
 
 impl Address16 for Addressing {
-    fn address16(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory) -> u16 {
+    fn address16(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus) -> u16 {
         match self {
             Addressing::Imm => AllAddressingModes::address_imm(clock, pc_state, memory, false),
             Addressing::Zp => AllAddressingModes::address_zp(clock, pc_state, memory, false),
@@ -136,161 +167,105 @@ impl Address16 for Addressing {
             Addressing::Abs | Addressing::Indirect | Addressing::Aby | Addressing::Abx | Addressing::AbyPageDelay | Addressing::AbxPageDelay => 2,
         }
     }
-
-    fn get_addressing_time(&self) -> u8 {
-        pc_state::PcState::CYCLES_TO_CLOCK
-            * match self {
-                Addressing::Imm => 0,
-                Addressing::Zp => 1,
-                Addressing::Izy => 3,
-                Addressing::IZYPageDelay => 3,
-                Addressing::Izx => 4,
-                Addressing::Zpx => 2,
-                Addressing::Zpy => 2,
-
-                Addressing::Abs => 2,
-                Addressing::Indirect => 4,
-                Addressing::Aby => 2,
-                Addressing::Abx => 2,
-                Addressing::Accumulator => 0,
-
-                Addressing::AbyPageDelay => 2,
-                Addressing::AbxPageDelay => 2,
-            }
-    }
 }
 
 pub trait ReadData {
-    fn read(&self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &mut memory::Memory, address: u16) -> u8;
-    fn get_reading_time(&self) -> u8;
+    fn read(&self, clock: &mut clocks::Clock, pc_state: &pc_state::PcState, memory: &mut dyn bus::Bus, address: u16) -> u8;
 }
 
-pub struct MemoryRead {
-    cycles: u8,
-}
+pub struct MemoryRead {}
 
 impl MemoryRead {
     pub const fn new() -> Self {
-        Self { cycles: 2 }
+        Self {}
     }
+}
 
-    fn read(&self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &mut memory::Memory, address: u16) -> u8 {
-        memory.read(clock, address)
+impl ReadData for MemoryRead {
+    fn read(&self, clock: &mut clocks::Clock, pc_state: &pc_state::PcState, memory: &mut dyn bus::Bus, address: u16) -> u8 {
+        cycle_read(clock, memory, address)
     }
 }
 
-pub struct AccumulatorRead {
-    cycles: u8,
-}
+pub struct AccumulatorRead {}
 
 impl AccumulatorRead {
     pub const fn new() -> Self {
-        Self { cycles: 2 }
+        Self {}
     }
+}
 
-    fn read(&self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &mut memory::Memory, address: u16) -> u8 {
+impl ReadData for AccumulatorRead {
+    fn read(&self, clock: &mut clocks::Clock, pc_state: &pc_state::PcState, memory: &mut dyn bus::Bus, address: u16) -> u8 {
+        // Dummy read of the next byte.
+        cycle_read(clock, memory, pc_state.get_pc().wrapping_add(1));
         pc_state.get_a()
     }
 }
 
-pub struct NullRead {
-    cycles: u8,
-}
+pub struct NullRead {}
 
 impl NullRead {
     pub const fn new() -> Self {
-        Self { cycles: 1 }
+        Self {}
     }
+}
 
-    fn read(&self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &mut memory::Memory, address: u16) -> u8 {
+impl ReadData for NullRead {
+    fn read(&self, clock: &mut clocks::Clock, pc_state: &pc_state::PcState, memory: &mut dyn bus::Bus, address: u16) -> u8 {
         0
     }
 }
 
-macro_rules! impl_read_data {
-    ($type:ty) => {
-        impl ReadData for $type {
-            fn read(&self, clock: &clocks::Clock, pc_state: &pc_state::PcState, memory: &mut memory::Memory, address: u16) -> u8 {
-                self.read(clock, pc_state, memory, address)
-            }
-
-            fn get_reading_time(&self) -> u8 {
-                self.cycles * pc_state::PcState::CYCLES_TO_CLOCK
-            }
-        }
-    };
-}
-
-impl_read_data!(AccumulatorRead);
-impl_read_data!(MemoryRead);
-impl_read_data!(NullRead);
-
 pub trait WriteData {
-    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8);
-    fn get_writing_time(&self) -> u8;
+    // 'value' is the value that was read (re-written by read-modify-write instructions), 'data' is the result to write.
+    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: u16, value: u8, data: u8);
 }
 
-pub struct MemoryWrite {
-    cycles: u8,
-}
+pub struct MemoryWrite {}
 
 impl MemoryWrite {
     pub const fn new() -> Self {
-        Self { cycles: 2 }
+        Self {}
     }
+}
 
-    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8) {
-        memory.write(clock, address, data);
+impl WriteData for MemoryWrite {
+    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: u16, value: u8, data: u8) {
+        // Read-modify-write, the unmodified value is written back whilst the result is calculated (a 'double write').
+        cycle_write(clock, memory, address, value);
+        cycle_write(clock, memory, address, data);
     }
 }
 
-pub struct AccumulatorWrite {
-    cycles: u8,
-}
+pub struct AccumulatorWrite {}
 
 impl AccumulatorWrite {
     pub const fn new() -> Self {
-        Self { cycles: 0 }
+        Self {}
     }
+}
 
-    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8) {
+impl WriteData for AccumulatorWrite {
+    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: u16, value: u8, data: u8) {
         pc_state.set_a(data);
     }
 }
 
-pub struct RegisterWrite {
-    cycles: u8,
-}
+pub struct RegisterWrite {}
 
 impl RegisterWrite {
     pub const fn new() -> Self {
-        Self { cycles: 1 }
-    }
-
-    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8) {
-        memory.write(clock, address, data);
+        Self {}
     }
 }
 
-macro_rules! impl_write_data {
-    ($type:ty) => {
-        impl WriteData for $type {
-            fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8) {
-                self.write(clock, pc_state, memory, address, data);
-            }
-
-            fn get_writing_time(&self) -> u8 {
-                self.cycles * pc_state::PcState::CYCLES_TO_CLOCK
-            }
-        }
-    };
+impl WriteData for RegisterWrite {
+    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: u16, value: u8, data: u8) {
+        cycle_write(clock, memory, address, data);
+    }
 }
 
-impl_write_data!(AccumulatorWrite);
-impl_write_data!(MemoryWrite);
-impl_write_data!(RegisterWrite);
-
-// TODO: Fix Null write
 pub struct MemoryNull {}
 
 impl MemoryNull {
@@ -300,8 +275,5 @@ impl MemoryNull {
 }
 
 impl WriteData for MemoryNull {
-    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut memory::Memory, address: u16, data: u8) {}
-    fn get_writing_time(&self) -> u8 {
-        0
-    }
+    fn write(&self, clock: &mut clocks::Clock, pc_state: &mut pc_state::PcState, memory: &mut dyn bus::Bus, address: u16, value: u8, data: u8) {}
 }
@@ -0,0 +1,28 @@
+use super::super::clocks;
+
+/// The 6507 view of the address space.
+///
+/// The CPU only reads/writes through this, so it can be driven against the
+/// full system ('memory::Memory') or flat RAM ('flat_memory::FlatMemory')
+/// for tests and tooling.
+pub trait Bus {
+    fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8;
+    fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8);
+
+    /// Read without any side effects (ie no bank switching/latching), for debugging and inspection.
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8;
+
+    fn read16(&mut self, clock: &clocks::Clock, address: u16) -> u16 {
+        self.read(clock, address) as u16 + ((self.read(clock, address.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn read_sp(&mut self, clock: &clocks::Clock, address: u8) -> u8 {
+        self.read(clock, address as u16 + STACK_PAGE)
+    }
+
+    fn write_sp(&mut self, clock: &mut clocks::Clock, address: u8, data: u8) {
+        self.write(clock, address as u16 + STACK_PAGE, data);
+    }
+}
+
+const STACK_PAGE: u16 = 0x100;
@@ -1,4 +1,11 @@
-use std::str::FromStr;
+use super::super::clocks;
+use super::super::state;
+use super::cartridge_3f;
+use super::cartridge_dpc;
+use super::cartridge_e0;
+use super::cartridge_e7;
+use super::cartridge_fe;
+use strum_macros::Display;
 use strum_macros::EnumIter;
 use strum_macros::EnumString;
 
@@ -8,7 +15,7 @@ type NumBanksType = u8;
 const BANK_SIZE: BankSizeType = 0x0400;
 const MAX_BANKS: NumBanksType = 8;
 
-#[derive(Debug, EnumIter, EnumString, Clone, Copy)]
+#[derive(Debug, Display, EnumIter, EnumString, Clone, Copy)]
 pub enum CartridgeType {
     Default,
     F4,
@@ -19,30 +26,83 @@ pub enum CartridgeType {
     F8SC,
     Cbs,
     Super,
+    E0,
+    E7,
+    FE,
+    #[strum(to_string = "3F", serialize = "Tigervision3F")]
+    Tigervision3F,
+    #[strum(to_string = "3E", serialize = "Tigervision3E")]
+    Tigervision3E,
+    #[strum(to_string = "3E+", serialize = "Tigervision3EPlus")]
+    Tigervision3EPlus,
+    DPC,
 }
 
 #[derive(Clone)]
 pub struct Bank {
-    data: Vec<u8>,
+    pub data: Vec<u8>,
 }
 
 impl Bank {
     fn new(bank_size: u16) -> Self {
         Self { data: vec![0; bank_size as usize] }
     }
+
+    /// Split the ROM image into a fixed number of banks (for the schemes with a fixed ROM size), padded with zeros.
+    pub fn split(source: &[u8], bank_size: u16, num_banks: usize) -> Vec<Bank> {
+        let expected = bank_size as usize * num_banks;
+        if source.len() != expected {
+            println!("Cartridge is {} bytes, expected {} bytes (it will be truncated/padded with zeros)", source.len(), expected);
+        }
+        (0..num_banks)
+            .map(|i| {
+                let mut bank = Bank::new(bank_size);
+                let start = (i * bank_size as usize).min(source.len());
+                let end = ((i + 1) * bank_size as usize).min(source.len());
+                bank.data[..end - start].copy_from_slice(&source[start..end]);
+                bank
+            })
+            .collect()
+    }
 }
 
 pub trait Cartridge {
-    fn load(&mut self) -> std::io::Result<()>;
+    // Load the banks from the ROM image.
+    fn load(&mut self, data: &[u8]);
 
     fn read(&mut self, address: u16) -> u8;
     fn write(&mut self, address: u16, data: u8);
+    // Read without triggering any bank switching.
+    fn peek(&self, address: u16) -> u8;
+
+    // Schemes that watch every CPU access (not just those with A12 set, ie stack or TIA accesses) return true, they're
+    // then given each access (after it's been decoded, with the data read/written) via 'snoop'.
+    fn snoops(&self) -> bool {
+        false
+    }
+    fn snoop(&mut self, address: u16, data: u8, write: bool) {}
+
+    // Schemes with their own timing (ie an oscillator) return true, they're then given the emulated clock (colour clock
+    // ticks) via 'clock' before each cartridge access.
+    fn clocked(&self) -> bool {
+        false
+    }
+    fn clock(&mut self, ticks: clocks::ClockType) {}
+
+    // The raw contents of each bank (for tools, ie disassembly).
+    fn bank_count(&self) -> usize;
+    fn bank_data(&self, bank: usize) -> Option<&[u8]>;
+    // The bank currently mapped at the address ('bank_count' if it isn't ROM, ie cartridge RAM).
+    fn bank_at(&self, address: u16) -> usize;
+
+    // Save states (bank selection and RAM), restore returns an error if the state doesn't match the cartridge.
+    fn save_state(&self, state: &mut state::StateWriter);
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()>;
 
     fn summary(&self);
 }
 
 pub struct GenericCartridge {
-    filename: String,
     pub num_banks: NumBanksType,
     cartridge_banks: Vec<Bank>,
 
@@ -64,9 +124,8 @@ pub struct GenericCartridge {
 }
 
 impl GenericCartridge {
-    pub fn new(filename: &str, max_banks: u8, current_bank: u8, bank_size: u16, hot_swap: u16, ram_size: u16) -> Self {
+    pub fn new(max_banks: u8, current_bank: u8, bank_size: u16, hot_swap: u16, ram_size: u16) -> Self {
         Self {
-            filename: filename.to_string(),
             cartridge_banks: Vec::new(),
             ram: vec![0; ram_size as usize],
             bank_size,
@@ -80,34 +139,9 @@ impl GenericCartridge {
         }
     }
 
-    pub fn load(&mut self) -> std::io::Result<()> {
-        let mut buffer = Vec::new();
-
-        #[cfg(not(target_os = "emscripten"))]
-        {
-            use std::fs::File;
-            use std::io::Read;
-
-            let f = File::open(&self.filename);
-            match f {
-                Ok(mut file) => {
-                    file.read_to_end(&mut buffer)?;
-                }
-                Err(e) => return Err(e),
-            }
-        }
-
-        #[cfg(target_os = "emscripten")]
-        {
-            JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
-                buffer = ref_cell_data.borrow().raw_cart_data.clone();
-            });
-        }
-
-        self.load_banks(&mut buffer);
+    pub fn load(&mut self, data: &[u8]) {
+        self.load_banks(&mut data.to_vec());
         self.summary();
-
-        Ok(())
     }
 
     fn load_banks(&mut self, source: &mut Vec<u8>) {
@@ -182,6 +216,17 @@ impl GenericCartridge {
         }
     }
 
+    fn peek(&self, address: u16) -> u8 {
+        let address = address & (self.bank_size - 1);
+        if (self.ram_size > 0) && (address < 2 * self.ram_size) && (address >= self.ram_size) {
+            self.ram[(address & self.ram_addr_mask) as usize]
+        } else if !self.cartridge_banks.is_empty() {
+            self.cartridge_banks[self.current_bank as usize].data[address as usize]
+        } else {
+            0
+        }
+    }
+
     fn write(&mut self, address: u16, data: u8) {
         let address = address & (self.bank_size - 1);
         if (self.ram_size > 0) && (address < self.ram_size) {
@@ -195,12 +240,11 @@ impl GenericCartridge {
 }
 
 impl Cartridge for GenericCartridge {
-    fn load(&mut self) -> std::io::Result<()> {
-        self.load()
+    fn load(&mut self, data: &[u8]) {
+        self.load(data);
     }
 
     fn summary(&self) {
-        println!("cartridge read: {}", self.filename);
         println!(" num banks: {}", self.num_banks);
         if !self.cartridge_banks.is_empty() {
             println!(" bank size = {}", self.cartridge_banks[0].data.len());
@@ -214,91 +258,93 @@ impl Cartridge for GenericCartridge {
     fn write(&mut self, address: u16, data: u8) {
         self.write(address, data);
     }
-}
 
-struct JavaScriptData {
-    pub raw_cart_data: Vec<u8>,
-    pub raw_cart_type: CartridgeType,
-}
-impl JavaScriptData {
-    pub fn new() -> Self {
-        Self {
-            raw_cart_data: Vec::new(),
-            raw_cart_type: CartridgeType::Default,
-        }
+    fn peek(&self, address: u16) -> u8 {
+        self.peek(address)
     }
-}
-
-use std::cell::RefCell;
 
-thread_local! {
-    static JAVASCRIPT_DATA_STORE: RefCell<JavaScriptData> = RefCell::new(JavaScriptData::new());
-}
-
-pub fn is_cart_ready() -> bool {
-    let mut is_ready = false;
-    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
-        is_ready = !ref_cell_data.borrow().raw_cart_data.is_empty();
-    });
-    is_ready
-}
+    fn bank_count(&self) -> usize {
+        self.cartridge_banks.len()
+    }
 
-pub fn get_cart_type() -> CartridgeType {
-    let mut cart_type = CartridgeType::Default;
-    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
-        cart_type = ref_cell_data.borrow().raw_cart_type;
-    });
-    cart_type
-}
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.cartridge_banks.get(bank).map(|bank| bank.data.as_slice())
+    }
 
-#[no_mangle]
-pub extern "C" fn display_data(raw_data_ptr: *const u8, raw_data_length: usize, cart_type_char_ptr: *const std::ffi::c_char) {
-    // TODO: Although it's possible there's another way (alternate arguments), I'll just use the unsafe option for now.
-    let v = unsafe { std::slice::from_raw_parts(raw_data_ptr, raw_data_length) };
-    let cart_type_string = unsafe { std::ffi::CStr::from_ptr(cart_type_char_ptr) }.to_str().unwrap();
-    println!("Called from javascript. Rom size: {}, Cartridge Type: {}", v.len(), cart_type_string);
+    fn bank_at(&self, address: u16) -> usize {
+        self.current_bank as usize
+    }
 
-    let cart_type = CartridgeType::from_str(cart_type_string).expect("Couldn't convert from string to CartType.");
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_u8(self.current_bank);
+        state.write_u8(self.bank_select);
+        state.write_bytes(&self.ram);
+    }
 
-    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
-        ref_cell_data.borrow_mut().raw_cart_data = v.to_vec();
-        ref_cell_data.borrow_mut().raw_cart_type = cart_type
-    });
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let current_bank = state.read_u8()?;
+        if current_bank >= self.num_banks.max(1) {
+            return Err(state::StateReader::invalid("Save state bank is outside the cartridge"));
+        }
+        self.current_bank = current_bank;
+        self.bank_select = state.read_u8()?;
+        state.read_bytes_into(&mut self.ram)
+    }
 }
 
-pub fn get_new_cartridge(filename: &String, cartridge_type: &CartridgeType) -> Box<GenericCartridge> {
+/// A cartridge from the ROM image (an empty cartridge if there's no data).
+pub fn new_cartridge(data: &[u8], cartridge_type: &CartridgeType) -> Box<dyn Cartridge> {
     const NO_RAM: u16 = 0x0000;
     const RAM_128_BYTES: u16 = 0x0080;
     const RAM_256_BYTES: u16 = 0x0100;
-    let mut new_cartridge = match cartridge_type {
-        // filename,  max_banks (4K banks), bank_size, hot_swap, ram_size
+    let mut new_cartridge: Box<dyn Cartridge> = match cartridge_type {
+        // max_banks (4K banks), bank_size, hot_swap, ram_size
         // 'hot_swap' values is the 'upper' value, generally, subsequent banks are selected via 'value - 1'.
         // TODO: Confirm initial/starting bank for each type.
-        CartridgeType::Default => Box::new(GenericCartridge::new(filename, 8, 1, 0x1000, 0xFF9, NO_RAM)),
-        CartridgeType::F4 => Box::new(GenericCartridge::new(filename, 8, 0, 0x1000, 0xFFB, NO_RAM)),
-        CartridgeType::F4SC => Box::new(GenericCartridge::new(filename, 8, 0, 0x1000, 0xFFB, RAM_128_BYTES)),
-
-        CartridgeType::F8 => Box::new(GenericCartridge::new(filename, 2, 1, 0x1000, 0xFF9, NO_RAM)),
-        CartridgeType::F8SC => Box::new(GenericCartridge::new(filename, 2, 1, 0x1000, 0xFF9, RAM_128_BYTES)),
-
-        CartridgeType::F6 => Box::new(GenericCartridge::new(filename, 4, 0, 0x1000, 0xFF9, NO_RAM)),
-        CartridgeType::F6SC => Box::new(GenericCartridge::new(filename, 4, 0, 0x1000, 0xFF9, RAM_128_BYTES)),
-
-        CartridgeType::Cbs => Box::new(GenericCartridge::new(filename, 3, 0, 0x1000, 0xFFA, RAM_256_BYTES)),
-        CartridgeType::Super => Box::new(GenericCartridge::new(filename, 4, 0, 0x1000, 0xFF9, NO_RAM)),
+        CartridgeType::Default => Box::new(GenericCartridge::new(8, 1, 0x1000, 0xFF9, NO_RAM)),
+        CartridgeType::F4 => Box::new(GenericCartridge::new(8, 0, 0x1000, 0xFFB, NO_RAM)),
+        CartridgeType::F4SC => Box::new(GenericCartridge::new(8, 0, 0x1000, 0xFFB, RAM_128_BYTES)),
+
+        CartridgeType::F8 => Box::new(GenericCartridge::new(2, 1, 0x1000, 0xFF9, NO_RAM)),
+        CartridgeType::F8SC => Box::new(GenericCartridge::new(2, 1, 0x1000, 0xFF9, RAM_128_BYTES)),
+
+        CartridgeType::F6 => Box::new(GenericCartridge::new(4, 0, 0x1000, 0xFF9, NO_RAM)),
+        CartridgeType::F6SC => Box::new(GenericCartridge::new(4, 0, 0x1000, 0xFF9, RAM_128_BYTES)),
+
+        CartridgeType::Cbs => Box::new(GenericCartridge::new(3, 0, 0x1000, 0xFFA, RAM_256_BYTES)),
+        CartridgeType::Super => Box::new(GenericCartridge::new(4, 0, 0x1000, 0xFF9, NO_RAM)),
+
+        // Sub-4K slices, see the modules.
+        CartridgeType::E0 => Box::new(cartridge_e0::E0Cartridge::new()),
+        CartridgeType::E7 => Box::new(cartridge_e7::E7Cartridge::new()),
+        // Switched by watching the bus (see 'snoop').
+        CartridgeType::FE => Box::new(cartridge_fe::FECartridge::new()),
+        CartridgeType::Tigervision3F => Box::new(cartridge_3f::Cartridge3F::new(cartridge_3f::Scheme::Tigervision3F)),
+        CartridgeType::Tigervision3E => Box::new(cartridge_3f::Cartridge3F::new(cartridge_3f::Scheme::Tigervision3E)),
+        CartridgeType::Tigervision3EPlus => Box::new(cartridge_3f::Cartridge3F::new(cartridge_3f::Scheme::Tigervision3EPlus)),
+        // A coprocessor, clocked by the emulated clock (see 'clock').
+        CartridgeType::DPC => Box::new(cartridge_dpc::DPCCartridge::new()),
     };
 
-    // Load the cartridge.
-    match new_cartridge.load() {
-        Ok(()) => {
-            println!("Ok");
+    new_cartridge.load(data);
+    new_cartridge
+}
+
+/// Read the ROM image from the file.
+pub fn read_cartridge(filename: &str) -> Vec<u8> {
+    match std::fs::read(filename) {
+        Ok(data) => {
+            println!("cartridge read: {}", filename);
+            data
         }
         Err(e) => {
             panic!("Error loading cartridge \"{}\".\n {}", filename, e);
         }
     }
+}
 
-    new_cartridge
+pub fn get_new_cartridge(filename: &str, cartridge_type: &CartridgeType) -> Box<dyn Cartridge> {
+    new_cartridge(&read_cartridge(filename), cartridge_type)
 }
 
 #[cfg(test)]
@@ -306,7 +352,7 @@ mod tests {
     use crate::atari2600::memory::cartridge::GenericCartridge;
     #[test]
     fn test_simple_generic_cartridge() {
-        let mut sample_cartridge = GenericCartridge::new("dummy", 3, 0, 0x200, 0xF9, 40);
+        let mut sample_cartridge = GenericCartridge::new(3, 0, 0x200, 0xF9, 40);
         assert_eq!(sample_cartridge.cartridge_banks.len(), 0);
 
         // A slice implements 'Read'
@@ -0,0 +1,309 @@
+//! Tigervision '3F' bank switching (Miner 2049er, Polaris) and the homebrew '3E'/'3E+' extensions with RAM.
+//!
+//! The banks are selected by writes to the TIA addresses (which still reach the TIA), so the cartridge watches the
+//! bus (see 'Cartridge::snoop'):
+//!   3F:  2K ROM banks (up to 512K).  0x1000-0x17FF is switched by writing the bank to any of 0x00-0x3F, 0x1800-0x1FFF
+//!        is fixed to the last bank.
+//!   3E:  3F, writing to 0x3F only, and 32K of RAM as 1K banks.  Writing the RAM bank to 0x3E maps it at 0x1000 (read
+//!        at 0x1000-0x13FF, written at 0x1400-0x17FF).
+//!   3E+: four 1K segments, 1K ROM banks (up to 64K) and 32K of RAM as 512 byte banks (read in the lower half of the
+//!        segment, written in the upper half).  The written data is the segment (bits 6-7) and bank (bits 0-5), to 0x3F
+//!        for ROM and 0x3E for RAM.  Power on maps ROM bank 0 into every segment.
+//! Bank numbers beyond the cartridge wrap around.  Reading a RAM write port returns 0.
+
+use super::super::state;
+use super::cartridge;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Scheme {
+    Tigervision3F,
+    Tigervision3E,
+    Tigervision3EPlus,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Segment {
+    Rom(u8),
+    Ram(u8),
+}
+
+pub struct Cartridge3F {
+    scheme: Scheme,
+    banks: Vec<cartridge::Bank>,
+    ram: Vec<u8>,
+    segments: Vec<Segment>,
+}
+
+impl Cartridge3F {
+    const WINDOW_SIZE: u16 = 0x1000;
+    const HOTSPOT_RAM: u16 = 0x3E;
+    const HOTSPOT_ROM: u16 = 0x3F;
+    // The 6507 has 13 address lines.
+    const ADDRESS_MASK: u16 = 0x1FFF;
+    const RAM_SIZE: usize = 0x8000;
+    const SEGMENT_SHIFT: u8 = 6;
+    const BANK_MASK: u8 = 0x3F;
+
+    pub fn new(scheme: Scheme) -> Self {
+        Self {
+            scheme,
+            banks: Vec::new(),
+            ram: vec![0; if Scheme::Tigervision3F == scheme { 0 } else { Cartridge3F::RAM_SIZE }],
+            segments: Vec::new(),
+        }
+    }
+
+    fn segment_size(&self) -> u16 {
+        match self.scheme {
+            Scheme::Tigervision3EPlus => 0x0400,
+            _ => 0x0800,
+        }
+    }
+
+    // A RAM bank is half of a segment (read and write ports).
+    fn ram_bank_size(&self) -> u16 {
+        self.segment_size() / 2
+    }
+
+    fn power_on_segments(&self) -> Vec<Segment> {
+        match self.scheme {
+            Scheme::Tigervision3EPlus => vec![Segment::Rom(0); (Cartridge3F::WINDOW_SIZE / self.segment_size()) as usize],
+            _ => vec![Segment::Rom(0), Segment::Rom(self.banks.len().saturating_sub(1) as u8)],
+        }
+    }
+
+    fn rom(&self, bank: u8) -> Segment {
+        Segment::Rom((bank as usize % self.banks.len().max(1)) as u8)
+    }
+
+    fn ram(&self, bank: u8) -> Segment {
+        Segment::Ram((bank as usize % (self.ram.len() / self.ram_bank_size() as usize)) as u8)
+    }
+
+    /// The segment (index), and the offset within it.
+    fn segment(&self, address: u16) -> (usize, u16) {
+        let address = address & (Cartridge3F::WINDOW_SIZE - 1);
+        ((address / self.segment_size()) as usize, address % self.segment_size())
+    }
+
+    fn ram_index(&self, bank: u8, offset: u16) -> usize {
+        bank as usize * self.ram_bank_size() as usize + (offset % self.ram_bank_size()) as usize
+    }
+
+    fn hotspot(&mut self, address: u16, data: u8) {
+        match (self.scheme, address) {
+            (Scheme::Tigervision3F, ..=Cartridge3F::HOTSPOT_ROM) => self.segments[0] = self.rom(data),
+            (Scheme::Tigervision3E, Cartridge3F::HOTSPOT_ROM) => self.segments[0] = self.rom(data),
+            (Scheme::Tigervision3E, Cartridge3F::HOTSPOT_RAM) => self.segments[0] = self.ram(data),
+            (Scheme::Tigervision3EPlus, Cartridge3F::HOTSPOT_ROM) => self.segments[(data >> Cartridge3F::SEGMENT_SHIFT) as usize] = self.rom(data & Cartridge3F::BANK_MASK),
+            (Scheme::Tigervision3EPlus, Cartridge3F::HOTSPOT_RAM) => self.segments[(data >> Cartridge3F::SEGMENT_SHIFT) as usize] = self.ram(data & Cartridge3F::BANK_MASK),
+            _ => {}
+        }
+    }
+}
+
+impl cartridge::Cartridge for Cartridge3F {
+    fn load(&mut self, data: &[u8]) {
+        let bank_size = self.segment_size();
+        self.banks = cartridge::Bank::split(data, bank_size, data.len().div_ceil(bank_size as usize).max(1));
+        self.ram.fill(0);
+        self.segments = self.power_on_segments();
+        self.summary();
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        let (segment, offset) = self.segment(address);
+        if let Some(Segment::Ram(bank)) = self.segments.get(segment) {
+            if offset >= self.ram_bank_size() {
+                let index = self.ram_index(*bank, offset);
+                self.ram[index] = data;
+            }
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        let (segment, offset) = self.segment(address);
+        match self.segments.get(segment) {
+            Some(Segment::Rom(bank)) => self.banks[*bank as usize].data[offset as usize],
+            Some(Segment::Ram(bank)) if offset < self.ram_bank_size() => self.ram[self.ram_index(*bank, offset)],
+            _ => 0,
+        }
+    }
+
+    fn snoops(&self) -> bool {
+        true
+    }
+
+    fn snoop(&mut self, address: u16, data: u8, write: bool) {
+        if write {
+            self.hotspot(address & Cartridge3F::ADDRESS_MASK, data);
+        }
+    }
+
+    fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|bank| bank.data.as_slice())
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        match self.segments.get(self.segment(address).0) {
+            Some(Segment::Rom(bank)) => *bank as usize,
+            _ => self.banks.len(),
+        }
+    }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        for segment in &self.segments {
+            match segment {
+                Segment::Rom(bank) => {
+                    state.write_bool(false);
+                    state.write_u8(*bank);
+                }
+                Segment::Ram(bank) => {
+                    state.write_bool(true);
+                    state.write_u8(*bank);
+                }
+            }
+        }
+        state.write_bytes(&self.ram);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        // No wrapping (unlike the hotspots), a bank outside the cartridge (or RAM on 3F) is an invalid state.
+        let ram_banks = self.ram.len() / self.ram_bank_size() as usize;
+        let mut segments = Vec::new();
+        for _ in 0..self.segments.len() {
+            let segment = match (state.read_bool()?, state.read_u8()?) {
+                (true, bank) if (bank as usize) < ram_banks => Segment::Ram(bank),
+                (false, bank) if (bank as usize) < self.banks.len() => Segment::Rom(bank),
+                _ => return Err(state::StateReader::invalid("Save state bank is outside the cartridge")),
+            };
+            segments.push(segment);
+        }
+        state.read_bytes_into(&mut self.ram)?;
+        self.segments = segments;
+        Ok(())
+    }
+
+    fn summary(&self) {
+        println!(" {:?}: {} banks of {} bytes, {} bytes of RAM", self.scheme, self.banks.len(), self.segment_size(), self.ram.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::machine;
+    use crate::atari2600::memory::cartridge;
+    use crate::atari2600::memory::cartridge::Cartridge;
+    use crate::atari2600::memory::cartridge_3f::Cartridge3F;
+    use crate::atari2600::memory::cartridge_3f::Scheme;
+    use crate::atari2600::state;
+
+    #[test]
+    fn test_3f_write_switches_and_reaches_tia() {
+        // 8K, four 2K banks.  The fixed bank (0xF800): LDA #$01, STA $3F, LDA $F000, STA $80, then LDA #$1E and
+        // STA COLUBK (a TIA register, in the 3F hotspot range), LDA $F000, STA $81, then a frame loop (VSYNC for 3 lines,
+        // VBLANK off, 200 lines of WSYNC), which also switches banks.
+        let mut rom = vec![0; 0x2000];
+        for bank in 0..4 {
+            rom[bank * 0x800] = 0x10 + bank as u8;
+        }
+        let program = [
+            0xA9, 0x01, 0x85, 0x3F, 0xAD, 0x00, 0xF0, 0x85, 0x80, 0xA9, 0x1E, 0x85, 0x09, 0xAD, 0x00, 0xF0, 0x85, 0x81, 0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xA2, 0xC8, 0x85, 0x02, 0xCA,
+            0xD0, 0xFB, 0x4C, 0x12, 0xF8,
+        ];
+        rom[0x1800..0x1800 + program.len()].copy_from_slice(&program);
+        rom[0x1FFC..0x1FFE].copy_from_slice(&[0x00, 0xF8]);
+
+        let mut machine = machine::Machine::new(machine::MachineConfig::default());
+        machine.insert_cartridge(&rom, &cartridge::CartridgeType::Tigervision3F);
+        assert_eq!(machine.ram()[0], 0);
+        for _ in 0..8 {
+            machine.step();
+        }
+        assert_eq!(machine.ram()[0..2], [0x11, 0x12]);
+
+        // The TIA still got the write (the background, during the next frame).
+        machine.run_frame();
+        machine.run_frame();
+        let pixel = 100 * machine::Machine::FRAME_WIDTH as usize + 80;
+        assert_eq!(machine.frame_palette_indices()[pixel], 0x1E >> 1);
+    }
+
+    #[test]
+    fn test_3e_3e_plus_ram() {
+        // Each 1K is filled with its number (plus 0x10).
+        let data: Vec<u8> = (0..8).flat_map(|kilobyte| vec![0x10 + kilobyte; 0x400]).collect();
+
+        // 3E, only 0x3F/0x3E switch.
+        let mut cartridge = Cartridge3F::new(Scheme::Tigervision3E);
+        cartridge.load(&data);
+        assert_eq!([0x1000, 0x1800].map(|address| cartridge.read(address)), [0x10, 0x16]);
+        cartridge.snoop(0x09, 3, true);
+        cartridge.snoop(0x3F, 3, false);
+        assert_eq!(cartridge.read(0x1000), 0x10);
+        cartridge.snoop(0x3F, 2, true);
+        assert_eq!(cartridge.read(0x1000), 0x14);
+        cartridge.snoop(0x3E, 31, true);
+        cartridge.write(0x1400, 0xA5);
+        cartridge.write(0x17FF, 0x5A);
+        assert_eq!([0x1000, 0x13FF, 0x1400, 0x1800].map(|address| cartridge.read(address)), [0xA5, 0x5A, 0x00, 0x16]);
+        assert_eq!(cartridge.bank_at(0x1000), cartridge.bank_count());
+        cartridge.snoop(0x3E, 0, true);
+        assert_eq!(cartridge.read(0x1000), 0x00);
+        cartridge.snoop(0x3E, 32 + 31, true);
+        assert_eq!(cartridge.read(0x1000), 0xA5);
+
+        // 3E+, segment (bits 6-7) and bank.
+        let mut cartridge = Cartridge3F::new(Scheme::Tigervision3EPlus);
+        cartridge.load(&data);
+        let segments = |cartridge: &mut Cartridge3F| [0x1000, 0x1400, 0x1800, 0x1C00].map(|address| cartridge.read(address));
+        assert_eq!(segments(&mut cartridge), [0x10, 0x10, 0x10, 0x10]);
+        cartridge.snoop(0x3F, 0xC7, true);
+        cartridge.snoop(0x3F, 0x45, true);
+        cartridge.snoop(0x3E, 0x80 | 63, true);
+        cartridge.write(0x1A00, 0xC3);
+        cartridge.write(0x1BFF, 0x3C);
+        assert_eq!(segments(&mut cartridge), [0x10, 0x15, 0xC3, 0x17]);
+        assert_eq!(cartridge.read(0x19FF), 0x3C);
+        assert_eq!(cartridge.read(0x1A00), 0x00);
+
+        // Another segment, the same RAM bank.
+        cartridge.snoop(0x3E, 63, true);
+        assert_eq!(segments(&mut cartridge), [0xC3, 0x15, 0xC3, 0x17]);
+
+        let mut state = state::StateWriter::new();
+        cartridge.save_state(&mut state);
+        let saved = state.into_bytes();
+        cartridge.snoop(0x3F, 0x00, true);
+        cartridge.write(0x1200, 0);
+        cartridge.restore_state(&mut state::StateReader::new(&saved)).unwrap();
+        assert_eq!(segments(&mut cartridge), [0xC3, 0x15, 0xC3, 0x17]);
+
+        // Banks outside the cartridge are rejected, leaving it unchanged.
+        let mut out_of_range = saved.clone();
+        out_of_range[3] = 8;
+        assert!(cartridge.restore_state(&mut state::StateReader::new(&out_of_range)).is_err());
+        let mut out_of_range = saved.clone();
+        out_of_range[5] = 64;
+        assert!(cartridge.restore_state(&mut state::StateReader::new(&out_of_range)).is_err());
+        assert_eq!(segments(&mut cartridge), [0xC3, 0x15, 0xC3, 0x17]);
+
+        // 3F has no RAM.
+        let mut cartridge = Cartridge3F::new(Scheme::Tigervision3F);
+        cartridge.load(&data);
+        let mut state = state::StateWriter::new();
+        cartridge.save_state(&mut state);
+        let mut saved = state.into_bytes();
+        saved[0] = 1;
+        assert!(cartridge.restore_state(&mut state::StateReader::new(&saved)).is_err());
+        assert_eq!([0x1000, 0x1800].map(|address| cartridge.read(address)), [0x10, 0x16]);
+    }
+}
@@ -0,0 +1,355 @@
+//! Activision 'DPC' (Display Processor Chip) cartridge, as used by Pitfall II.
+//!
+//! 8K of program ROM as two 4K banks (selected by accessing 0x1FF8/0x1FF9, as F8) and 2K of display data (graphics and
+//! music), only readable via the DPC's eight data fetchers.  The registers are at 0x1000-0x107F (reads at
+//! 0x1000-0x103F, writes at 0x1040-0x107F), 'function' is bits 3-5 and 'fetcher' bits 0-2 of the address:
+//!   Read 0:  random number (fetchers 0-3), music amplitude (fetchers 4-7)
+//!   Read 1:  display data, at 2047 minus the fetcher's counter
+//!   Read 2:  display data, ANDed with the fetcher's flag
+//!   Read 7:  the fetcher's flag
+//!   Write 0: top (and clear the flag), 1: bottom, 2: counter low, 3: counter high (and music mode, fetchers 5-7)
+//!   Write 6: reset the random number generator
+//! Reading a fetcher's data decrements its counter, its flag is set when the counter reaches the top and cleared when
+//! it reaches the bottom.  The music fetchers (5-7, in music mode) instead count down from the top, clocked by the DPC's
+//! oscillator (tied to the emulated clock), their flags being a square wave.  The amplitude is the three flags mixed,
+//! the program writes it to AUDV0 (with AUDC0 at 0, so the TIA outputs the volume directly).
+
+use super::super::clocks;
+use super::super::cpu::core;
+use super::super::state;
+use super::cartridge;
+
+pub struct DPCCartridge {
+    banks: Vec<cartridge::Bank>,
+    display: Vec<u8>,
+    current_bank: u8,
+
+    tops: [u8; DPCCartridge::NUM_FETCHERS],
+    bottoms: [u8; DPCCartridge::NUM_FETCHERS],
+    counters: [u16; DPCCartridge::NUM_FETCHERS],
+    flags: [u8; DPCCartridge::NUM_FETCHERS],
+    music_mode: [bool; DPCCartridge::NUM_MUSIC_FETCHERS],
+    random: u8,
+
+    // The emulated clock (at the current access), and when the music fetchers were last updated.
+    ticks: clocks::ClockType,
+    music_ticks: clocks::ClockType,
+}
+
+impl DPCCartridge {
+    const BANK_SIZE: u16 = 0x1000;
+    const NUM_BANKS: usize = 2;
+    const DISPLAY_SIZE: usize = 0x0800;
+    const NUM_FETCHERS: usize = 8;
+    const NUM_MUSIC_FETCHERS: usize = 3;
+    const FIRST_MUSIC_FETCHER: usize = 5;
+    const NUM_RANDOM_FETCHERS: usize = 4;
+    const COUNTER_MASK: u16 = 0x07FF;
+
+    const READ_END: u16 = 0x0040;
+    const WRITE_END: u16 = 0x0080;
+    const HOTSPOT_START: u16 = 0x0FF8;
+    const HOTSPOT_END: u16 = 0x0FF9;
+    // As Stella.
+    const POWER_ON_BANK: u8 = 1;
+
+    // The music oscillator (Hz), as Stella.
+    const OSC_HZ: u64 = 20000;
+    // The amplitude for each combination of the music fetchers' flags.
+    const MUSIC_AMPLITUDES: [u8; 8] = [0x00, 0x04, 0x05, 0x09, 0x06, 0x0A, 0x0B, 0x0F];
+    // Feedback of the random number generator, indexed by bits 7, 5, 4 and 3 (XNOR).
+    const RANDOM_FEEDBACK: [u8; 16] = [1, 0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 1];
+
+    pub fn new() -> Self {
+        Self {
+            banks: Vec::new(),
+            display: vec![0; DPCCartridge::DISPLAY_SIZE],
+            current_bank: DPCCartridge::POWER_ON_BANK,
+            tops: [0; DPCCartridge::NUM_FETCHERS],
+            bottoms: [0; DPCCartridge::NUM_FETCHERS],
+            counters: [0; DPCCartridge::NUM_FETCHERS],
+            flags: [0; DPCCartridge::NUM_FETCHERS],
+            music_mode: [false; DPCCartridge::NUM_MUSIC_FETCHERS],
+            random: 1,
+            ticks: 0,
+            music_ticks: 0,
+        }
+    }
+
+    fn decode(address: u16) -> (usize, u16) {
+        ((address & 0x07) as usize, (address >> 3) & 0x07)
+    }
+
+    fn hotspot(&mut self, address: u16) {
+        if (DPCCartridge::HOTSPOT_START..=DPCCartridge::HOTSPOT_END).contains(&address) {
+            self.current_bank = (address - DPCCartridge::HOTSPOT_START) as u8;
+        }
+    }
+
+    fn clock_random(&mut self) {
+        let taps = ((self.random >> 3) & 0x07) | if 0 != self.random & 0x80 { 0x08 } else { 0x00 };
+        self.random = (self.random << 1) | DPCCartridge::RANDOM_FEEDBACK[taps as usize];
+    }
+
+    fn in_music_mode(&self, fetcher: usize) -> bool {
+        fetcher >= DPCCartridge::FIRST_MUSIC_FETCHER && self.music_mode[fetcher - DPCCartridge::FIRST_MUSIC_FETCHER]
+    }
+
+    /// The fetcher's flag, after comparing its counter with the top/bottom.
+    fn flag(&self, fetcher: usize) -> u8 {
+        let low = (self.counters[fetcher] & 0xFF) as u8;
+        if low == self.tops[fetcher] {
+            0xFF
+        } else if low == self.bottoms[fetcher] {
+            0x00
+        } else {
+            self.flags[fetcher]
+        }
+    }
+
+    fn display_data(&self, fetcher: usize) -> u8 {
+        self.display[DPCCartridge::DISPLAY_SIZE - 1 - self.counters[fetcher] as usize]
+    }
+
+    /// Count down the music fetchers by the oscillator clocks since the last update (from the absolute times, so the
+    /// rounding doesn't accumulate).
+    fn update_music(&mut self) {
+        let osc_time = |ticks: clocks::ClockType| ticks * DPCCartridge::OSC_HZ / core::Constants::CLOCK_HZ as u64;
+        let osc_clocks = osc_time(self.ticks).saturating_sub(osc_time(self.music_ticks));
+        self.music_ticks = self.ticks;
+        if 0 == osc_clocks {
+            return;
+        }
+
+        for fetcher in DPCCartridge::FIRST_MUSIC_FETCHER..DPCCartridge::NUM_FETCHERS {
+            // Only the fetchers in music mode are clocked (as Stella does).
+            if !self.in_music_mode(fetcher) {
+                continue;
+            }
+
+            let top = self.tops[fetcher] as u64;
+            let low = if 0 == top {
+                0
+            } else {
+                // Counts down from the top to 0, then reloads the top.
+                let low = (self.counters[fetcher] & 0xFF) as u64;
+                (low + top + 1 - osc_clocks % (top + 1)) % (top + 1)
+            } as u8;
+
+            if low <= self.bottoms[fetcher] {
+                self.flags[fetcher] = 0x00;
+            } else if low <= self.tops[fetcher] {
+                self.flags[fetcher] = 0xFF;
+            }
+            self.counters[fetcher] = (self.counters[fetcher] & 0x0700) | low as u16;
+        }
+    }
+
+    fn music_amplitude(&self) -> u8 {
+        let index = (0..DPCCartridge::NUM_MUSIC_FETCHERS).filter(|voice| self.music_mode[*voice] && 0 != self.flags[DPCCartridge::FIRST_MUSIC_FETCHER + voice]).fold(0, |index, voice| index | (1 << voice));
+        DPCCartridge::MUSIC_AMPLITUDES[index]
+    }
+
+    fn read_register(&self, address: u16) -> u8 {
+        let (fetcher, function) = DPCCartridge::decode(address);
+        match function {
+            0x0 if fetcher < DPCCartridge::NUM_RANDOM_FETCHERS => self.random,
+            0x0 => self.music_amplitude(),
+            0x1 => self.display_data(fetcher),
+            0x2 => self.display_data(fetcher) & self.flag(fetcher),
+            0x7 => self.flag(fetcher),
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8) {
+        let (fetcher, function) = DPCCartridge::decode(address);
+        match function {
+            0x0 => {
+                self.tops[fetcher] = data;
+                self.flags[fetcher] = 0x00;
+            }
+            0x1 => self.bottoms[fetcher] = data,
+            0x2 => {
+                // Music fetchers reload from the top.
+                let low = if self.in_music_mode(fetcher) { self.tops[fetcher] } else { data };
+                self.counters[fetcher] = (self.counters[fetcher] & 0x0700) | low as u16;
+            }
+            0x3 => {
+                self.counters[fetcher] = (((data & 0x07) as u16) << 8) | (self.counters[fetcher] & 0x00FF);
+                if fetcher >= DPCCartridge::FIRST_MUSIC_FETCHER {
+                    self.music_mode[fetcher - DPCCartridge::FIRST_MUSIC_FETCHER] = 0 != data & 0x10;
+                }
+            }
+            0x6 => self.random = 1,
+            _ => {}
+        }
+    }
+}
+
+impl cartridge::Cartridge for DPCCartridge {
+    fn load(&mut self, data: &[u8]) {
+        let program_size = DPCCartridge::BANK_SIZE as usize * DPCCartridge::NUM_BANKS;
+        let banks = cartridge::Bank::split(&data[..data.len().min(program_size)], DPCCartridge::BANK_SIZE, DPCCartridge::NUM_BANKS);
+        // The display data follows the program (some images have extra bytes after it).
+        let mut display = data[data.len().min(program_size)..data.len().min(program_size + DPCCartridge::DISPLAY_SIZE)].to_vec();
+        display.resize(DPCCartridge::DISPLAY_SIZE, 0);
+        *self = Self { banks, display, ..Self::new() };
+        self.summary();
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        let address = address & 0x0FFF;
+        if address >= DPCCartridge::READ_END {
+            self.hotspot(address);
+            return self.peek(address);
+        }
+
+        self.clock_random();
+        let (fetcher, function) = DPCCartridge::decode(address);
+        self.flags[fetcher] = self.flag(fetcher);
+        if 0 == function && fetcher >= DPCCartridge::NUM_RANDOM_FETCHERS {
+            self.update_music();
+        }
+        let data = self.read_register(address);
+
+        if !self.in_music_mode(fetcher) {
+            self.counters[fetcher] = self.counters[fetcher].wrapping_sub(1) & DPCCartridge::COUNTER_MASK;
+        }
+        data
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        let address = address & 0x0FFF;
+        if (DPCCartridge::READ_END..DPCCartridge::WRITE_END).contains(&address) {
+            self.clock_random();
+            self.write_register(address, data);
+        } else {
+            self.hotspot(address);
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        let address = address & 0x0FFF;
+        if address < DPCCartridge::READ_END {
+            self.read_register(address)
+        } else {
+            self.banks.get(self.current_bank as usize).map_or(0, |bank| bank.data[address as usize])
+        }
+    }
+
+    fn clocked(&self) -> bool {
+        true
+    }
+
+    fn clock(&mut self, ticks: clocks::ClockType) {
+        self.ticks = ticks;
+    }
+
+    fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|bank| bank.data.as_slice())
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        self.current_bank as usize
+    }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_u8(self.current_bank);
+        state.write_bytes(&self.tops);
+        state.write_bytes(&self.bottoms);
+        for counter in &self.counters {
+            state.write_u16(*counter);
+        }
+        state.write_bytes(&self.flags);
+        state.write_bools(&self.music_mode);
+        state.write_u8(self.random);
+        state.write_u64(self.music_ticks);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let current_bank = state.read_u8()?;
+        if current_bank as usize >= DPCCartridge::NUM_BANKS {
+            return Err(state::StateReader::invalid("Save state bank is outside the cartridge"));
+        }
+        self.current_bank = current_bank;
+        state.read_bytes_into(&mut self.tops)?;
+        state.read_bytes_into(&mut self.bottoms)?;
+        for counter in self.counters.iter_mut() {
+            *counter = state.read_u16()? & DPCCartridge::COUNTER_MASK;
+        }
+        state.read_bytes_into(&mut self.flags)?;
+        state.read_bools_into(&mut self.music_mode)?;
+        self.random = state.read_u8()?;
+        self.music_ticks = state.read_u64()?;
+        Ok(())
+    }
+
+    fn summary(&self) {
+        println!(" DPC: {} banks of {} bytes, {} bytes of display data", self.banks.len(), DPCCartridge::BANK_SIZE, self.display.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::cpu::core;
+    use crate::atari2600::memory::cartridge::Cartridge;
+    use crate::atari2600::memory::cartridge_dpc::DPCCartridge;
+    use crate::atari2600::state;
+
+    #[test]
+    fn test_dpc_fetchers_and_music() {
+        // Program banks filled with their number, the display data with its offset.
+        let mut data = vec![0x10; 0x1000];
+        data.extend(vec![0x11; 0x1000]);
+        data.extend((0..0x800).map(|offset| offset as u8));
+        let mut cartridge = DPCCartridge::new();
+        cartridge.load(&data);
+
+        assert_eq!(cartridge.read(0x1100), 0x11);
+        cartridge.read(0x1FF8);
+        assert_eq!(cartridge.read(0x1100), 0x10);
+
+        // Fetcher 2: counter 0x0103, top 0x02, bottom 0x00.  Reads count down through the display data (backwards).
+        for (address, data) in [(0x1042, 0x02), (0x104A, 0x00), (0x1052, 0x03), (0x105A, 0x01)] {
+            cartridge.write(address, data);
+        }
+        assert_eq!(cartridge.peek(0x100A), (0x7FF - 0x103) as u8);
+        assert_eq!([0x100A, 0x100A].map(|address| cartridge.read(address)), [(0x7FF - 0x103) as u8, (0x7FF - 0x102) as u8]);
+        // The flag is set at the top and cleared at the bottom, masking the data.
+        assert_eq!([0x1012, 0x103A, 0x1012].map(|address| cartridge.read(address)), [(0x7FF - 0x101) as u8, 0x00, 0x00]);
+
+        // The random number generator (reset to 1), reading it clocks it.
+        cartridge.write(0x1070, 0);
+        assert_eq!([0x1000, 0x1001, 0x1003].map(|address| cartridge.read(address)), [0x03, 0x07, 0x0F]);
+
+        // Music fetcher 5: top 3, bottom 1, in music mode, a square wave at a quarter of the oscillator (the flag is set
+        // above the bottom).  Fetcher 6 (top 3, counter 2) isn't in music mode, so isn't clocked.
+        for (address, data) in [(0x1045, 0x03), (0x104D, 0x01), (0x105D, 0x10), (0x1055, 0x00), (0x1046, 0x03), (0x1056, 0x02)] {
+            cartridge.write(address, data);
+        }
+        let osc_ticks = |clocks: u64| (clocks * core::Constants::CLOCK_HZ as u64).div_ceil(DPCCartridge::OSC_HZ);
+        let amplitudes: Vec<u8> = (0..8)
+            .map(|clocks| {
+                cartridge.clock(osc_ticks(clocks));
+                cartridge.read(0x1005)
+            })
+            .collect();
+        assert_eq!(amplitudes, [0x04, 0x04, 0x00, 0x00, 0x04, 0x04, 0x00, 0x00]);
+        assert_eq!(cartridge.peek(0x100E), (0x7FF - 0x002) as u8);
+
+        let mut state = state::StateWriter::new();
+        cartridge.save_state(&mut state);
+        let saved = state.into_bytes();
+        cartridge.write(0x105D, 0x00);
+        cartridge.read(0x1FF9);
+        cartridge.restore_state(&mut state::StateReader::new(&saved)).unwrap();
+        assert_eq!(cartridge.peek(0x1100), 0x10);
+        cartridge.clock(osc_ticks(8));
+        assert_eq!(cartridge.read(0x1005), 0x04);
+    }
+}
@@ -0,0 +1,143 @@
+//! Parker Brothers 'E0' bank switching (Frogger II, Montezuma's Revenge, Super Cobra, Popeye).
+//!
+//! 8K of ROM as eight 1K banks, with the 4K cartridge window split into four 1K slices.  The first three slices are
+//! switched independently by accessing (reading or writing) a hotspot:
+//!   0x1FE0-0x1FE7: slice 0 (0x1000-0x13FF) to bank 0-7
+//!   0x1FE8-0x1FEF: slice 1 (0x1400-0x17FF) to bank 0-7
+//!   0x1FF0-0x1FF7: slice 2 (0x1800-0x1BFF) to bank 0-7
+//! The last slice (0x1C00-0x1FFF, with the hotspots and vectors) is fixed to the last bank.
+
+use super::super::state;
+use super::cartridge;
+
+pub struct E0Cartridge {
+    banks: Vec<cartridge::Bank>,
+    // The bank in each slice.
+    slices: [u8; E0Cartridge::NUM_SLICES],
+}
+
+impl E0Cartridge {
+    const BANK_SIZE: u16 = 0x0400;
+    const NUM_BANKS: usize = 8;
+    const NUM_SLICES: usize = 4;
+    const SWITCHED_SLICES: usize = 3;
+    const HOTSPOT_START: u16 = 0x0FE0;
+    const HOTSPOT_END: u16 = 0x0FF7;
+    // As Stella, the fixed slice is the last bank.
+    const POWER_ON_SLICES: [u8; E0Cartridge::NUM_SLICES] = [4, 5, 6, 7];
+
+    pub fn new() -> Self {
+        Self {
+            banks: Vec::new(),
+            slices: E0Cartridge::POWER_ON_SLICES,
+        }
+    }
+
+    fn slice(address: u16) -> usize {
+        ((address & 0x0FFF) / E0Cartridge::BANK_SIZE) as usize
+    }
+
+    fn hotspot(&mut self, address: u16) {
+        let address = address & 0x0FFF;
+        if (E0Cartridge::HOTSPOT_START..=E0Cartridge::HOTSPOT_END).contains(&address) {
+            let index = address - E0Cartridge::HOTSPOT_START;
+            self.slices[(index / 8) as usize] = (index % 8) as u8;
+        }
+    }
+}
+
+impl cartridge::Cartridge for E0Cartridge {
+    fn load(&mut self, data: &[u8]) {
+        self.banks = cartridge::Bank::split(data, E0Cartridge::BANK_SIZE, E0Cartridge::NUM_BANKS);
+        self.slices = E0Cartridge::POWER_ON_SLICES;
+        self.summary();
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.hotspot(address);
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.hotspot(address);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.banks.get(self.bank_at(address)).map_or(0, |bank| bank.data[(address & (E0Cartridge::BANK_SIZE - 1)) as usize])
+    }
+
+    fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|bank| bank.data.as_slice())
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        self.slices[E0Cartridge::slice(address)] as usize
+    }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_bytes(&self.slices[..E0Cartridge::SWITCHED_SLICES]);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let mut slices = [0; E0Cartridge::SWITCHED_SLICES];
+        state.read_bytes_into(&mut slices)?;
+        if slices.iter().any(|bank| *bank as usize >= E0Cartridge::NUM_BANKS) {
+            return Err(state::StateReader::invalid("Save state bank is outside the cartridge"));
+        }
+        self.slices[..E0Cartridge::SWITCHED_SLICES].copy_from_slice(&slices);
+        Ok(())
+    }
+
+    fn summary(&self) {
+        println!(" E0: {} banks of {} bytes", self.banks.len(), E0Cartridge::BANK_SIZE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::memory::cartridge::Cartridge;
+    use crate::atari2600::memory::cartridge_e0::E0Cartridge;
+    use crate::atari2600::state;
+
+    #[test]
+    fn test_e0_slices() {
+        // Each bank is filled with its number.
+        let data: Vec<u8> = (0..8).flat_map(|bank| vec![bank; 0x400]).collect();
+        let mut cartridge = E0Cartridge::new();
+        cartridge.load(&data);
+        let slices = |cartridge: &mut E0Cartridge| [0x1000, 0x1400, 0x1800, 0x1C00].map(|address| cartridge.read(address));
+        assert_eq!(slices(&mut cartridge), [4, 5, 6, 7]);
+
+        // Each slice is switched independently, by reads or writes.
+        cartridge.read(0x1FE2);
+        assert_eq!(slices(&mut cartridge), [2, 5, 6, 7]);
+        cartridge.write(0x1FE9, 0);
+        assert_eq!(slices(&mut cartridge), [2, 1, 6, 7]);
+        cartridge.read(0x1FF7);
+        assert_eq!(slices(&mut cartridge), [2, 1, 7, 7]);
+        cartridge.read(0x1FF0);
+        cartridge.read(0x1FE8);
+        cartridge.read(0x1FE7);
+        assert_eq!(slices(&mut cartridge), [7, 0, 0, 7]);
+        // Mirrored, the whole slice and (only) within the hotspots.
+        cartridge.read(0xFFE3);
+        cartridge.read(0x1FF8);
+        assert_eq!([0x13FF, 0x17FF, 0x1BFF, 0x1FFF].map(|address| cartridge.read(address)), [3, 0, 0, 7]);
+        assert_eq!(cartridge.bank_at(0x1400), 0);
+
+        // Peeks don't switch.
+        assert_eq!(cartridge.peek(0x1FE5), 7);
+        assert_eq!(cartridge.peek(0x1000), 3);
+
+        let mut state = state::StateWriter::new();
+        cartridge.save_state(&mut state);
+        let saved = state.into_bytes();
+        cartridge.read(0x1FF4);
+        cartridge.restore_state(&mut state::StateReader::new(&saved)).unwrap();
+        assert_eq!(slices(&mut cartridge), [3, 0, 0, 7]);
+    }
+}
@@ -0,0 +1,200 @@
+//! M-Network 'E7' bank switching (Burgertime, Masters of the Universe, Bump 'n' Jump).
+//!
+//! 16K of ROM as eight 2K banks and 2K of RAM (a 1K bank and four 256 byte pages), RAM has separate write and read
+//! ports (the R/W line isn't on the cartridge port):
+//!   0x1000-0x17FF: ROM bank 0-6, or the 1K RAM (written at 0x1000-0x13FF, read at 0x1400-0x17FF)
+//!   0x1800-0x19FF: a 256 byte RAM page (written at 0x1800-0x18FF, read at 0x1900-0x19FF)
+//!   0x1A00-0x1FFF: fixed, the end of the last ROM bank
+//! Selected by accessing (reading or writing) a hotspot:
+//!   0x1FE0-0x1FE6: ROM bank 0-6 at 0x1000
+//!   0x1FE7:        1K RAM at 0x1000
+//!   0x1FE8-0x1FEB: RAM page 0-3 at 0x1800
+//! Reading a write port returns 0 (on the console it would also write to the RAM).
+
+use super::super::state;
+use super::cartridge;
+
+pub struct E7Cartridge {
+    banks: Vec<cartridge::Bank>,
+    // The 1K RAM bank, followed by the 256 byte pages.
+    ram: Vec<u8>,
+    // ROM bank at 0x1000 ('RAM_BANK' for the 1K RAM).
+    lower_bank: u8,
+    ram_page: u8,
+}
+
+impl E7Cartridge {
+    const BANK_SIZE: u16 = 0x0800;
+    const NUM_BANKS: usize = 8;
+    const RAM_BANK: u8 = 7;
+    const RAM_BANK_SIZE: u16 = 0x0400;
+    const RAM_PAGE_SIZE: u16 = 0x0100;
+    const NUM_RAM_PAGES: u8 = 4;
+
+    const RAM_PAGE_START: u16 = 0x0800;
+    const FIXED_START: u16 = 0x0A00;
+    const HOTSPOT_START: u16 = 0x0FE0;
+    const HOTSPOT_RAM_PAGE: u16 = 0x0FE8;
+    const HOTSPOT_END: u16 = 0x0FEB;
+
+    pub fn new() -> Self {
+        Self {
+            banks: Vec::new(),
+            ram: vec![0; (E7Cartridge::RAM_BANK_SIZE + E7Cartridge::NUM_RAM_PAGES as u16 * E7Cartridge::RAM_PAGE_SIZE) as usize],
+            lower_bank: 0,
+            ram_page: 0,
+        }
+    }
+
+    fn hotspot(&mut self, address: u16) {
+        match address {
+            E7Cartridge::HOTSPOT_START..E7Cartridge::HOTSPOT_RAM_PAGE => self.lower_bank = (address - E7Cartridge::HOTSPOT_START) as u8,
+            E7Cartridge::HOTSPOT_RAM_PAGE..=E7Cartridge::HOTSPOT_END => self.ram_page = (address - E7Cartridge::HOTSPOT_RAM_PAGE) as u8,
+            _ => {}
+        }
+    }
+
+    /// The RAM index and whether it's the write port, if the address is RAM.
+    fn ram_port(&self, address: u16) -> Option<(usize, bool)> {
+        if address < E7Cartridge::RAM_PAGE_START {
+            (self.lower_bank == E7Cartridge::RAM_BANK).then_some(((address % E7Cartridge::RAM_BANK_SIZE) as usize, address < E7Cartridge::RAM_BANK_SIZE))
+        } else if address < E7Cartridge::FIXED_START {
+            let offset = address - E7Cartridge::RAM_PAGE_START;
+            Some((
+                (E7Cartridge::RAM_BANK_SIZE + self.ram_page as u16 * E7Cartridge::RAM_PAGE_SIZE + offset % E7Cartridge::RAM_PAGE_SIZE) as usize,
+                offset < E7Cartridge::RAM_PAGE_SIZE,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl cartridge::Cartridge for E7Cartridge {
+    fn load(&mut self, data: &[u8]) {
+        self.banks = cartridge::Bank::split(data, E7Cartridge::BANK_SIZE, E7Cartridge::NUM_BANKS);
+        self.ram.fill(0);
+        self.lower_bank = 0;
+        self.ram_page = 0;
+        self.summary();
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        let address = address & 0x0FFF;
+        self.hotspot(address);
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        let address = address & 0x0FFF;
+        self.hotspot(address);
+        if let Some((index, true)) = self.ram_port(address) {
+            self.ram[index] = data;
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        let address = address & 0x0FFF;
+        match self.ram_port(address) {
+            Some((index, false)) => self.ram[index],
+            Some((_, true)) => 0,
+            None => self.banks.get(self.bank_at(address)).map_or(0, |bank| bank.data[(address & (E7Cartridge::BANK_SIZE - 1)) as usize]),
+        }
+    }
+
+    fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|bank| bank.data.as_slice())
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        match self.ram_port(address & 0x0FFF) {
+            Some(_) => self.banks.len(),
+            None if (address & 0x0FFF) < E7Cartridge::RAM_PAGE_START => self.lower_bank as usize,
+            None => E7Cartridge::NUM_BANKS - 1,
+        }
+    }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_u8(self.lower_bank);
+        state.write_u8(self.ram_page);
+        state.write_bytes(&self.ram);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let lower_bank = state.read_u8()?;
+        let ram_page = state.read_u8()?;
+        if lower_bank as usize >= E7Cartridge::NUM_BANKS || ram_page >= E7Cartridge::NUM_RAM_PAGES {
+            return Err(state::StateReader::invalid("Save state bank is outside the cartridge"));
+        }
+        self.lower_bank = lower_bank;
+        self.ram_page = ram_page;
+        state.read_bytes_into(&mut self.ram)
+    }
+
+    fn summary(&self) {
+        println!(" E7: {} banks of {} bytes, {} bytes of RAM", self.banks.len(), E7Cartridge::BANK_SIZE, self.ram.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::memory::cartridge::Cartridge;
+    use crate::atari2600::memory::cartridge_e7::E7Cartridge;
+    use crate::atari2600::state;
+
+    #[test]
+    fn test_e7_banks_and_ram() {
+        // Each bank is filled with its number (plus 0x10).
+        let data: Vec<u8> = (0..8).flat_map(|bank| vec![0x10 + bank; 0x800]).collect();
+        let mut cartridge = E7Cartridge::new();
+        cartridge.load(&data);
+
+        // ROM banks 0-6 at 0x1000, the end of bank 7 is fixed.
+        assert_eq!([0x1000, 0x17FF, 0x1A00, 0x1FFF].map(|address| cartridge.read(address)), [0x10, 0x10, 0x17, 0x17]);
+        cartridge.read(0x1FE6);
+        assert_eq!(cartridge.read(0x1000), 0x16);
+        assert_eq!(cartridge.bank_at(0x1000), 6);
+        cartridge.write(0x1FE3, 0);
+        assert_eq!(cartridge.read(0x17FF), 0x13);
+
+        // 1K RAM, written via 0x1000-0x13FF and read via 0x1400-0x17FF.
+        cartridge.read(0x1FE7);
+        cartridge.write(0x1000, 0xA5);
+        cartridge.write(0x13FF, 0x5A);
+        cartridge.write(0x1401, 0xFF);
+        assert_eq!([0x1400, 0x17FF, 0x1401].map(|address| cartridge.read(address)), [0xA5, 0x5A, 0x00]);
+        assert_eq!(cartridge.read(0x1000), 0);
+        assert_eq!(cartridge.bank_at(0x1400), cartridge.bank_count());
+
+        // RAM pages, written via 0x1800-0x18FF and read via 0x1900-0x19FF, independent of the 1K RAM.
+        for page in 0..4 {
+            cartridge.read(0x1FE8 + page);
+            cartridge.write(0x1800, 0xC0 + page as u8);
+            cartridge.write(0x18FF, 0xD0 + page as u8);
+        }
+        for page in 0..4 {
+            cartridge.write(0x1FE8 + page, 0);
+            assert_eq!([0x1900, 0x19FF].map(|address| cartridge.read(address)), [0xC0 + page as u8, 0xD0 + page as u8]);
+        }
+        assert_eq!(cartridge.read(0x1400), 0xA5);
+
+        // Back to ROM, the RAM is kept (checked after the restore).
+        cartridge.read(0x1FE1);
+        assert_eq!(cartridge.read(0x1400), 0x11);
+
+        let mut state = state::StateWriter::new();
+        cartridge.save_state(&mut state);
+        let saved = state.into_bytes();
+        cartridge.read(0x1FE7);
+        cartridge.read(0x1FE8);
+        cartridge.write(0x1000, 0);
+        cartridge.restore_state(&mut state::StateReader::new(&saved)).unwrap();
+        assert_eq!([0x1400, 0x1900].map(|address| cartridge.read(address)), [0x11, 0xC3]);
+        cartridge.read(0x1FE7);
+        assert_eq!(cartridge.peek(0x1400), 0xA5);
+    }
+}
@@ -0,0 +1,129 @@
+//! Activision 'FE' bank switching (Decathlon, Robot Tank).
+//!
+//! 8K of ROM as two 4K banks, with no hotspots.  Instead the cartridge watches the bus (see 'Cartridge::snoop'), the
+//! access after one of 0x01FE selects the bank from bit 5 of the data (ie bit 13 of an address high byte):
+//!   JSR: pushes the PC (to 0x01FF/0x01FE, with the stack pointer at 0xFF), then reads the high byte of the target.
+//!   RTS: pulls the PC (from 0x01FE/0x01FF), the second being the high byte of the return address.
+//! So code at 0xF000-0xFFFF (bit 13 set) is in bank 0, code at 0xD000-0xDFFF is in bank 1 (as Stella).
+
+use super::super::state;
+use super::cartridge;
+
+pub struct FECartridge {
+    banks: Vec<cartridge::Bank>,
+    current_bank: u8,
+    // The last access was to 0x01FE, so the next selects the bank.
+    last_access_fe: bool,
+}
+
+impl FECartridge {
+    const BANK_SIZE: u16 = 0x1000;
+    const NUM_BANKS: usize = 2;
+    const SWITCH_ADDRESS: u16 = 0x01FE;
+    // The 6507 has 13 address lines.
+    const ADDRESS_MASK: u16 = 0x1FFF;
+    const BANK_BIT: u8 = 0x20;
+
+    pub fn new() -> Self {
+        Self {
+            banks: Vec::new(),
+            current_bank: 0,
+            last_access_fe: false,
+        }
+    }
+}
+
+impl cartridge::Cartridge for FECartridge {
+    fn load(&mut self, data: &[u8]) {
+        self.banks = cartridge::Bank::split(data, FECartridge::BANK_SIZE, FECartridge::NUM_BANKS);
+        self.current_bank = 0;
+        self.last_access_fe = false;
+        self.summary();
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {}
+
+    fn peek(&self, address: u16) -> u8 {
+        self.banks.get(self.current_bank as usize).map_or(0, |bank| bank.data[(address & (FECartridge::BANK_SIZE - 1)) as usize])
+    }
+
+    fn snoops(&self) -> bool {
+        true
+    }
+
+    fn snoop(&mut self, address: u16, data: u8, write: bool) {
+        if self.last_access_fe {
+            self.current_bank = if 0 != data & FECartridge::BANK_BIT { 0 } else { 1 };
+        }
+        self.last_access_fe = (address & FECartridge::ADDRESS_MASK) == FECartridge::SWITCH_ADDRESS;
+    }
+
+    fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn bank_data(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|bank| bank.data.as_slice())
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        self.current_bank as usize
+    }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        state.write_u8(self.current_bank);
+        state.write_bool(self.last_access_fe);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        let current_bank = state.read_u8()?;
+        if current_bank as usize >= FECartridge::NUM_BANKS {
+            return Err(state::StateReader::invalid("Save state bank is outside the cartridge"));
+        }
+        self.current_bank = current_bank;
+        self.last_access_fe = state.read_bool()?;
+        Ok(())
+    }
+
+    fn summary(&self) {
+        println!(" FE: {} banks of {} bytes", self.banks.len(), FECartridge::BANK_SIZE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::machine;
+    use crate::atari2600::memory::cartridge;
+
+    #[test]
+    fn test_fe_jsr_rts() {
+        // Bank 0 (0xF000): LDX #$FF, TXS, JSR $D100, STA $81, JMP $F009
+        // Bank 1 (0xD100): LDA #$42, STA $80, RTS (and bank 1 at 0xF006 would store to $82)
+        let mut rom = vec![0; 0x2000];
+        rom[0x0000..0x000C].copy_from_slice(&[0xA2, 0xFF, 0x9A, 0x20, 0x00, 0xD1, 0x85, 0x81, 0x4C, 0x09, 0xF0, 0x00]);
+        rom[0x0FFC..0x0FFE].copy_from_slice(&[0x00, 0xF0]);
+        rom[0x1100..0x1105].copy_from_slice(&[0xA9, 0x42, 0x85, 0x80, 0x60]);
+        rom[0x1006..0x1008].copy_from_slice(&[0x85, 0x82]);
+
+        let mut machine = machine::Machine::new(machine::MachineConfig::default());
+        machine.insert_cartridge(&rom, &cartridge::CartridgeType::FE);
+        assert_eq!(machine.core().memory.cartridge().bank_at(0xF000), 0);
+
+        // LDX, TXS, JSR, then in bank 1.
+        for _ in 0..3 {
+            machine.step();
+        }
+        assert_eq!(machine.core().memory.cartridge().bank_at(0xD100), 1);
+
+        // LDA, STA, RTS, STA (in bank 0).
+        for _ in 0..4 {
+            machine.step();
+        }
+        assert_eq!(machine.core().memory.cartridge().bank_at(0xF006), 0);
+        assert_eq!(machine.ram()[0..3], [0x42, 0x42, 0x00]);
+    }
+}
@@ -0,0 +1,38 @@
+use super::super::clocks;
+use super::bus;
+
+/// Full 64K of RAM, with no memory mapped devices (no TIA, RIOT or cartridge).
+///
+/// Allows the CPU to be run in isolation (ie conformance tests, functional test binaries).
+pub struct FlatMemory {
+    pub ram: Vec<u8>,
+}
+
+impl FlatMemory {
+    pub const SIZE: usize = 0x10000;
+
+    pub fn new() -> Self {
+        Self { ram: vec![0; FlatMemory::SIZE] }
+    }
+
+    /// Load 'data' starting at 'address' (wrapping at the end of memory).
+    pub fn load(&mut self, address: u16, data: &[u8]) {
+        for (i, value) in data.iter().enumerate() {
+            self.ram[(address as usize + i) % FlatMemory::SIZE] = *value;
+        }
+    }
+}
+
+impl bus::Bus for FlatMemory {
+    fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+
+    fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
+        self.ram[address as usize] = data;
+    }
+
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+}
@@ -1,11 +1,44 @@
 use super::super::clocks;
 use super::super::io;
+use super::super::state;
+use super::bus;
 use super::cartridge;
+use std::fmt;
+
+/// Debugger watch of CPU accesses to an address.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Watchpoint {
+    pub address: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// An access that matched a watchpoint.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub data: u8,
+    pub write: bool,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        write!(dest, "{} 0x{:04X} 0x{:02X}", if self.write { "write" } else { "read" }, self.address, self.data)
+    }
+}
 
 pub struct Memory {
     cartridge: Box<dyn cartridge::Cartridge>,
+    // The cartridge watches every access (see 'Cartridge::snoop').
+    cartridge_snoops: bool,
+    // The cartridge is given the clock (see 'Cartridge::clock').
+    cartridge_clocked: bool,
     pub stella: Box<dyn io::StellaIO>,
     pub riot: Box<dyn io::RiotIO>,
+    watchpoints: Vec<Watchpoint>,
+    watch_hits: Vec<WatchHit>,
+    // Cartridge (bank, offset) locations read, when recording (for profiling).
+    cartridge_reads: Option<Vec<(usize, u16)>>,
 }
 
 impl Memory {
@@ -17,20 +50,107 @@ impl Memory {
     const RIOT_ADDR: u16 = 0x80;
     const ROM_MASK: u16 = 0xD000;
     const ROM_ADDRLINE: u16 = 0x1000;
+    const ADDRESS_MASK: u16 = 0x1FFF; // The 6507 only has 13 address lines (so watch all mirrors of an address).
 
-    pub fn new(cartridge_name: &String, cartridge_type: &cartridge::CartridgeType, stella: Box<dyn io::StellaIO>, riot: Box<dyn io::RiotIO>) -> Self {
+    pub fn new(cartridge: Box<dyn cartridge::Cartridge>, stella: Box<dyn io::StellaIO>, riot: Box<dyn io::RiotIO>) -> Self {
         Self {
-            cartridge: cartridge::get_new_cartridge(cartridge_name, cartridge_type),
+            cartridge_snoops: cartridge.snoops(),
+            cartridge_clocked: cartridge.clocked(),
+            cartridge,
             stella,
             riot,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            cartridge_reads: None,
         }
     }
 
-    pub fn reset(&mut self, cartridge_name: &String, cartridge_type: &cartridge::CartridgeType) {
-        self.cartridge = cartridge::get_new_cartridge(cartridge_name, cartridge_type);
+    pub fn cartridge(&self) -> &dyn cartridge::Cartridge {
+        self.cartridge.as_ref()
+    }
+
+    /// The cartridge (bank, offset within the bank) currently at the address (if it's a cartridge address).
+    pub fn cartridge_location(&self, address: u16) -> Option<(usize, u16)> {
+        if (address & Memory::ROM_ADDRLINE) != Memory::ROM_ADDRLINE {
+            return None;
+        }
+        let bank = self.cartridge.bank_at(address);
+        let bank_size = self.cartridge.bank_data(bank).map_or(0, |data| data.len()) as u16;
+        (bank_size > 0).then(|| (bank, (address & (Memory::ROM_ADDRLINE - 1)) % bank_size))
+    }
+
+    /// Record the cartridge locations read (for profiling), see 'take_cartridge_reads'.
+    pub fn record_cartridge_reads(&mut self, record: bool) {
+        self.cartridge_reads = if record { Some(Vec::new()) } else { None };
+    }
+
+    pub fn take_cartridge_reads(&mut self) -> Vec<(usize, u16)> {
+        self.cartridge_reads.as_mut().map_or(Vec::new(), std::mem::take)
+    }
+
+    /// Watch CPU reads and/or writes of 'address' (removing the watchpoint if neither).
+    pub fn set_watchpoint(&mut self, address: u16, read: bool, write: bool) {
+        let address = address & Memory::ADDRESS_MASK;
+        self.watchpoints.retain(|watchpoint| watchpoint.address != address);
+        if read || write {
+            self.watchpoints.push(Watchpoint { address, read, write });
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Watched accesses since the last call.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    fn watch(&mut self, address: u16, data: u8, write: bool) {
+        let address = address & Memory::ADDRESS_MASK;
+        if self.watchpoints.iter().any(|watchpoint| watchpoint.address == address && if write { watchpoint.write } else { watchpoint.read }) {
+            self.watch_hits.push(WatchHit { address, data, write });
+        }
+    }
+
+    pub fn save_state(&self, state: &mut state::StateWriter) {
+        self.riot.save_state(state);
+        self.stella.save_state(state);
+        self.cartridge.save_state(state);
+    }
+
+    pub fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        self.riot.restore_state(state)?;
+        self.stella.restore_state(state)?;
+        self.cartridge.restore_state(state)
+    }
+
+    pub fn insert_cartridge(&mut self, cartridge: Box<dyn cartridge::Cartridge>) {
+        self.cartridge_snoops = cartridge.snoops();
+        self.cartridge_clocked = cartridge.clocked();
+        self.cartridge = cartridge;
     }
 
     pub fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
+        if !self.watchpoints.is_empty() {
+            self.watch(address, data, true);
+        }
+        self.write_decoded(clock, address, data);
+        if self.cartridge_snoops {
+            self.cartridge.snoop(address, data, true);
+        }
+    }
+
+    /// Whether a write to the address reaches a device (other writes are invalid), for tools writing to user supplied
+    /// addresses.  Same decoding as 'write'.
+    pub fn is_write_mapped(&self, address: u16) -> bool {
+        ((address & 0xFFEF) & Memory::STELLA_MASK) == Memory::STELLA_ADDR
+            || (address & Memory::RIOT_MASK) == Memory::RIOT_ADDR
+            || (Memory::STACK_OFFSET..Memory::STACK_OFFSET + Memory::STACK_LENGTH).contains(&address)
+            || (address & Memory::ROM_ADDRLINE) == Memory::ROM_ADDRLINE
+    }
+
+    fn write_decoded(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
         if ((address & 0xFFEF) & Memory::STELLA_MASK) == Memory::STELLA_ADDR {
             self.stella.write(clock, address & !Memory::STELLA_MASK, data);
         } else if (address & Memory::RIOT_MASK) == Memory::RIOT_ADDR {
@@ -39,6 +159,9 @@ impl Memory {
             self.riot.write(clock, address, data);
         } else if (address & Memory::ROM_ADDRLINE) == Memory::ROM_ADDRLINE {
             // Only address lines 1-13 are connected, higher bits ignored.
+            if self.cartridge_clocked {
+                self.cartridge.clock(clock.ticks);
+            }
             return self.cartridge.write(address & !Memory::ROM_MASK, data);
         } else {
             println!("Write: {:#X}", address);
@@ -47,8 +170,28 @@ impl Memory {
     }
 
     pub fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
+        if self.cartridge_reads.is_some() {
+            let location = self.cartridge_location(address);
+            if let (Some(reads), Some(location)) = (self.cartridge_reads.as_mut(), location) {
+                reads.push(location);
+            }
+        }
+        let data = self.read_decoded(clock, address);
+        if self.cartridge_snoops {
+            self.cartridge.snoop(address, data, false);
+        }
+        if !self.watchpoints.is_empty() {
+            self.watch(address, data, false);
+        }
+        data
+    }
+
+    fn read_decoded(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
         // Only address lines 1-13 are connected, higher bits ignored.
         if (address & Memory::ROM_ADDRLINE) == Memory::ROM_ADDRLINE {
+            if self.cartridge_clocked {
+                self.cartridge.clock(clock.ticks);
+            }
             return self.cartridge.read(address & !Memory::ROM_MASK);
         }
 
@@ -67,15 +210,32 @@ impl Memory {
         self.cartridge.read(address & Memory::ROM_MASK)
     }
 
-    pub fn read16(&mut self, clock: &clocks::Clock, address: u16) -> u16 {
-        self.read(clock, address) as u16 + ((self.read(clock, address + 1) as u16) << 8)
+    pub fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        // Same decoding as 'read'.
+        if (address & Memory::ROM_ADDRLINE) == Memory::ROM_ADDRLINE {
+            self.cartridge.peek(address & !Memory::ROM_MASK)
+        } else if (address & Memory::RIOT_MASK) == Memory::RIOT_ADDR {
+            self.riot.peek(clock, address & !Memory::RIOT_MASK)
+        } else if (address & Memory::STELLA_MASK) == Memory::STELLA_ADDR {
+            self.stella.peek(clock, address & !Memory::STELLA_MASK)
+        } else if (Memory::STACK_OFFSET..Memory::STACK_OFFSET + Memory::STACK_LENGTH).contains(&address) {
+            self.riot.peek(clock, address)
+        } else {
+            self.cartridge.peek(address & Memory::ROM_MASK)
+        }
+    }
+}
+
+impl bus::Bus for Memory {
+    fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.read(clock, address)
     }
 
-    pub fn read_sp(&mut self, clock: &clocks::Clock, address: u8) -> u8 {
-        self.read(clock, address as u16 + Memory::STACK_LENGTH)
+    fn write(&mut self, clock: &mut clocks::Clock, address: u16, data: u8) {
+        self.write(clock, address, data);
     }
 
-    pub fn write_sp(&mut self, clock: &mut clocks::Clock, address: u8, data: u8) {
-        self.write(clock, address as u16 + Memory::STACK_LENGTH, data);
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.peek(clock, address)
     }
 }
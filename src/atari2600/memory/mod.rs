@@ -1,4 +1,11 @@
 pub mod addressing;
+pub mod bus;
 pub mod cartridge;
+pub mod cartridge_3f;
+pub mod cartridge_dpc;
+pub mod cartridge_e0;
+pub mod cartridge_e7;
+pub mod cartridge_fe;
+pub mod flat_memory;
 pub mod memory;
 pub mod riot;
@@ -1,6 +1,7 @@
 use super::super::clocks;
 use super::super::inputs;
 use super::super::io;
+use super::super::state;
 
 #[derive(Clone, Copy)]
 enum Interval {
@@ -47,9 +48,14 @@ impl Riot {
     }
 
     pub fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.peek(clock, address)
+    }
+
+    pub fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
         let mut value: u8 = 0;
 
-        let future_clock = clock.ticks + 12;
+        // Reads happen on their bus cycle, so the current clock is the time of the read.
+        let read_clock = clock.ticks;
 
         if 0 == (address & Riot::NOT_RAMSELECT) {
             return self.ram[(address as u8 & Riot::RIOT_ADDRMASK) as usize];
@@ -63,16 +69,16 @@ impl Riot {
         } else if test == Riot::RIOT_SWCHB {
             value = self.input.swchb;
         } else if test == Riot::RIOT_TIM1T || test == Riot::RIOT_TIM8T || test == Riot::RIOT_TIM64T || test == Riot::RIOT_T1024T || test == Riot::TIMERADDR {
-            if self.expiration_time >= future_clock {
+            if self.expiration_time >= read_clock {
                 // If expiration hasn't occured, return the time remaining.
-                value = ((self.expiration_time - future_clock) / (self.interval as clocks::ClockType * Riot::CYCLES_TO_CLOCK)) as u8;
+                value = ((self.expiration_time - read_clock) / (self.interval as clocks::ClockType * Riot::CYCLES_TO_CLOCK)) as u8;
             } else {
                 // Calculate ticks past zero, may not be quite right
                 // The interval was passed, value counts down from 255.
-                value = 0x100_i16.wrapping_sub(((future_clock - self.expiration_time) / Riot::CYCLES_TO_CLOCK) as i16) as u8;
+                value = 0x100_i16.wrapping_sub(((read_clock - self.expiration_time) / Riot::CYCLES_TO_CLOCK) as i16) as u8;
             }
         } else if test == Riot::RIOT_INTERRUPT {
-            if self.expiration_time >= future_clock {
+            if self.expiration_time >= read_clock {
                 value = 0;
             } else {
                 // Return the interrupt flag if time has expired.
@@ -114,10 +120,33 @@ impl io::ReadWriteMemory for Riot {
     fn read(&mut self, clock: &clocks::Clock, address: u16) -> u8 {
         self.read(clock, address)
     }
+    fn peek(&self, clock: &clocks::Clock, address: u16) -> u8 {
+        self.peek(clock, address)
+    }
 }
 
 impl io::RiotIO for Riot {
     fn set_inputs(&mut self, inputs: inputs::Input) {
         self.input = inputs;
     }
+
+    fn save_state(&self, state: &mut state::StateWriter) {
+        self.input.save_state(state);
+        state.write_u32(self.interval as u32);
+        state.write_u64(self.expiration_time);
+        state.write_bytes(&self.ram);
+    }
+
+    fn restore_state(&mut self, state: &mut state::StateReader) -> std::io::Result<()> {
+        self.input.restore_state(state)?;
+        self.interval = match state.read_u32()? {
+            1 => Interval::Tim1,
+            8 => Interval::Tim8,
+            64 => Interval::Tim64,
+            1024 => Interval::Tim1024,
+            _ => return Err(state::StateReader::invalid("Invalid RIOT timer interval")),
+        };
+        self.expiration_time = state.read_u64()?;
+        state.read_bytes_into(&mut self.ram)
+    }
 }
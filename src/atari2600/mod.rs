@@ -1,9 +1,25 @@
+#[cfg(feature = "sdl")]
 pub mod atari2600;
 pub mod audio;
+pub mod capture;
 pub mod clocks;
 pub mod cpu;
+pub mod debugger;
+pub mod gdb;
 pub mod graphics;
+pub mod headless;
 pub mod inputs;
 pub mod io;
+pub mod machine;
 pub mod memory;
+pub mod movie;
+pub mod png;
 pub mod ports;
+pub mod profiler;
+pub mod regression;
+pub mod rewind;
+pub mod screenshot;
+pub mod state;
+pub mod symbols;
+#[cfg(test)]
+pub mod testing;
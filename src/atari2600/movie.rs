@@ -0,0 +1,185 @@
+//! Input movies, the console inputs for each frame from power-on, for reproducible runs (ie bug reports).
+//!
+//! A text file, a header (with a checksum of the cartridge ROM) then a line per run of identical frames:
+//!
+//! `<frames> <SWCHA> <SWCHB> <INPT0> <INPT1> <INPT2> <INPT3> <INPT4> <INPT5>` (values in hex)
+//!
+//! Inputs are only applied at the end of each frame (both when recording and replaying), so playback is exact.
+
+use super::inputs;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+type FrameInputs = [u8; 8];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    Record,
+    Playback,
+}
+
+pub struct Movie {
+    file_name: String,
+    mode: Mode,
+    checksum: u32,
+    frames: Vec<FrameInputs>,
+    position: usize,
+}
+
+impl Movie {
+    const HEADER: &'static str = "rusted_atari2600 movie 1";
+
+    /// Record to 'file_name' (written by 'finish').
+    pub fn record(file_name: &str, checksum: u32) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            mode: Mode::Record,
+            checksum,
+            frames: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Replay 'file_name', which must have been recorded with the same cartridge (checksum).
+    pub fn playback(file_name: &str, checksum: u32) -> io::Result<Self> {
+        Ok(Self {
+            file_name: file_name.to_string(),
+            mode: Mode::Playback,
+            checksum,
+            frames: Movie::parse(&fs::read_to_string(file_name)?, checksum)?,
+            position: 0,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn frame_inputs(input: &inputs::Input) -> FrameInputs {
+        [input.swcha, input.swchb, input.input0, input.input1, input.input2, input.input3, input.input4, input.input5]
+    }
+
+    /// End of a frame, record the input or replace it with the recorded input (the live input is used once the
+    /// recording has finished).
+    pub fn frame(&mut self, input: &mut inputs::Input) {
+        match self.mode {
+            Mode::Record => self.frames.push(Movie::frame_inputs(input)),
+            Mode::Playback => {
+                if let Some(values) = self.frames.get(self.position) {
+                    [input.swcha, input.swchb, input.input0, input.input1, input.input2, input.input3, input.input4, input.input5] = *values;
+                } else if self.position == self.frames.len() {
+                    println!("Replay finished after {} frames, continuing with the keyboard", self.frames.len());
+                }
+                self.position += 1;
+            }
+        }
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    pub fn parse(text: &str, checksum: u32) -> io::Result<Vec<FrameInputs>> {
+        let mut lines = text.lines();
+        if lines.next() != Some(Movie::HEADER) {
+            return Err(Movie::invalid("Not a movie file".to_string()));
+        }
+        let movie_checksum = lines.next().and_then(|line| line.strip_prefix("cartridge ")).and_then(|value| u32::from_str_radix(value, 16).ok());
+        if movie_checksum != Some(checksum) {
+            return Err(Movie::invalid("The movie was recorded with a different cartridge".to_string()));
+        }
+
+        let mut frames = Vec::new();
+        for (number, line) in lines.enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let count = words.first().and_then(|count| count.parse::<usize>().ok());
+            let values: Vec<u8> = words.iter().skip(1).filter_map(|value| u8::from_str_radix(value, 16).ok()).collect();
+            match (count, FrameInputs::try_from(values)) {
+                (Some(count), Ok(values)) if words.len() == 1 + values.len() => frames.extend(std::iter::repeat_n(values, count)),
+                _ => return Err(Movie::invalid(format!("Invalid movie line {}: '{}'", number + 3, line))),
+            }
+        }
+        Ok(frames)
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> io::Result<()> {
+        writeln!(output, "{}", Movie::HEADER)?;
+        writeln!(output, "cartridge {:08X}", self.checksum)?;
+        for run in self.frames.chunk_by(|a, b| a == b) {
+            let values: Vec<String> = run[0].iter().map(|value| format!("{:02X}", value)).collect();
+            writeln!(output, "{} {}", run.len(), values.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Write the recording (nothing to do for playback).
+    pub fn finish(&self) -> io::Result<()> {
+        if self.mode == Mode::Record {
+            let mut output = io::BufWriter::new(fs::File::create(&self.file_name)?);
+            self.write(&mut output)?;
+            output.flush()?;
+            println!("Movie ({} frames) written to: {}", self.frames.len(), self.file_name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::inputs;
+    use crate::atari2600::movie;
+    use crate::atari2600::testing;
+    use std::fs;
+
+    #[test]
+    fn test_record_playback() {
+        let mut recording = movie::Movie::record("unused", 0x1234ABCD);
+        let mut joystick = inputs::Joystick::new();
+        for frame in 0..10 {
+            joystick.j1_left((3..6).contains(&frame));
+            joystick.j1_fire(frame == 8);
+            let mut input = joystick.input;
+            recording.frame(&mut input);
+        }
+
+        let mut text = Vec::new();
+        recording.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(
+            text,
+            "rusted_atari2600 movie 1\ncartridge 1234ABCD\n\
+             3 FF 3F FF FF FF FF FF FF\n3 BF 3F FF FF FF FF FF FF\n2 FF 3F FF FF FF FF FF FF\n\
+             1 FF 3F FF FF FF FF 7F FF\n1 FF 3F FF FF FF FF FF FF\n"
+        );
+
+        let frames = movie::Movie::parse(&text, 0x1234ABCD).unwrap();
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[4][0], 0xBF);
+        assert_eq!(frames[8][6], 0x7F);
+
+        let file_name = testing::temp_path("movie_test.txt").to_string_lossy().to_string();
+        fs::write(&file_name, &text).unwrap();
+        let mut playback = movie::Movie::playback(&file_name, 0x1234ABCD).unwrap();
+        fs::remove_file(&file_name).unwrap();
+        let mut live = inputs::Joystick::new();
+        live.j1_up(true);
+        let replayed: Vec<u8> = (0..11)
+            .map(|_| {
+                let mut input = live.input;
+                playback.frame(&mut input);
+                input.swcha
+            })
+            .collect();
+        // The live input is used after the end of the movie.
+        assert_eq!(replayed, vec![0xFF, 0xFF, 0xFF, 0xBF, 0xBF, 0xBF, 0xFF, 0xFF, 0xFF, 0xFF, 0xEF]);
+
+        assert!(movie::Movie::parse(&text, 0x1234ABCE).is_err());
+        assert!(movie::Movie::parse("rusted_atari2600 movie 1\ncartridge 1234ABCD\n3 FF 3F\n", 0x1234ABCD).is_err());
+    }
+}
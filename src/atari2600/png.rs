@@ -0,0 +1,176 @@
+//! Minimal PNG images (8 bit RGB), for exact captures of frames.
+//!
+//! The image data is stored uncompressed (zlib 'stored' blocks), so no compression library is needed.  'read_png'
+//! only reads images in the same form (ie those written by 'write_png', such as golden frames).
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+const BIT_DEPTH: u8 = 8;
+const COLOUR_TYPE_RGB: u8 = 2;
+const FILTER_NONE: u8 = 0;
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFFFFFF_u32, |crc, byte| (0..8).fold(crc ^ *byte as u32, |crc, _| if 0 != crc & 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 }))
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (a, b) = data.iter().fold((1, 0), |(a, b), byte| {
+        let a = (a + *byte as u32) % MODULUS;
+        (a, (b + a) % MODULUS)
+    });
+    (b << 16) | a
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn write_chunk(output: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(data);
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    output.write_all(&chunk)?;
+    output.write_all(&crc32(&chunk).to_be_bytes())
+}
+
+/// Write the image, 3 bytes (RGB) per pixel, a line at a time.
+pub fn write_png(output: &mut dyn Write, rgb: &[u8], width: u16, height: u16) -> io::Result<()> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, colour type, compression (deflate), filter method and no interlacing.
+    header.extend_from_slice(&[BIT_DEPTH, COLOUR_TYPE_RGB, 0, 0, 0]);
+
+    // Each line starts with its filter type.
+    let mut lines = Vec::with_capacity(rgb.len() + height as usize);
+    for line in rgb.chunks(3 * width as usize) {
+        lines.push(FILTER_NONE);
+        lines.extend_from_slice(line);
+    }
+
+    // zlib (deflate, no dictionary), stored blocks (a header byte with the 'final' flag, the length and its complement).
+    let mut data = vec![0x78, 0x01];
+    let mut blocks: Vec<&[u8]> = lines.chunks(MAX_STORED_BLOCK).collect();
+    if blocks.is_empty() {
+        blocks.push(&[]);
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        data.push((index + 1 == blocks.len()) as u8);
+        data.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        data.extend_from_slice(block);
+    }
+    data.extend_from_slice(&adler32(&lines).to_be_bytes());
+
+    output.write_all(SIGNATURE)?;
+    write_chunk(output, b"IHDR", &header)?;
+    write_chunk(output, b"IDAT", &data)?;
+    write_chunk(output, b"IEND", &[])
+}
+
+pub fn save_png(file_name: &str, rgb: &[u8], width: u16, height: u16) -> io::Result<()> {
+    let mut output = io::BufWriter::new(fs::File::create(file_name)?);
+    write_png(&mut output, rgb, width, height)?;
+    output.flush()
+}
+
+/// Read an image written by 'write_png', returning the pixels (RGB), width and height.
+pub fn read_png(input: &[u8]) -> io::Result<(Vec<u8>, u16, u16)> {
+    if !input.starts_with(SIGNATURE) {
+        return Err(invalid("Not a PNG image"));
+    }
+
+    let mut position = SIGNATURE.len();
+    let mut size = None;
+    let mut data = Vec::new();
+    loop {
+        let length = input.get(position..position + 4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize).ok_or_else(|| invalid("PNG is truncated"))?;
+        let chunk = input.get(position + 4..position + 8 + length).ok_or_else(|| invalid("PNG is truncated"))?;
+        let crc = input.get(position + 8 + length..position + 12 + length).ok_or_else(|| invalid("PNG is truncated"))?;
+        if crc32(chunk) != u32::from_be_bytes(crc.try_into().unwrap()) {
+            return Err(invalid("PNG chunk CRC mismatch"));
+        }
+        position += 12 + length;
+
+        let (kind, chunk_data) = chunk.split_at(4);
+        match kind {
+            b"IHDR" => {
+                if chunk_data.len() != 13 || chunk_data[8..] != [BIT_DEPTH, COLOUR_TYPE_RGB, 0, 0, 0] {
+                    return Err(invalid("Only 8 bit RGB PNGs are supported"));
+                }
+                let width = u32::from_be_bytes(chunk_data[0..4].try_into().unwrap());
+                let height = u32::from_be_bytes(chunk_data[4..8].try_into().unwrap());
+                size = Some((u16::try_from(width).map_err(|_| invalid("PNG is too wide"))?, u16::try_from(height).map_err(|_| invalid("PNG is too high"))?));
+            }
+            b"IDAT" => data.extend_from_slice(chunk_data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let (width, height) = size.ok_or_else(|| invalid("PNG has no header"))?;
+
+    let mut lines = Vec::new();
+    let mut position = 2;
+    loop {
+        let block_type = *data.get(position).ok_or_else(|| invalid("PNG data is truncated"))?;
+        if 0 != block_type & 0x6 {
+            return Err(invalid("Only uncompressed PNGs are supported"));
+        }
+        let block = data.get(position + 1..position + 5).ok_or_else(|| invalid("PNG data is truncated"))?;
+        let length = u16::from_le_bytes([block[0], block[1]]);
+        if length != !u16::from_le_bytes([block[2], block[3]]) {
+            return Err(invalid("PNG block length mismatch"));
+        }
+        lines.extend_from_slice(data.get(position + 5..position + 5 + length as usize).ok_or_else(|| invalid("PNG data is truncated"))?);
+        position += 5 + length as usize;
+        if 0 != block_type & 0x1 {
+            break;
+        }
+    }
+    if data.get(position..position + 4) != Some(adler32(&lines).to_be_bytes().as_slice()) {
+        return Err(invalid("PNG data checksum mismatch"));
+    }
+
+    let line_length = 1 + 3 * width as usize;
+    if lines.len() != line_length * height as usize {
+        return Err(invalid("PNG data size mismatch"));
+    }
+    let mut rgb = Vec::with_capacity(3 * width as usize * height as usize);
+    for line in lines.chunks(line_length) {
+        if line[0] != FILTER_NONE {
+            return Err(invalid("Only unfiltered PNGs are supported"));
+        }
+        rgb.extend_from_slice(&line[1..]);
+    }
+    Ok((rgb, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::png;
+
+    #[test]
+    fn test_write_read_png() {
+        assert_eq!(png::crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(png::adler32(b"Wikipedia"), 0x11E60398);
+
+        // Large enough to need multiple stored blocks.
+        let (width, height) = (320, 220);
+        let rgb: Vec<u8> = (0..3 * width as usize * height as usize).map(|i| (i % 251) as u8).collect();
+        let mut image = Vec::new();
+        png::write_png(&mut image, &rgb, width, height).unwrap();
+        assert!(image.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x01\x40\x00\x00\x00\xdc\x08\x02"));
+        assert!(image.ends_with(b"IEND\xae\x42\x60\x82"));
+        assert_eq!(png::read_png(&image).unwrap(), (rgb, width, height));
+
+        let mut corrupt = image.clone();
+        corrupt[100] ^= 0xFF;
+        assert!(png::read_png(&corrupt).is_err());
+        assert!(png::read_png(&image[..image.len() - 1]).is_err());
+    }
+}
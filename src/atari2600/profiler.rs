@@ -0,0 +1,274 @@
+//! Execution profiler and code coverage.
+//!
+//! Records, per cartridge bank and address, the number of times each instruction is executed and the CPU cycles spent
+//! (including any 'WSYNC' stall, as that's where the time goes), along with which bytes were executed (op-codes and
+//! operands) versus only read as data.  Data is the instructions' operand reads (their effective address, or a JMP's
+//! indirect pointer), not the CPU's dummy reads (ie of the byte after an implied instruction or RTS).
+//!
+//! At exit a text report ('<name>') and a CSV report ('<name>.csv', a row per byte used) are written.
+
+use super::cpu;
+use super::memory::cartridge::Cartridge;
+use super::symbols;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Counts {
+    pub executions: u64,
+    pub cycles: u64,
+}
+
+/// How a byte has been used (executed takes priority over data).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Coverage {
+    Data,
+    Operand,
+    Instruction,
+}
+
+// Location of the instruction being executed, (bank, offset) and length, the locations it reads as data and the clock
+// at the start.
+struct PendingStep {
+    location: Option<(usize, u16)>,
+    length: u16,
+    data: Vec<(usize, u16)>,
+    ticks: u64,
+}
+
+pub struct Profiler {
+    report_name: String,
+    instructions: BTreeMap<(usize, u16), Counts>,
+    coverage: BTreeMap<(usize, u16), Coverage>,
+    total: Counts,
+    // Instructions executed outside of the cartridge (ie RAM).
+    other: Counts,
+    pending: Option<PendingStep>,
+}
+
+impl Profiler {
+    const HOT_SPOTS: usize = 50;
+
+    pub fn new(report_name: &str) -> Self {
+        Self {
+            report_name: report_name.to_string(),
+            instructions: BTreeMap::new(),
+            coverage: BTreeMap::new(),
+            total: Counts::default(),
+            other: Counts::default(),
+            pending: None,
+        }
+    }
+
+    fn bank_size(cartridge: &dyn Cartridge, bank: usize) -> u16 {
+        cartridge.bank_data(bank).map_or(0, |data| data.len()) as u16
+    }
+
+    /// Address of the offset, when the bank is at the top of memory (as disassembled).
+    fn address(cartridge: &dyn Cartridge, bank: usize, offset: u16) -> u16 {
+        (0x10000 - Profiler::bank_size(cartridge, bank) as u32 + offset as u32) as u16
+    }
+
+    fn mark(&mut self, location: (usize, u16), coverage: Coverage) {
+        let entry = self.coverage.entry(location).or_insert(coverage);
+        *entry = (*entry).max(coverage);
+    }
+
+    /// The addresses the instruction reads as data, from its addressing mode and the registers (before it executes).
+    fn data_addresses(disassembly: &cpu::disasm::Disassembly, x: u8, y: u8, read: &dyn Fn(u16) -> u8) -> Vec<u16> {
+        use cpu::disasm::Mode;
+
+        // Zero page pointers wrap within the zero page.
+        let pointer = |address: u8| read(address as u16) as u16 | ((read(address.wrapping_add(1) as u16) as u16) << 8);
+        let operand = disassembly.operand;
+        match (disassembly.mnemonic, disassembly.mode) {
+            // The (page wrapping) pointer.
+            ("JMP", Mode::Indirect) => vec![operand, (operand & 0xFF00) | (operand.wrapping_add(1) & 0x00FF)],
+            // Jumps and stores don't read their effective address.
+            ("JMP" | "JSR" | "STA" | "STX" | "STY" | "SAX" | "SHA" | "SHX" | "SHY" | "TAS", _) => Vec::new(),
+            (_, Mode::ZeroPage | Mode::Absolute) => vec![operand],
+            (_, Mode::ZeroPageX) => vec![(operand as u8).wrapping_add(x) as u16],
+            (_, Mode::ZeroPageY) => vec![(operand as u8).wrapping_add(y) as u16],
+            (_, Mode::AbsoluteX) => vec![operand.wrapping_add(x as u16)],
+            (_, Mode::AbsoluteY) => vec![operand.wrapping_add(y as u16)],
+            (_, Mode::IndirectX) => vec![pointer((operand as u8).wrapping_add(x))],
+            (_, Mode::IndirectY) => vec![pointer(operand as u8).wrapping_add(y as u16)],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn before_step(&mut self, core: &mut cpu::core::Core) {
+        let pc = core.pc_state().get_pc();
+        let peek = |address| core.memory.peek(&core.clock, address);
+        let disassembly = cpu::disasm::disassemble(pc, &peek);
+        let data = Profiler::data_addresses(&disassembly, core.pc_state().get_x(), core.pc_state().get_y(), &peek);
+        core.memory.take_cartridge_reads();
+        self.pending = Some(PendingStep {
+            location: core.memory.cartridge_location(pc),
+            length: disassembly.length(),
+            data: data.iter().filter_map(|address| core.memory.cartridge_location(*address)).collect(),
+            ticks: core.clock.ticks,
+        });
+    }
+
+    pub fn after_step(&mut self, core: &mut cpu::core::Core) {
+        let Some(step) = self.pending.take() else {
+            return;
+        };
+
+        let cycles = (core.clock.ticks - step.ticks) / cpu::pc_state::PcState::CYCLES_TO_CLOCK as u64;
+        self.total.executions += 1;
+        self.total.cycles += cycles;
+
+        match step.location {
+            Some((bank, offset)) => {
+                let counts = self.instructions.entry((bank, offset)).or_default();
+                counts.executions += 1;
+                counts.cycles += cycles;
+
+                let bank_size = Profiler::bank_size(core.memory.cartridge(), bank);
+                self.mark((bank, offset), Coverage::Instruction);
+                for i in 1..step.length {
+                    self.mark((bank, (offset + i) % bank_size), Coverage::Operand);
+                }
+            }
+            None => {
+                self.other.executions += 1;
+                self.other.cycles += cycles;
+            }
+        }
+
+        // Only the reads of the data locations (not the dummy reads).
+        for location in core.memory.take_cartridge_reads().into_iter().filter(|location| step.data.contains(location)) {
+            self.mark(location, Coverage::Data);
+        }
+    }
+
+    pub fn counts(&self, bank: usize, offset: u16) -> Option<Counts> {
+        self.instructions.get(&(bank, offset)).copied()
+    }
+
+    pub fn coverage(&self, bank: usize, offset: u16) -> Option<Coverage> {
+        self.coverage.get(&(bank, offset)).copied()
+    }
+
+    pub fn write_text_report(&self, output: &mut dyn Write, cartridge: &dyn Cartridge, symbols: &symbols::Symbols) -> io::Result<()> {
+        writeln!(output, "Profile: {} CPU cycles, {} instructions executed", self.total.cycles, self.total.executions)?;
+        if self.other.executions > 0 {
+            writeln!(output, "Outside the cartridge: {} CPU cycles, {} instructions executed", self.other.cycles, self.other.executions)?;
+        }
+
+        let mut hot_spots: Vec<(&(usize, u16), &Counts)> = self.instructions.iter().collect();
+        hot_spots.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(b.0)));
+
+        writeln!(output, "\nHot spots (by cycles):")?;
+        writeln!(output, "{:>4}  {:<7}  {:>12}  {:>14}  {:>6}  Instruction", "Bank", "Address", "Executions", "Cycles", "%")?;
+        for (&(bank, offset), counts) in hot_spots.iter().take(Profiler::HOT_SPOTS) {
+            let address = Profiler::address(cartridge, bank, offset);
+            let data = cartridge.bank_data(bank).unwrap_or(&[]);
+            let read = |read_address: u16| data.get(read_address.wrapping_sub(address).wrapping_add(offset) as usize).copied().unwrap_or(0);
+            let disassembly = cpu::disasm::disassemble(address, &read);
+            writeln!(
+                output,
+                "{:>4}  {:04X}     {:>12}  {:>14}  {:>6.2}  {}{}",
+                bank,
+                address,
+                counts.executions,
+                counts.cycles,
+                100.0 * counts.cycles as f64 / self.total.cycles.max(1) as f64,
+                disassembly.text_with_symbols(symbols),
+                symbols.name(address).map_or(String::new(), |name| format!("  ({})", name))
+            )?;
+        }
+
+        writeln!(output, "\nCoverage (bytes):")?;
+        writeln!(output, "{:>4}  {:>6}  {:>15}  {:>15}  {:>15}", "Bank", "Size", "Executed", "Data", "Unused")?;
+        for bank in 0..cartridge.bank_count() {
+            let size = Profiler::bank_size(cartridge, bank) as usize;
+            let count = |used: &dyn Fn(Coverage) -> bool| self.coverage.range((bank, 0)..(bank + 1, 0)).filter(|(_, coverage)| used(**coverage)).count();
+            let executed = count(&|coverage| coverage != Coverage::Data);
+            let data = count(&|coverage| coverage == Coverage::Data);
+            let percent = |bytes: usize| format!("{} ({:.1}%)", bytes, 100.0 * bytes as f64 / size.max(1) as f64);
+            writeln!(output, "{:>4}  {:>6}  {:>15}  {:>15}  {:>15}", bank, size, percent(executed), percent(data), percent(size - executed - data))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv_report(&self, output: &mut dyn Write, cartridge: &dyn Cartridge, symbols: &symbols::Symbols) -> io::Result<()> {
+        writeln!(output, "bank,address,coverage,executions,cycles,label")?;
+        for (&(bank, offset), coverage) in &self.coverage {
+            let address = Profiler::address(cartridge, bank, offset);
+            let counts = self.counts(bank, offset).unwrap_or_default();
+            let coverage = match coverage {
+                Coverage::Instruction => "instruction",
+                Coverage::Operand => "operand",
+                Coverage::Data => "data",
+            };
+            writeln!(output, "{},{:04X},{},{},{},{}", bank, address, coverage, counts.executions, counts.cycles, symbols.name(address).unwrap_or(""))?;
+        }
+        Ok(())
+    }
+
+    /// Write the text and CSV reports.
+    pub fn write_reports(&self, core: &cpu::core::Core) -> io::Result<()> {
+        let cartridge = core.memory.cartridge();
+        self.write_text_report(&mut io::BufWriter::new(fs::File::create(&self.report_name)?), cartridge, core.symbols())?;
+        self.write_csv_report(&mut io::BufWriter::new(fs::File::create(format!("{}.csv", self.report_name))?), cartridge, core.symbols())?;
+        println!("Profile written to: {} (and {}.csv)", self.report_name, self.report_name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::profiler;
+    use crate::atari2600::symbols;
+    use crate::atari2600::testing;
+
+    #[test]
+    fn test_profile() {
+        // LDX #$01, LDA $F010,X, STA $80, JSR $F00D, JMP $F002, then RTS (at 0xF00D, its dummy read is of 0xF00E), with
+        // the data table at 0xF010.
+        let mut rom = testing::rom(&[0xA2, 0x01, 0xBD, 0x10, 0xF0, 0x85, 0x80, 0x20, 0x0D, 0xF0, 0x4C, 0x02, 0xF0, 0x60, 0xFF], 0x00);
+        rom[0x010..0x012].copy_from_slice(&[0x12, 0x34]);
+        let mut core = testing::machine(&rom).into_core();
+        // The stack in the RIOT RAM, for the JSR.
+        core.pc_state_mut().set_s(0xFF);
+
+        let mut profiler = profiler::Profiler::new("unused");
+        core.memory.record_cartridge_reads(true);
+        for _ in 0..(1 + 5 * 10) {
+            profiler.before_step(&mut core);
+            core.step(false, false);
+            profiler.after_step(&mut core);
+        }
+
+        assert_eq!(profiler.counts(0, 0x000), Some(profiler::Counts { executions: 1, cycles: 2 }));
+        assert_eq!(profiler.counts(0, 0x002), Some(profiler::Counts { executions: 10, cycles: 40 }));
+        assert_eq!(profiler.counts(0, 0x007), Some(profiler::Counts { executions: 10, cycles: 60 }));
+        assert_eq!(profiler.counts(0, 0x00D), Some(profiler::Counts { executions: 10, cycles: 60 }));
+        assert_eq!(profiler.coverage(0, 0x002), Some(profiler::Coverage::Instruction));
+        assert_eq!(profiler.coverage(0, 0x004), Some(profiler::Coverage::Operand));
+        assert_eq!(profiler.coverage(0, 0x00E), None);
+        assert_eq!(profiler.coverage(0, 0x010), None);
+        assert_eq!(profiler.coverage(0, 0x011), Some(profiler::Coverage::Data));
+        assert_eq!(profiler.coverage(0, 0x012), None);
+
+        let mut symbols = symbols::Symbols::new();
+        symbols.add("Loop", 0xF002);
+        let mut text = Vec::new();
+        profiler.write_text_report(&mut text, core.memory.cartridge(), &symbols).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("Profile: 222 CPU cycles, 51 instructions executed"), "{}", text);
+        assert!(text.contains("   0  F002               10              40   18.02  LDA $F010,X  (Loop)"), "{}", text);
+
+        let mut csv = Vec::new();
+        profiler.write_csv_report(&mut csv, core.memory.cartridge(), &symbols).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.contains("\n0,F002,instruction,10,40,Loop\n0,F003,operand,0,0,\n"), "{}", csv);
+        assert!(csv.contains("\n0,F011,data,0,0,\n"), "{}", csv);
+    }
+}
@@ -0,0 +1,327 @@
+//! Golden frame regression tests, over a directory of ROMs (to catch changes in the TIA timing, ie 'get_write_delay'
+//! and 'hmove_clocks').
+//!
+//! The directory's 'manifest.txt' has a line per ROM ('#' starts a comment):
+//!
+//! `<rom file> <cartridge type> <frames> [inputs=<movie file>] [frame=<hash>] [ram=<hash>]`
+//!
+//! Each ROM is run from power-on for the frames (with the inputs of the movie, see 'movie', if given), then the hashes
+//! (64 bit FNV-1a, in hex) of the frame (palette indices) and RAM are compared against the golden values.  For a
+//! mismatch, the golden frame (from 'golden/<rom file>.png'), the frame and their differences (in red) are written
+//! side by side to 'failures/<rom file>.png'.
+//!
+//! Updating writes the hashes into the manifest and the frames to 'golden'.
+//!
+//! The ROMs aren't part of the repository, set 'REGRESSION_DIR' to the directory containing them (and the manifest),
+//! otherwise 'test_data/regression' is used (and the tests are skipped if it doesn't exist).  Set 'REGRESSION_UPDATE'
+//! to update the golden values.
+
+use super::machine;
+use super::memory::cartridge;
+use super::movie;
+use super::png;
+use super::state;
+
+use std::fs;
+use std::io;
+use std::path;
+use std::str::FromStr;
+
+pub const REGRESSION_DIR_ENV: &str = "REGRESSION_DIR";
+pub const DEFAULT_REGRESSION_DIR: &str = "test_data/regression";
+pub const REGRESSION_UPDATE_ENV: &str = "REGRESSION_UPDATE";
+
+pub struct RegressionTest {
+    // Line of the manifest.
+    line: usize,
+    pub rom: String,
+    pub cartridge_type: cartridge::CartridgeType,
+    pub frames: u64,
+    pub inputs: Option<String>,
+    pub frame_hash: Option<u64>,
+    pub ram_hash: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    Updated,
+}
+
+/// Result of running a ROM, the frame (RGB) and the hashes.
+struct Run {
+    frame_rgb24: Vec<u8>,
+    frame_hash: u64,
+    ram_hash: u64,
+}
+
+pub struct Regression {
+    directory: path::PathBuf,
+    manifest: Vec<String>,
+    pub tests: Vec<RegressionTest>,
+}
+
+impl Regression {
+    pub const MANIFEST: &'static str = "manifest.txt";
+    const GOLDEN_DIR: &'static str = "golden";
+    const FAILURES_DIR: &'static str = "failures";
+    const DIFF_COLOUR: [u8; 3] = [0xFF, 0x00, 0x00];
+
+    pub fn regression_dir() -> path::PathBuf {
+        path::PathBuf::from(std::env::var(REGRESSION_DIR_ENV).unwrap_or(DEFAULT_REGRESSION_DIR.to_string()))
+    }
+
+    pub fn load(directory: &path::Path) -> io::Result<Self> {
+        let manifest: Vec<String> = fs::read_to_string(directory.join(Regression::MANIFEST))?.lines().map(|line| line.to_string()).collect();
+        let tests = Regression::parse(&manifest)?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            manifest,
+            tests,
+        })
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    pub fn parse(manifest: &[String]) -> io::Result<Vec<RegressionTest>> {
+        let mut tests = Vec::new();
+        for (line, text) in manifest.iter().enumerate() {
+            let words: Vec<&str> = text[..text.find('#').unwrap_or(text.len())].split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let error = |message: &str| Regression::invalid(format!("{} {}: {} ('{}')", Regression::MANIFEST, line + 1, message, text));
+
+            if words.len() < 3 {
+                return Err(error("Expected '<rom file> <cartridge type> <frames>'"));
+            }
+            let mut test = RegressionTest {
+                line,
+                rom: words[0].to_string(),
+                cartridge_type: cartridge::CartridgeType::from_str(words[1]).map_err(|_| error("Unknown cartridge type"))?,
+                frames: words[2].parse().map_err(|_| error("Invalid number of frames"))?,
+                inputs: None,
+                frame_hash: None,
+                ram_hash: None,
+            };
+            for option in &words[3..] {
+                let hash = |value: &str| u64::from_str_radix(value, 16).map_err(|_| error("Invalid hash"));
+                match option.split_once('=') {
+                    Some(("inputs", value)) => test.inputs = Some(value.to_string()),
+                    Some(("frame", value)) => test.frame_hash = Some(hash(value)?),
+                    Some(("ram", value)) => test.ram_hash = Some(hash(value)?),
+                    _ => return Err(error(&format!("Unknown option '{}'", option))),
+                }
+            }
+            tests.push(test);
+        }
+        Ok(tests)
+    }
+
+    /// 64 bit FNV-1a.
+    pub fn hash(data: &[u8]) -> u64 {
+        data.iter().fold(0xCBF29CE484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x00000100000001B3))
+    }
+
+    fn run_test(&self, test: &RegressionTest) -> io::Result<Run> {
+        let mut machine = machine::Machine::new(machine::MachineConfig::default());
+        machine.insert_cartridge(&fs::read(self.directory.join(&test.rom))?, &test.cartridge_type);
+
+        let checksum = state::cartridge_checksum(machine.core());
+        let mut inputs = match &test.inputs {
+            Some(inputs) => Some(movie::Movie::playback(&self.directory.join(inputs).to_string_lossy(), checksum)?),
+            None => None,
+        };
+
+        for frame in 0..test.frames {
+            if !machine.run_frame() {
+                return Err(Regression::invalid(format!("Frame {} didn't finish", frame + 1)));
+            }
+            // Inputs change at the end of each frame (as when recorded).
+            if let Some(movie) = inputs.as_mut() {
+                let mut input = machine.input();
+                movie.frame(&mut input);
+                machine.set_input(input);
+            }
+        }
+
+        Ok(Run {
+            frame_rgb24: machine.frame_rgb24(),
+            frame_hash: Regression::hash(&machine.frame_palette_indices()),
+            ram_hash: Regression::hash(&machine.ram()),
+        })
+    }
+
+    fn image_path(&self, directory: &str, test: &RegressionTest) -> path::PathBuf {
+        self.directory.join(directory).join(format!("{}.png", test.rom))
+    }
+
+    /// The golden frame, the frame and their differences, side by side.
+    fn diff_image(golden: &[u8], frame: &[u8]) -> Vec<u8> {
+        let width = 3 * machine::Machine::FRAME_WIDTH as usize;
+        let mut image = Vec::with_capacity(3 * frame.len());
+        for (golden_line, line) in golden.chunks(width).zip(frame.chunks(width)) {
+            image.extend_from_slice(golden_line);
+            image.extend_from_slice(line);
+            for (golden_pixel, pixel) in golden_line.chunks(3).zip(line.chunks(3)) {
+                // Dim the matching pixels.
+                if golden_pixel == pixel {
+                    image.extend(pixel.iter().map(|value| value / 4));
+                } else {
+                    image.extend_from_slice(&Regression::DIFF_COLOUR);
+                }
+            }
+        }
+        image
+    }
+
+    fn write_failure(&self, test: &RegressionTest, run: &Run) -> io::Result<path::PathBuf> {
+        let (width, height) = (machine::Machine::FRAME_WIDTH, machine::Machine::FRAME_HEIGHT);
+        let golden = fs::read(self.image_path(Regression::GOLDEN_DIR, test)).and_then(|image| png::read_png(&image));
+        let image = match golden {
+            Ok((golden, golden_width, golden_height)) if (golden_width, golden_height) == (width, height) => Regression::diff_image(&golden, &run.frame_rgb24),
+            // Without the golden frame, just the frame.
+            _ => run.frame_rgb24.clone(),
+        };
+        let image_width = (image.len() / (3 * height as usize)) as u16;
+
+        let file_name = self.image_path(Regression::FAILURES_DIR, test);
+        fs::create_dir_all(self.directory.join(Regression::FAILURES_DIR))?;
+        png::save_png(&file_name.to_string_lossy(), &image, image_width, height)?;
+        Ok(file_name)
+    }
+
+    fn check(&self, test: &RegressionTest, run: &Run) -> io::Result<Outcome> {
+        let mut mismatches = Vec::new();
+        for (name, golden, hash) in [("frame", test.frame_hash, run.frame_hash), ("ram", test.ram_hash, run.ram_hash)] {
+            match golden {
+                Some(golden) if golden == hash => {}
+                Some(golden) => mismatches.push(format!("{} hash {:016X} (expected {:016X})", name, hash, golden)),
+                None => mismatches.push(format!("no golden {} hash", name)),
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(Outcome::Pass)
+        } else {
+            let file_name = self.write_failure(test, run)?;
+            Ok(Outcome::Fail(format!("{}, see {}", mismatches.join(", "), file_name.display())))
+        }
+    }
+
+    fn update(&mut self, index: usize, run: &Run) -> io::Result<Outcome> {
+        let test = &mut self.tests[index];
+        test.frame_hash = Some(run.frame_hash);
+        test.ram_hash = Some(run.ram_hash);
+
+        let mut line = format!("{} {} {}", test.rom, test.cartridge_type, test.frames);
+        if let Some(inputs) = &test.inputs {
+            line += &format!(" inputs={}", inputs);
+        }
+        line += &format!(" frame={:016X} ram={:016X}", run.frame_hash, run.ram_hash);
+        if let Some(comment) = self.manifest[test.line].find('#').map(|start| &self.manifest[test.line][start..]) {
+            line += &format!(" {}", comment);
+        }
+        self.manifest[test.line] = line;
+
+        let test = &self.tests[index];
+        fs::create_dir_all(self.directory.join(Regression::GOLDEN_DIR))?;
+        png::save_png(&self.image_path(Regression::GOLDEN_DIR, test).to_string_lossy(), &run.frame_rgb24, machine::Machine::FRAME_WIDTH, machine::Machine::FRAME_HEIGHT)?;
+        Ok(Outcome::Updated)
+    }
+
+    /// Run all of the tests, checking them against (or updating) the golden values.
+    pub fn run(&mut self, update: bool) -> Vec<(String, Outcome)> {
+        let mut outcomes = Vec::new();
+        for index in 0..self.tests.len() {
+            let outcome = match self.run_test(&self.tests[index]) {
+                Ok(run) if update => self.update(index, &run),
+                Ok(run) => self.check(&self.tests[index], &run),
+                Err(e) => Err(e),
+            };
+            outcomes.push((self.tests[index].rom.clone(), outcome.unwrap_or_else(|e| Outcome::Fail(e.to_string()))));
+        }
+
+        if update {
+            let mut manifest = self.manifest.join("\n");
+            manifest.push('\n');
+            if let Err(e) = fs::write(self.directory.join(Regression::MANIFEST), manifest) {
+                outcomes.push((Regression::MANIFEST.to_string(), Outcome::Fail(e.to_string())));
+            }
+        }
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::png;
+    use crate::atari2600::regression;
+    use crate::atari2600::testing;
+    use std::fs;
+
+    #[test]
+    fn test_regression() {
+        // Frame loop: VSYNC for 3 lines, VBLANK off, INC $80, LDA $80, STA COLUBK, 200 lines of WSYNC, JMP $F000
+        let rom = testing::rom(
+            &[
+                0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xE6, 0x80, 0xA5, 0x80, 0x85, 0x09, 0xA2, 0xC8, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00, 0xF0,
+            ],
+            0x00,
+        );
+
+        let directory = testing::temp_path("regression_test");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("colours.bin"), &rom).unwrap();
+        fs::write(directory.join("counter.bin"), &rom).unwrap();
+        fs::write(directory.join(regression::Regression::MANIFEST), "# Test ROMs\ncolours.bin Default 30 # Background colour\n\ncounter.bin Default 5\n").unwrap();
+
+        let mut regression = regression::Regression::load(&directory).unwrap();
+        let outcomes = regression.run(false);
+        assert!(matches!(outcomes[0].1, regression::Outcome::Fail(_)), "{:?}", outcomes);
+
+        assert!(regression.run(true).iter().all(|(_, outcome)| *outcome == regression::Outcome::Updated));
+        let manifest = fs::read_to_string(directory.join(regression::Regression::MANIFEST)).unwrap();
+        assert!(manifest.starts_with("# Test ROMs\ncolours.bin Default 30 frame="), "{}", manifest);
+        assert!(manifest.contains(" # Background colour\n\ncounter.bin Default 5 frame="), "{}", manifest);
+
+        let mut regression = regression::Regression::load(&directory).unwrap();
+        assert!(regression.run(false).iter().all(|(_, outcome)| *outcome == regression::Outcome::Pass));
+
+        // A different frame (background colour) and RAM (counter) for the same golden values.
+        regression.tests[0].frames = 32;
+        let outcomes = regression.run(false);
+        match &outcomes[0].1 {
+            regression::Outcome::Fail(message) => assert!(message.starts_with("frame hash") && message.contains("ram hash"), "{}", message),
+            outcome => panic!("Expected a failure, got {:?}", outcome),
+        }
+        assert_eq!(outcomes[1].1, regression::Outcome::Pass);
+        let (image, width, height) = png::read_png(&fs::read(directory.join("failures").join("colours.bin.png")).unwrap()).unwrap();
+        assert_eq!((width, height), (3 * 160, 220));
+        // The difference is highlighted.
+        let pixel = 3 * (100 * 480 + 2 * 160 + 80);
+        assert_eq!(image[pixel..pixel + 3], [0xFF, 0x00, 0x00]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_regression_corpus() {
+        let directory = regression::Regression::regression_dir();
+        if !directory.join(regression::Regression::MANIFEST).is_file() {
+            println!("Skipping regression tests, no manifest found in {:?} (set {}).", directory, regression::REGRESSION_DIR_ENV);
+            return;
+        }
+
+        let update = std::env::var(regression::REGRESSION_UPDATE_ENV).is_ok();
+        let outcomes = regression::Regression::load(&directory).unwrap().run(update);
+        for (rom, outcome) in &outcomes {
+            println!("{}: {:?}", rom, outcome);
+        }
+        let failures: Vec<&String> = outcomes.iter().filter(|(_, outcome)| matches!(outcome, regression::Outcome::Fail(_))).map(|(rom, _)| rom).collect();
+        assert!(failures.is_empty(), "Failing ROMs: {:?}", failures);
+    }
+}
@@ -0,0 +1,164 @@
+//! Rewind, a ring buffer of whole machine snapshots (see 'state') taken each frame.
+//!
+//! Consecutive frames differ in very few bytes, so only the newest snapshot is kept as is, each older one is stored
+//! as the run length encoded XOR against the snapshot after it.  Stepping back a frame then undoes one delta, and the
+//! oldest deltas are discarded to keep within the memory budget (as nothing depends on them).
+
+use std::collections::VecDeque;
+
+pub struct Rewind {
+    budget: usize,
+    latest: Option<Vec<u8>>,
+    // Oldest first, each is 'encode(older, newer)'.
+    deltas: VecDeque<Vec<u8>>,
+    deltas_size: usize,
+}
+
+impl Rewind {
+    pub const DEFAULT_BUDGET_MB: usize = 32;
+
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            latest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+        }
+    }
+
+    /// Number of frames that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| 1 + self.deltas.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, |latest| latest.len()) + self.deltas_size
+    }
+
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(previous) = self.latest.replace(snapshot) {
+            let delta = Rewind::encode(&previous, self.latest.as_ref().unwrap());
+            self.deltas_size += delta.len();
+            self.deltas.push_back(delta);
+        }
+
+        while self.memory_used() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.deltas_size -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Remove and return the newest snapshot (the one before it becomes the newest).
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.deltas_size -= delta.len();
+            self.latest = Some(Rewind::decode(&delta, &latest));
+        }
+        Some(latest)
+    }
+
+    fn write_count(output: &mut Vec<u8>, mut count: usize) {
+        // LEB128, 7 bits per byte with the top bit set on all but the last.
+        while count >= 0x80 {
+            output.push((count as u8) | 0x80);
+            count >>= 7;
+        }
+        output.push(count as u8);
+    }
+
+    fn read_count(input: &[u8], position: &mut usize) -> usize {
+        let mut count = 0;
+        let mut shift = 0;
+        while let Some(byte) = input.get(*position) {
+            *position += 1;
+            count |= ((byte & 0x7F) as usize) << shift;
+            if 0 == byte & 0x80 {
+                break;
+            }
+            shift += 7;
+        }
+        count
+    }
+
+    /// Encode 'older' relative to 'newer', as its length then (zero run, literal run, literals) of the XOR.
+    pub fn encode(older: &[u8], newer: &[u8]) -> Vec<u8> {
+        let xor: Vec<u8> = older.iter().enumerate().map(|(i, byte)| byte ^ newer.get(i).copied().unwrap_or(0)).collect();
+
+        let mut output = Vec::new();
+        Rewind::write_count(&mut output, older.len());
+        let mut position = 0;
+        while position < xor.len() {
+            let zeros = xor[position..].iter().take_while(|byte| 0 == **byte).count();
+            let literals = xor[position + zeros..].iter().take_while(|byte| 0 != **byte).count();
+            Rewind::write_count(&mut output, zeros);
+            Rewind::write_count(&mut output, literals);
+            output.extend_from_slice(&xor[position + zeros..position + zeros + literals]);
+            position += zeros + literals;
+        }
+        output
+    }
+
+    pub fn decode(delta: &[u8], newer: &[u8]) -> Vec<u8> {
+        let mut position = 0;
+        let length = Rewind::read_count(delta, &mut position);
+        let mut older: Vec<u8> = (0..length).map(|i| newer.get(i).copied().unwrap_or(0)).collect();
+
+        let mut index = 0;
+        while position < delta.len() {
+            index += Rewind::read_count(delta, &mut position);
+            let literals = Rewind::read_count(delta, &mut position);
+            for byte in &delta[position..position + literals] {
+                older[index] ^= byte;
+                index += 1;
+            }
+            position += literals;
+        }
+        older
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::rewind;
+
+    #[test]
+    fn test_encode_decode() {
+        let newer = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        for older in [vec![0, 1, 2, 3, 4, 5, 6, 7], vec![9, 1, 2, 3, 4, 5, 8, 9], vec![1; 300], vec![7, 1]] {
+            assert_eq!(rewind::Rewind::decode(&rewind::Rewind::encode(&older, &newer), &newer), older);
+        }
+        // Identical snapshots only need the length and a single (empty) run.
+        assert_eq!(rewind::Rewind::encode(&newer, &newer), vec![8, 8, 0]);
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let frame = |n: u8| {
+            let mut snapshot = vec![0_u8; 1000];
+            snapshot[n as usize] = n;
+            snapshot
+        };
+
+        let mut rewind = rewind::Rewind::new(1100);
+        for n in 1..=20 {
+            rewind.push(frame(n));
+        }
+        assert!(rewind.memory_used() <= 1100);
+        assert!(rewind.len() > 2 && rewind.len() < 20, "{}", rewind.len());
+
+        // Newest first, until the budget limited start.
+        let frames = rewind.len() as u8;
+        for n in (21 - frames..=20).rev() {
+            assert_eq!(rewind.pop(), Some(frame(n)));
+        }
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.pop(), None);
+    }
+}
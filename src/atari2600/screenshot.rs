@@ -0,0 +1,118 @@
+//! Screenshots, exact captures of frames as PNG images (see 'png').
+//!
+//! At the native 160 pixel width, or optionally stretched by 'PIXEL_WIDTH_STRETCH' (as displayed).  They're taken at
+//! the end of a frame (so the frame is complete), either on request (the hotkey) or of given frames (counted from
+//! power-on), and are named '<cartridge>.<frame>.png'.
+
+use super::graphics::stella;
+use super::machine;
+use super::png;
+
+use std::io;
+use std::path;
+
+pub struct Screenshots {
+    cartridge_name: String,
+    frames: Vec<u64>,
+    stretch: bool,
+    requested: bool,
+}
+
+impl Screenshots {
+    pub fn new(cartridge_name: &str) -> Self {
+        Self {
+            cartridge_name: cartridge_name.to_string(),
+            frames: Vec::new(),
+            stretch: false,
+            requested: false,
+        }
+    }
+
+    /// Frames to capture.
+    pub fn set_frames(&mut self, frames: &[u64]) {
+        self.frames = frames.to_vec();
+    }
+
+    pub fn set_stretch(&mut self, stretch: bool) {
+        self.stretch = stretch;
+    }
+
+    /// Capture the frame being drawn (once it's complete).
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn file_name(&self, frame: u64) -> String {
+        path::Path::new(&self.cartridge_name).with_extension(format!("{}.png", frame)).to_string_lossy().to_string()
+    }
+
+    /// Repeat each pixel 'factor' times horizontally, 'rgb' is 3 bytes per pixel.
+    pub fn stretch_width(rgb: &[u8], factor: u8) -> Vec<u8> {
+        rgb.chunks(3).flat_map(|pixel| pixel.repeat(factor as usize)).collect()
+    }
+
+    pub fn save(machine: &machine::Machine, file_name: &str, stretch: bool) -> io::Result<()> {
+        let factor = if stretch { stella::Constants::PIXEL_WIDTH_STRETCH } else { 1 };
+        let rgb = Screenshots::stretch_width(&machine.frame_rgb24(), factor);
+        png::save_png(file_name, &rgb, factor as u16 * machine::Machine::FRAME_WIDTH, machine::Machine::FRAME_HEIGHT)
+    }
+
+    /// At the end of each frame ('frame' counts from 1), capture it if requested.
+    pub fn end_of_frame(&mut self, machine: &machine::Machine, frame: u64) {
+        if self.requested || self.frames.contains(&frame) {
+            self.requested = false;
+            let file_name = self.file_name(frame);
+            match Screenshots::save(machine, &file_name, self.stretch) {
+                Ok(()) => println!("Screenshot saved to: {}", file_name),
+                Err(e) => println!("Unable to save the screenshot to {}: {}", file_name, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::png;
+    use crate::atari2600::screenshot;
+    use crate::atari2600::testing;
+    use std::fs;
+
+    #[test]
+    fn test_screenshot() {
+        assert_eq!(screenshot::Screenshots::stretch_width(&[1, 2, 3, 4, 5, 6], 2), vec![1, 2, 3, 1, 2, 3, 4, 5, 6, 4, 5, 6]);
+
+        // Frame loop: VSYNC for 3 lines, VBLANK off, LDA #$1E, STA COLUBK, 200 lines of WSYNC, JMP $F000
+        let mut machine = testing::machine(&testing::rom(
+            &[
+                0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00, 0x85, 0x01, 0xA9, 0x1E, 0x85, 0x09, 0xA2, 0xC8, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00, 0xF0,
+            ],
+            0x00,
+        ));
+
+        let cartridge_name = testing::temp_path("screenshot_test.bin").to_string_lossy().to_string();
+        let mut screenshots = screenshot::Screenshots::new(&cartridge_name);
+        screenshots.set_frames(&[2]);
+        screenshots.set_stretch(true);
+
+        // Frame 2 is given, frame 4 is requested (part way through it).
+        let mut expected = Vec::new();
+        for frame in 1..=4 {
+            if frame == 4 {
+                screenshots.request();
+            }
+            machine.run_frame();
+            screenshots.end_of_frame(&machine, frame);
+            expected.push(screenshot::Screenshots::stretch_width(&machine.frame_rgb24(), 2));
+        }
+
+        assert!(!fs::exists(screenshots.file_name(3)).unwrap());
+        for frame in [2, 4] {
+            let file_name = screenshots.file_name(frame);
+            assert!(file_name.ends_with(&format!("_screenshot_test.{}.png", frame)));
+            let (rgb, width, height) = png::read_png(&fs::read(&file_name).unwrap()).unwrap();
+            fs::remove_file(&file_name).unwrap();
+            assert_eq!((width, height), (320, 220));
+            assert_eq!(rgb, expected[frame as usize - 1]);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Save states, a snapshot of the whole machine.
+//!
+//! The file is a small header (magic, format version and a checksum of the cartridge ROM) followed by each
+//! component's state in turn: CPU registers, clock, joystick inputs, RIOT, Stella (TIA) and the cartridge.  Each
+//! component writes/restores its own fields via 'save_state'/'restore_state', values are little endian and vectors are
+//! prefixed with their (u32) length.
+//!
+//! Increment 'VERSION' whenever a component's layout changes, older states are then rejected rather than misread.
+
+use super::cpu;
+
+use std::fs;
+use std::io;
+
+/// Serialise values into a save state.
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_bytes(&mut self, values: &[u8]) {
+        self.write_u32(values.len() as u32);
+        self.data.extend_from_slice(values);
+    }
+
+    pub fn write_bools(&mut self, values: &[bool]) {
+        self.write_u32(values.len() as u32);
+        self.data.extend(values.iter().map(|value| *value as u8));
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Read values back from a save state (in the order they were written).
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn invalid(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+    }
+
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.data.len()).ok_or_else(|| StateReader::invalid("Save state is truncated"))?;
+        let values = &self.data[self.position..end];
+        self.position = end;
+        Ok(values)
+    }
+
+    pub fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bool(&mut self) -> io::Result<bool> {
+        Ok(0 != self.read_u8()?)
+    }
+
+    pub fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let length = self.read_u32()? as usize;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    pub fn read_bools(&mut self) -> io::Result<Vec<bool>> {
+        Ok(self.read_bytes()?.iter().map(|value| 0 != *value).collect())
+    }
+
+    /// Read a vector, that must be the same length as 'expected' (ie RAM), into it.
+    pub fn read_bytes_into(&mut self, expected: &mut [u8]) -> io::Result<()> {
+        let values = self.read_bytes()?;
+        if values.len() != expected.len() {
+            return Err(StateReader::invalid("Save state size mismatch"));
+        }
+        expected.copy_from_slice(&values);
+        Ok(())
+    }
+
+    pub fn read_bools_into(&mut self, expected: &mut [bool]) -> io::Result<()> {
+        let values = self.read_bools()?;
+        if values.len() != expected.len() {
+            return Err(StateReader::invalid("Save state size mismatch"));
+        }
+        expected.copy_from_slice(&values);
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+pub const MAGIC: &[u8; 4] = b"RA26";
+pub const VERSION: u32 = 2;
+
+/// FNV-1a hash of the cartridge banks, so a state isn't loaded against a different cartridge.
+pub fn cartridge_checksum(core: &cpu::core::Core) -> u32 {
+    let cartridge = core.memory.cartridge();
+    (0..cartridge.bank_count()).filter_map(|bank| cartridge.bank_data(bank)).flatten().fold(0x811C9DC5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+/// Snapshot the whole machine.
+pub fn save(core: &cpu::core::Core) -> Vec<u8> {
+    let mut state = StateWriter::new();
+    state.data.extend_from_slice(MAGIC);
+    state.write_u32(VERSION);
+    state.write_u32(cartridge_checksum(core));
+    core.save_state(&mut state);
+    state.into_bytes()
+}
+
+fn restore_sections(core: &mut cpu::core::Core, data: &[u8]) -> io::Result<()> {
+    let mut state = StateReader::new(data);
+    if state.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(StateReader::invalid("Not a save state"));
+    }
+    let version = state.read_u32()?;
+    if version != VERSION {
+        return Err(StateReader::invalid(&format!("Unsupported save state version {} (expected {})", version, VERSION)));
+    }
+    if state.read_u32()? != cartridge_checksum(core) {
+        return Err(StateReader::invalid("Save state is for a different cartridge"));
+    }
+
+    core.restore_state(&mut state)?;
+    if !state.is_finished() {
+        return Err(StateReader::invalid("Unexpected data at the end of the save state"));
+    }
+    Ok(())
+}
+
+/// Restore the whole machine from a snapshot, the machine is left unchanged if the snapshot is invalid.
+pub fn restore(core: &mut cpu::core::Core, data: &[u8]) -> io::Result<()> {
+    let current = save(core);
+    restore_sections(core, data).inspect_err(|_| {
+        restore_sections(core, &current).expect("Restoring the current state should always succeed");
+    })
+}
+
+pub fn save_file(core: &cpu::core::Core, file_name: &str) -> io::Result<()> {
+    fs::write(file_name, save(core))
+}
+
+pub fn load_file(core: &mut cpu::core::Core, file_name: &str) -> io::Result<()> {
+    restore(core, &fs::read(file_name)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::cpu::core;
+    use crate::atari2600::state;
+    use crate::atari2600::testing;
+
+    #[test]
+    fn test_save_restore() {
+        // INC $80, STA WSYNC, LDA $80, STA COLUBK, STA GRP0, STA RESP0, STA AUDV0, STA TIM64T, STA VSYNC, JMP $F000
+        let rom = testing::rom(&[0xE6, 0x80, 0x85, 0x02, 0xA5, 0x80, 0x85, 0x09, 0x85, 0x1B, 0x85, 0x10, 0x85, 0x19, 0x8D, 0x96, 0x02, 0x85, 0x00, 0x4C, 0x00, 0xF0], 0x00);
+        let mut core = testing::machine(&rom).into_core();
+
+        let run = |core: &mut core::Core| {
+            for _ in 0..1000 {
+                core.step(false, false);
+                core.memory.stella.step_tia_sound(&core.clock);
+            }
+        };
+
+        run(&mut core);
+        let snapshot = state::save(&core);
+        run(&mut core);
+        let expected = state::save(&core);
+        assert_ne!(snapshot, expected);
+
+        state::restore(&mut core, &snapshot).unwrap();
+        assert_eq!(state::save(&core), snapshot);
+        run(&mut core);
+        assert_eq!(state::save(&core), expected);
+
+        // Invalid states are rejected, leaving the machine unchanged.
+        assert!(state::restore(&mut core, &snapshot[..snapshot.len() - 1]).is_err());
+        assert!(state::restore(&mut core, b"not a state").is_err());
+        let mut other_version = snapshot.clone();
+        other_version[4] = 0xFF;
+        assert!(state::restore(&mut core, &other_version).is_err());
+        let mut other_cartridge = snapshot.clone();
+        other_cartridge[8] ^= 0xFF;
+        assert!(state::restore(&mut core, &other_cartridge).is_err());
+        assert_eq!(state::save(&core), expected);
+    }
+}
@@ -0,0 +1,211 @@
+//! DASM symbol ('.sym') and listing ('.lst') files, for symbolic debugging.
+//!
+//! The '.sym' file is the complete symbol table ('name value [flags]' per line).  The '.lst' file only provides
+//! labels, found as the first word of the source (after the line number, address and bytes) when it isn't a 6502
+//! mnemonic, directive or macro, ie 'MainLoop' or 'MainLoop lda #0' but not 'lda #0'.
+//!
+//! DASM doesn't distinguish labels from other equates, so when several names share a value the first loaded is used
+//! for that address.
+
+use super::cpu::disasm;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path;
+
+#[derive(Default)]
+pub struct Symbols {
+    names: HashMap<u16, String>,
+    addresses: HashMap<String, u16>,
+}
+
+impl Symbols {
+    const SYM_EXTENSION: &'static str = "sym";
+    const LST_EXTENSION: &'static str = "lst";
+
+    // Directives that could otherwise be mistaken for labels in a listing.
+    const LST_DIRECTIVES: [&'static str; 34] = [
+        "PROCESSOR",
+        "SEG",
+        "SEG.U",
+        "ORG",
+        "RORG",
+        "REND",
+        "ECHO",
+        "INCLUDE",
+        "INCBIN",
+        "INCDIR",
+        "DC",
+        "DC.B",
+        "DC.W",
+        "DS",
+        "DS.B",
+        "DS.W",
+        "DV",
+        "BYTE",
+        "WORD",
+        "HEX",
+        "ALIGN",
+        "REPEAT",
+        "REPEND",
+        "IF",
+        "IFCONST",
+        "IFNCONST",
+        "ELSE",
+        "ENDIF",
+        "EIF",
+        "MAC",
+        "MACRO",
+        "ENDM",
+        "MEXIT",
+        "SUBROUTINE",
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the '.sym'/'.lst' files alongside the cartridge (ie 'game.bin' -> 'game.sym', 'game.lst') if they exist.
+    pub fn for_cartridge(cartridge_name: &str) -> Self {
+        let mut symbols = Symbols::new();
+        for extension in [Symbols::SYM_EXTENSION, Symbols::LST_EXTENSION] {
+            let file_name = path::Path::new(cartridge_name).with_extension(extension);
+            if file_name.is_file() {
+                match symbols.load(&file_name.to_string_lossy()) {
+                    Ok(()) => println!("Symbols loaded from: {}", file_name.display()),
+                    Err(e) => println!("Unable to load symbols from {}: {}", file_name.display(), e),
+                }
+            }
+        }
+        symbols
+    }
+
+    /// Load a '.lst' (by extension) or '.sym' file.
+    pub fn load(&mut self, file_name: &str) -> io::Result<()> {
+        let text = fs::read_to_string(file_name)?;
+        match path::Path::new(file_name).extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case(Symbols::LST_EXTENSION) => self.parse_lst(&text),
+            _ => self.parse_sym(&text),
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, name: &str, address: u16) {
+        self.names.entry(address).or_insert_with(|| name.to_string());
+        self.addresses.entry(name.to_string()).or_insert(address);
+    }
+
+    pub fn parse_sym(&mut self, text: &str) {
+        for line in text.lines().filter(|line| !line.starts_with("---")) {
+            let mut words = line.split_whitespace();
+            if let (Some(name), Some(value)) = (words.next(), words.next()) {
+                // Ignore string (quoted) and out of range values.
+                if let Ok(address) = u16::from_str_radix(value, 16) {
+                    self.add(name, address);
+                }
+            }
+        }
+    }
+
+    /// The source words (after the line number, address and bytes) and the address of each listing line.
+    fn lst_source(line: &str) -> Option<(u16, Vec<&str>)> {
+        let mut words = line.split_whitespace().peekable();
+        let address = match (words.next().map(|number| number.parse::<u32>()), words.next().map(|address| u16::from_str_radix(address.trim_start_matches('U'), 16))) {
+            (Some(Ok(_)), Some(Ok(address))) => address,
+            _ => return None,
+        };
+
+        while words.next_if(|word| word.len() == 2 && u8::from_str_radix(word, 16).is_ok()).is_some() {}
+        Some((address, words.collect()))
+    }
+
+    pub fn parse_lst(&mut self, text: &str) {
+        let is_directive = |word: &str| Symbols::LST_DIRECTIVES.iter().any(|directive| directive.eq_ignore_ascii_case(word));
+        let macros: Vec<&str> = text
+            .lines()
+            .filter_map(Symbols::lst_source)
+            .filter_map(|(_, words)| match words.as_slice() {
+                [mac, name, ..] if mac.eq_ignore_ascii_case("MAC") || mac.eq_ignore_ascii_case("MACRO") => Some(*name),
+                _ => None,
+            })
+            .collect();
+
+        for (address, words) in text.lines().filter_map(Symbols::lst_source) {
+            let mut words = words.into_iter();
+            if let Some(word) = words.next() {
+                let label = word.trim_end_matches(':');
+                let is_identifier = label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.') && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+                let is_keyword = disasm::is_mnemonic(label) || is_directive(label) || macros.contains(&label);
+                if is_identifier && !is_keyword && words.next().is_none_or(|next| !next.starts_with(['=', '.']) && !next.eq_ignore_ascii_case("equ")) {
+                    self.add(label, address);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(|name| name.as_str())
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// The symbol for the address, otherwise the address as hex.
+    pub fn name_or_hex(&self, address: u16) -> String {
+        self.name(address).map_or(format!("{:X}", address), |name| name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atari2600::symbols;
+
+    #[test]
+    fn test_parse_sym() {
+        let mut symbols = symbols::Symbols::new();
+        symbols.parse_sym(
+            "--- Symbol List (sorted by symbol)\n\
+             MainLoop                 f004                  (R )\n\
+             PlayerX                  0080                  (R )\n\
+             Title                    \"hello\"\n\
+             VSYNC                    0000\n\
+             --- End of Symbol List.\n",
+        );
+
+        assert_eq!(symbols.address("MainLoop"), Some(0xF004));
+        assert_eq!(symbols.name(0x80), Some("PlayerX"));
+        assert_eq!(symbols.name(0x00), Some("VSYNC"));
+        assert_eq!(symbols.address("Title"), None);
+        assert_eq!(symbols.name_or_hex(0xF000), "F000");
+    }
+
+    #[test]
+    fn test_parse_lst() {
+        let mut symbols = symbols::Symbols::new();
+        symbols.parse_lst(
+            "------- FILE game.asm LEVEL 1 PASS 2\n\
+             \x20     1  0000 ????\t\t\t\t      processor\t6502\n\
+             \x20    10 U0080\t\t\t\t      seg.u\tvars\n\
+             \x20    11 U0080\t\t\t       PlayerX\tds\t1\n\
+             \x20    12  f000\t\t\t\t      seg\tcode\n\
+             \x20    13  f000\t\t\t       Start\n\
+             \x20    14  f000\t\t       a9 00\t      lda\t#0\n\
+             \x20    15  f002\t\t       85 80\t   MainLoop sta\tPlayerX\n\
+             \x20    16  f004\t\t       ea\t\t      nop\n\
+             \x20    17  f005\t\t\t\t      endm\n",
+        );
+
+        assert_eq!(symbols.address("Start"), Some(0xF000));
+        assert_eq!(symbols.address("MainLoop"), Some(0xF002));
+        assert_eq!(symbols.address("PlayerX"), Some(0x0080));
+        assert_eq!(symbols.name(0xF004), None);
+        assert_eq!(symbols.name(0xF005), None);
+        assert_eq!(symbols.address("seg.u"), None);
+    }
+}
@@ -0,0 +1,28 @@
+//! Shared test setup: 4K ROMs from a few bytes of test code, machines running them (built in memory) and temporary
+//! file names.
+
+use super::machine;
+use super::memory::cartridge;
+
+use std::path;
+
+/// The code padded (with 'fill') to a 4K ROM, with the reset vector at its start (0xF000).
+pub fn rom(code: &[u8], fill: u8) -> Vec<u8> {
+    let mut rom = code.to_vec();
+    rom.resize(0x1000, fill);
+    rom[0xFFC] = 0x00;
+    rom[0xFFD] = 0xF0;
+    rom
+}
+
+/// A machine (default configuration) with the ROM inserted.
+pub fn machine(rom: &[u8]) -> machine::Machine {
+    let mut machine = machine::Machine::new(machine::MachineConfig::default());
+    machine.insert_cartridge(rom, &cartridge::CartridgeType::Default);
+    machine
+}
+
+/// A path in the temporary directory, unique to the test process (so concurrent test runs don't share files).
+pub fn temp_path(name: &str) -> path::PathBuf {
+    std::env::temp_dir().join(format!("rusted_atari2600_{}_{}", std::process::id(), name))
+}
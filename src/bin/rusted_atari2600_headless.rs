@@ -0,0 +1,109 @@
+//! Headless runner (no SDL), builds without the 'sdl' feature, ie for regression tests in CI.
+//!
+//! Runs the cartridge for a number of frames and/or until the stop clock, optionally replaying a movie, then writes
+//! the requested outputs (see 'atari2600::headless').  The full binary ('rusted_atari2600 --headless') also supports
+//! the tools (debugger, profiler, traces, etc.), screenshots and capture.
+
+use argh::FromArgs;
+use rusted_atari2600::atari2600;
+// Only used by the library, dependencies are shared with the binary.
+use bitfield as _;
+use hound as _;
+#[cfg(feature = "sdl")]
+use sdl2 as _;
+use std::str::FromStr;
+use strum as _;
+use strum_macros as _;
+
+/// Rusty Atari 2600 Emulator (headless).
+#[derive(FromArgs)]
+struct HeadlessArgs {
+    /// number of clock cycles to stop the emulator
+    #[argh(option, short = 's')]
+    stop_clock: Option<u64>,
+
+    /// number of frames to run
+    #[argh(option)]
+    frames: Option<u64>,
+
+    /// write the final frame to the file, as a PPM image
+    #[argh(option)]
+    dump_frame: Option<String>,
+
+    /// write all of the audio to the file, as a WAV
+    #[argh(option)]
+    dump_audio: Option<String>,
+
+    /// write the console RAM (0x80-0xFF) to the file
+    #[argh(option)]
+    dump_ram: Option<String>,
+
+    /// replay a movie (the recorded inputs of each frame from power-on)
+    #[argh(option, short = 'r')]
+    replay_file: Option<String>,
+
+    /// use PAL palette (instead of NTSC)
+    #[argh(switch, short = 'p')]
+    pal_palette: bool,
+
+    /// name of cartridge to run
+    #[argh(positional)]
+    cartridge_name: String,
+
+    /// cartridge type (see 'rusted_atari2600 --help' for the available options)
+    #[argh(option, short = 'c', default = "atari2600::memory::cartridge::CartridgeType::Default", from_str_fn(parse_cartridge))]
+    cartridge_type: atari2600::memory::cartridge::CartridgeType,
+}
+
+fn parse_cartridge(value: &str) -> Result<atari2600::memory::cartridge::CartridgeType, String> {
+    atari2600::memory::cartridge::CartridgeType::from_str(value).map_err(|x| format!("Supplied {}. Error: {}", value, x))
+}
+
+fn main() {
+    let args: HeadlessArgs = argh::from_env();
+
+    if args.frames.is_none() && args.stop_clock.is_none() {
+        println!("Headless mode needs a number of frames (--frames) and/or a stop clock (-s)");
+        return;
+    }
+
+    let mut machine = atari2600::machine::Machine::new(atari2600::machine::MachineConfig {
+        debug: false,
+        realtime: false,
+        pal_palette: args.pal_palette,
+    });
+    machine.insert_cartridge(&atari2600::memory::cartridge::read_cartridge(&args.cartridge_name), &args.cartridge_type);
+
+    let mut movie = None;
+    if let Some(replay_file) = &args.replay_file {
+        match atari2600::movie::Movie::playback(replay_file, atari2600::state::cartridge_checksum(machine.core())) {
+            Ok(playback) => {
+                println!("Replaying {} frames from: {}", playback.frame_count(), replay_file);
+                movie = Some(playback);
+            }
+            Err(e) => {
+                println!("Unable to replay {}: {}", replay_file, e);
+                return;
+            }
+        }
+    }
+
+    let headless = atari2600::headless::Headless {
+        frames: args.frames,
+        frame_file: args.dump_frame,
+        audio_file: args.dump_audio,
+        ram_file: args.dump_ram,
+    };
+    let result = headless.run_with(&mut machine, args.stop_clock.unwrap_or(0), |machine| {
+        let end_of_frame = machine.step();
+        if let (true, Some(movie)) = (end_of_frame, movie.as_mut()) {
+            let mut input = machine.input();
+            movie.frame(&mut input);
+            machine.set_input(input);
+        }
+        Some(end_of_frame)
+    });
+    if let Err(e) = result {
+        println!("Unable to write the headless outputs: {}", e);
+    }
+}
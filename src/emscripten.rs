@@ -77,3 +77,65 @@ pub mod emscripten {
         });
     }
 }
+
+// The cartridge (ROM image and type) provided by javascript, see 'display_data'.
+
+use rusted_atari2600::atari2600::memory::cartridge::CartridgeType;
+use std::cell::RefCell;
+use std::str::FromStr;
+
+struct JavaScriptData {
+    pub raw_cart_data: Vec<u8>,
+    pub raw_cart_type: CartridgeType,
+}
+impl JavaScriptData {
+    pub fn new() -> Self {
+        Self {
+            raw_cart_data: Vec::new(),
+            raw_cart_type: CartridgeType::Default,
+        }
+    }
+}
+
+thread_local! {
+    static JAVASCRIPT_DATA_STORE: RefCell<JavaScriptData> = RefCell::new(JavaScriptData::new());
+}
+
+pub fn is_cart_ready() -> bool {
+    let mut is_ready = false;
+    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
+        is_ready = !ref_cell_data.borrow().raw_cart_data.is_empty();
+    });
+    is_ready
+}
+
+pub fn get_cart_type() -> CartridgeType {
+    let mut cart_type = CartridgeType::Default;
+    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
+        cart_type = ref_cell_data.borrow().raw_cart_type;
+    });
+    cart_type
+}
+
+pub fn get_cart_data() -> Vec<u8> {
+    let mut cart_data = Vec::new();
+    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
+        cart_data = ref_cell_data.borrow().raw_cart_data.clone();
+    });
+    cart_data
+}
+
+#[no_mangle]
+pub extern "C" fn display_data(raw_data_ptr: *const u8, raw_data_length: usize, cart_type_char_ptr: *const std::ffi::c_char) {
+    // TODO: Although it's possible there's another way (alternate arguments), I'll just use the unsafe option for now.
+    let v = unsafe { std::slice::from_raw_parts(raw_data_ptr, raw_data_length) };
+    let cart_type_string = unsafe { std::ffi::CStr::from_ptr(cart_type_char_ptr) }.to_str().unwrap();
+    println!("Called from javascript. Rom size: {}, Cartridge Type: {}", v.len(), cart_type_string);
+
+    let cart_type = CartridgeType::from_str(cart_type_string).expect("Couldn't convert from string to CartType.");
+
+    JAVASCRIPT_DATA_STORE.with(|ref_cell_data| {
+        ref_cell_data.borrow_mut().raw_cart_data = v.to_vec();
+        ref_cell_data.borrow_mut().raw_cart_type = cart_type
+    });
+}
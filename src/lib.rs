@@ -0,0 +1,15 @@
+//! Rusty Atari 2600 Emulator (library).
+//!
+//! The emulation, usable from any frontend via 'atari2600::machine::Machine' (see there for the flow).  The SDL
+//! frontend ('atari2600::atari2600::Atari2600') used by the binary and emscripten build is behind the (default) 'sdl'
+//! feature, so headless users can build without SDL ('--no-default-features').
+
+#![allow(dead_code)]
+#![allow(unused_variables)]
+// The components are constructed explicitly (with 'new'), rather than via 'Default'.
+#![allow(clippy::new_without_default)]
+
+// Only used by the binary (for the arguments), dependencies are shared with the library.
+use argh as _;
+
+pub mod atari2600;
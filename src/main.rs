@@ -31,11 +31,14 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-mod atari2600;
-
 use argh::FromArgs;
+use rusted_atari2600::atari2600;
+// Only used by the library, dependencies are shared with the binary.
+use bitfield as _;
+use hound as _;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
+use strum_macros as _;
 
 #[cfg(target_os = "emscripten")]
 pub mod emscripten;
@@ -67,6 +70,86 @@ struct RustAtari2600Args {
     #[argh(option, short = 's')]
     stop_clock: Option<u64>,
 
+    /// compare the instruction dispatch table against decoding each op-code, running until the stop clock (no display/audio)
+    #[argh(switch)]
+    benchmark: bool,
+
+    /// write a CPU trace (a line per instruction) to the file
+    #[argh(option)]
+    trace_file: Option<String>,
+
+    /// address (hex) to start the trace at (default: from reset)
+    #[argh(option, from_str_fn(parse_hex))]
+    trace_start: Option<u16>,
+
+    /// address (hex) to stop the trace after
+    #[argh(option, from_str_fn(parse_hex))]
+    trace_stop: Option<u16>,
+
+    /// start in the interactive debugger (text console prompt)
+    #[argh(switch, short = 'g')]
+    debugger: bool,
+
+    /// set a debugger breakpoint at the address (hex or symbol), can be repeated
+    #[argh(option, short = 'b')]
+    breakpoint: Vec<String>,
+
+    /// load a DASM symbol (.sym) or listing (.lst) file, can be repeated (those alongside the cartridge are loaded automatically)
+    #[argh(option)]
+    symbol_file: Vec<String>,
+
+    /// wait for a GDB remote protocol debugger to attach on the (loopback) TCP port (not with -g/-b)
+    #[argh(option)]
+    gdb_port: Option<u16>,
+
+    /// profile execution (per bank/address counts, cycles and coverage), writing a text report to the file (and CSV to '<file>.csv') at exit
+    #[argh(option)]
+    profile: Option<String>,
+
+    /// load a save state at startup
+    #[argh(option)]
+    load_state: Option<String>,
+
+    /// file used by the save/load state hotkeys (default: the cartridge name with a '.state' extension)
+    #[argh(option)]
+    state_file: Option<String>,
+
+    /// memory (MB) for the rewind snapshots, taken each frame (default 32, 0 disables rewind, not used headless)
+    #[argh(option)]
+    rewind_buffer: Option<usize>,
+
+    /// run without a display or audio (no SDL initialisation), for '--frames' and/or until the stop clock
+    #[argh(switch)]
+    headless: bool,
+
+    /// number of frames to run (headless)
+    #[argh(option)]
+    frames: Option<u64>,
+
+    /// write the final frame to the file, as a PPM image (headless)
+    #[argh(option)]
+    dump_frame: Option<String>,
+
+    /// write all of the audio to the file, as a WAV (headless)
+    #[argh(option)]
+    dump_audio: Option<String>,
+
+    /// write the console RAM (0x80-0xFF) to the file (headless)
+    #[argh(option)]
+    dump_ram: Option<String>,
+
+    /// save the frame (counted from power-on) as a PNG screenshot ('<cartridge>.<frame>.png'), can be repeated
+    #[argh(option)]
+    screenshot: Vec<u64>,
+
+    /// stretch screenshots to the displayed width (2x), rather than the native 160 pixels
+    #[argh(switch)]
+    screenshot_stretch: bool,
+
+    /// capture the video and audio from power-on, to '<capture>.y4m' (YUV4MPEG2) and '<capture>.wav'
+    #[argh(option)]
+    capture: Option<String>,
+
     /// run the emulator in full screen mode.
     #[argh(switch, short = 'f')]
     fullscreen: bool,
@@ -83,17 +166,47 @@ struct RustAtari2600Args {
     #[argh(positional)]
     cartridge_name: String,
 
-    /// replay file
+    /// replay a movie (the recorded inputs of each frame from power-on)
     #[argh(option, short = 'r')]
     replay_file: Option<String>,
 
+    /// record a movie (the inputs of each frame from power-on) to the file, written at exit
+    #[argh(option)]
+    record: Option<String>,
+
     /// cartridge type.  (Specifying an invalid option will display available options).
     #[argh(option, short = 'c', default = "atari2600::memory::cartridge::CartridgeType::Default", from_str_fn(parse_cartridge))]
     cartridge_type: atari2600::memory::cartridge::CartridgeType,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Disassemble(DisassembleArgs),
+}
+
+/// Disassemble a cartridge bank (to stdout), rather than running the cartridge.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "disasm")]
+struct DisassembleArgs {
+    /// bank to disassemble (default 0)
+    #[argh(option, short = 'b', default = "0")]
+    bank: usize,
+
+    /// address (hex) of the start of the bank (default so that the bank ends at 0xFFFF)
+    #[argh(option, short = 'o', from_str_fn(parse_hex))]
+    origin: Option<u16>,
+}
+
+fn parse_hex(value: &str) -> Result<u16, String> {
+    atari2600::debugger::Debugger::parse_value(value)
 }
 
 fn cartridge_type_help_fn() -> String {
-    atari2600::memory::cartridge::CartridgeType::iter().fold("cartridge type: ".to_owned(), |all, value| format!("{} {:?}", all, value))
+    atari2600::memory::cartridge::CartridgeType::iter().fold("cartridge type: ".to_owned(), |all, value| format!("{} {}", all, value))
 }
 
 fn parse_cartridge(value: &str) -> Result<atari2600::memory::cartridge::CartridgeType, String> {
@@ -121,14 +234,159 @@ fn main() {
         println!("{}", full_description_string());
     }
 
-    let mut atari_machine = atari2600::atari2600::Atari2600::new(args.debug, !args.no_delay, args.stop_clock.unwrap_or(0), &args.cartridge_name, &args.cartridge_type, args.fullscreen, args.pal_palette);
+    if let Some(Command::Disassemble(disassemble_args)) = &args.command {
+        if let Err(e) = atari2600::atari2600::Atari2600::disassemble_bank(&args.cartridge_name, &args.cartridge_type, disassemble_args.bank, disassemble_args.origin) {
+            println!("{}", e);
+        }
+        return;
+    }
+
+    if args.benchmark {
+        match args.stop_clock {
+            Some(stop_clock) => atari2600::atari2600::Atari2600::benchmark(&args.cartridge_name, &args.cartridge_type, stop_clock, args.pal_palette),
+            None => println!("Benchmarking requires a stop clock (-s/--stop-clock)"),
+        }
+        return;
+    }
+
+    let mut atari_machine = atari2600::atari2600::Atari2600::new(
+        args.debug,
+        !args.no_delay && !args.headless,
+        args.stop_clock.unwrap_or(0),
+        &args.cartridge_name,
+        &args.cartridge_type,
+        args.fullscreen,
+        args.pal_palette,
+    );
+
+    let mut symbols = atari2600::symbols::Symbols::for_cartridge(&args.cartridge_name);
+    for symbol_file in &args.symbol_file {
+        if let Err(e) = symbols.load(symbol_file) {
+            println!("Unable to load symbols from {}: {}", symbol_file, e);
+            return;
+        }
+    }
+
+    if args.gdb_port.is_some() && (args.debugger || !args.breakpoint.is_empty()) {
+        // Both would stop on (and take) the watchpoint hits.
+        println!("The GDB stub (--gdb-port) can't be used with the console debugger (-g/-b)");
+        return;
+    }
+
+    if args.debugger || !args.breakpoint.is_empty() {
+        let mut debugger = atari2600::debugger::Debugger::new(args.debugger);
+        for breakpoint in &args.breakpoint {
+            match atari2600::debugger::Debugger::parse_address(breakpoint, &symbols) {
+                Ok(address) => debugger.add_breakpoint(address),
+                Err(e) => {
+                    println!("Invalid breakpoint: {}", e);
+                    return;
+                }
+            }
+        }
+        atari_machine.set_debugger(debugger);
+    }
+    atari_machine.set_symbols(symbols);
+
+    if let Some(profile) = &args.profile {
+        atari_machine.set_profiler(atari2600::profiler::Profiler::new(profile));
+    }
+
+    if let Some(port) = args.gdb_port {
+        let gdb_stub = atari2600::gdb::GdbStub::listen(port).and_then(|listener| {
+            println!("Waiting for GDB to attach on {}", listener.local_addr()?);
+            atari2600::gdb::GdbStub::accept(&listener)
+        });
+        match gdb_stub {
+            Ok(gdb_stub) => atari_machine.set_gdb_stub(gdb_stub),
+            Err(e) => {
+                println!("Unable to start the GDB stub on port {}: {}", port, e);
+                return;
+            }
+        }
+    }
+
+    if let Some(state_file) = &args.state_file {
+        atari_machine.set_state_file(state_file);
+    }
+
+    atari_machine.set_screenshots(&args.screenshot, args.screenshot_stretch);
+
+    if let Some(rewind_buffer) = args.rewind_buffer {
+        atari_machine.set_rewind_budget(rewind_buffer * 1024 * 1024);
+    }
+
+    if args.load_state.is_some() && (args.replay_file.is_some() || args.record.is_some()) {
+        println!("Movies are recorded/replayed from power-on, so can't be used with a save state");
+        return;
+    }
+
+    match (&args.replay_file, &args.record) {
+        (Some(_), Some(_)) => {
+            println!("Either record or replay a movie, not both");
+            return;
+        }
+        (Some(replay_file), None) => match atari2600::movie::Movie::playback(replay_file, atari_machine.cartridge_checksum()) {
+            Ok(movie) => {
+                println!("Replaying {} frames from: {}", movie.frame_count(), replay_file);
+                atari_machine.set_movie(movie);
+            }
+            Err(e) => {
+                println!("Unable to replay {}: {}", replay_file, e);
+                return;
+            }
+        },
+        (None, Some(record)) => atari_machine.set_movie(atari2600::movie::Movie::record(record, atari_machine.cartridge_checksum())),
+        (None, None) => {}
+    }
+
+    if let Some(load_state) = &args.load_state {
+        if let Err(e) = atari_machine.load_state(load_state) {
+            println!("Unable to load state from {}: {}", load_state, e);
+            return;
+        }
+    }
+
+    if let Some(capture) = &args.capture {
+        if let Err(e) = atari_machine.start_capture(capture) {
+            println!("Unable to capture to {}: {}", capture, e);
+            return;
+        }
+    }
+
+    if let Some(trace_file) = &args.trace_file {
+        match atari2600::cpu::trace::Trace::create(trace_file, args.trace_start, args.trace_stop) {
+            Ok(trace) => atari_machine.set_trace(trace),
+            Err(e) => {
+                println!("Unable to create trace file '{}': {}", trace_file, e);
+                return;
+            }
+        }
+    }
+
+    if args.headless {
+        if args.frames.is_none() && args.stop_clock.is_none() {
+            println!("Headless mode needs a number of frames (--frames) and/or a stop clock (-s)");
+            return;
+        }
+        let headless = atari2600::headless::Headless {
+            frames: args.frames,
+            frame_file: args.dump_frame.clone(),
+            audio_file: args.dump_audio.clone(),
+            ram_file: args.dump_ram.clone(),
+        };
+        if let Err(e) = atari_machine.run_headless(&headless) {
+            println!("Unable to write the headless outputs: {}", e);
+        }
+        return;
+    }
 
     #[cfg(target_os = "emscripten")]
     {
         let mut main_loop = move || {
-            if atari2600::memory::cartridge::is_cart_ready() {
+            if emscripten::is_cart_ready() {
                 if !atari_machine.powered {
-                    atari_machine.reset(&args.cartridge_name, &atari2600::memory::cartridge::get_cart_type());
+                    atari_machine.insert_cartridge(&emscripten::get_cart_data(), &emscripten::get_cart_type());
                     atari_machine.power_atari2600();
                     false
                 } else {
@@ -139,12 +397,10 @@ fn main() {
             }
         };
 
-        use emscripten::emscripten;
-
         // After some 'static' wrangling, having the 'set_main_loop_callback' exist
         // in main appears to appease the lifetime checks.
         #[cfg(target_os = "emscripten")]
-        emscripten::set_main_loop_callback(move || {
+        emscripten::emscripten::set_main_loop_callback(move || {
             main_loop();
         });
     }